@@ -0,0 +1,236 @@
+use crate::{Colors, Fit, PageSize, Paper};
+use anyhow::Context;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// XDGの設定フォルダー（`~/.config/moocs-collect`）に置く設定ファイル
+pub const CONFIG_FILE: &str = "config.toml";
+/// カレントディレクトリか親ディレクトリに置くプロジェクトの設定ファイル
+pub const PROJECT_CONFIG_FILE: &str = "moocs-collect.toml";
+/// `default_profile`がないときに使うプロファイル
+pub const DEFAULT_PROFILE: &str = "default";
+
+const CONFIG_DIR: &str = "moocs-collect";
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub default_profile: Option<String>,
+    pub profiles: HashMap<String, Profile>,
+}
+
+/// 名前付きのプロファイル（省略した項目はコマンドラインの既定値）
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// ダウンロード先ディレクトリ（`~/`で始まるとホームディレクトリから）
+    pub path: Option<PathBuf>,
+    pub year: Option<u32>,
+    /// 保存するファイルのパスのテンプレート
+    pub filename: Option<String>,
+    /// 同時に取得・保存するページ数
+    pub concurrency: Option<usize>,
+    pub username: Option<String>,
+    pub user_agent: Option<String>,
+    pub pdf: PdfProfile,
+}
+
+/// PDFの設定（項目名はコマンドラインのオプションと同じ）
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct PdfProfile {
+    pub handout: Option<usize>,
+    pub paper: Option<Paper>,
+    pub notes: Option<bool>,
+    pub pdfa: Option<bool>,
+    pub tagged: Option<bool>,
+    pub lang: Option<String>,
+    pub attach_svg: Option<bool>,
+    pub optimize_images: Option<bool>,
+    pub font_dirs: Vec<PathBuf>,
+    pub page_size: Option<PageSize>,
+    pub fit: Option<Fit>,
+    pub margin: Option<f32>,
+    pub background: Option<String>,
+    pub colors: Option<Colors>,
+    pub header: Option<String>,
+    pub footer: Option<String>,
+    pub watermark: Option<String>,
+}
+
+impl Config {
+    /// 設定ファイルを読み込む
+    ///
+    /// `path`を省略すると、XDGの設定ファイルにプロジェクトの設定ファイルを重ねる。
+    /// プロジェクトの設定ファイルはカレントディレクトリから親へ辿って最初に見つかったもの。
+    /// どちらもなければ空の設定になる。
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let paths = match path {
+            Some(path) => vec![path.to_path_buf()],
+            None => default_paths()
+                .into_iter()
+                .filter(|path| path.is_file())
+                .collect(),
+        };
+
+        let mut table = toml::Table::new();
+        for path in paths {
+            let text = fs::read_to_string(&path)
+                .with_context(|| format!("設定ファイル{}を読み込めません", path.display()))?;
+            let overlay = text.parse::<toml::Table>().with_context(|| {
+                format!("設定ファイル{}の形式が正しくありません", path.display())
+            })?;
+            merge(&mut table, overlay);
+        }
+        Self::from_table(table)
+    }
+
+    fn from_table(table: toml::Table) -> anyhow::Result<Self> {
+        toml::Value::Table(table)
+            .try_into()
+            .context("設定ファイルの内容が正しくありません")
+    }
+
+    /// 名前を省略すると`default_profile`、それもなければ`default`のプロファイル
+    pub fn profile(&self, name: Option<&str>) -> anyhow::Result<Profile> {
+        match name.or(self.default_profile.as_deref()) {
+            Some(name) => self
+                .profiles
+                .get(name)
+                .cloned()
+                .with_context(|| format!("プロファイル{name}が設定ファイルにありません")),
+            None => Ok(self
+                .profiles
+                .get(DEFAULT_PROFILE)
+                .cloned()
+                .unwrap_or_default()),
+        }
+    }
+}
+
+impl Profile {
+    pub fn path(&self) -> Option<PathBuf> {
+        let path = self.path.as_ref()?;
+        match (path.strip_prefix("~"), dirs::home_dir()) {
+            (Ok(rest), Some(home)) => Some(home.join(rest)),
+            _ => Some(path.clone()),
+        }
+    }
+}
+
+fn default_paths() -> Vec<PathBuf> {
+    let mut paths = vec![];
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(dirs::config_dir);
+    if let Some(dir) = config_dir {
+        paths.push(dir.join(CONFIG_DIR).join(CONFIG_FILE));
+    }
+    if let Ok(dir) = env::current_dir() {
+        let project = dir
+            .ancestors()
+            .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+            .find(|path| path.is_file());
+        paths.extend(project);
+    }
+    paths
+}
+
+// テーブルは項目ごとに重ね、それ以外の値は後の設定で置き換える
+fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match value {
+            toml::Value::Table(overlay) if base.get(&key).is_some_and(toml::Value::is_table) => {
+                if let Some(toml::Value::Table(base)) = base.get_mut(&key) {
+                    merge(base, overlay);
+                }
+            }
+            value => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> toml::Table {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn test_project_config_overrides_fields() {
+        let mut table = parse(
+            r#"
+            default_profile = "work"
+
+            [profiles.work]
+            path = "~/moocs"
+            year = 2024
+            username = "s1f102400000"
+
+            [profiles.work.pdf]
+            handout = 4
+            paper = "letter"
+            "#,
+        );
+        merge(
+            &mut table,
+            parse(
+                r#"
+                [profiles.work]
+                year = 2025
+
+                [profiles.work.pdf]
+                page_size = "16x9"
+                "#,
+            ),
+        );
+        let config = Config::from_table(table).unwrap();
+        let profile = config.profile(None).unwrap();
+
+        assert_eq!(profile.year, Some(2025));
+        assert_eq!(profile.username.as_deref(), Some("s1f102400000"));
+        assert_eq!(profile.pdf.handout, Some(4));
+        assert!(matches!(profile.pdf.paper, Some(Paper::Letter)));
+        assert!(matches!(profile.pdf.page_size, Some(PageSize::Widescreen)));
+        assert_eq!(
+            profile.path(),
+            dirs::home_dir().map(|home| home.join("moocs"))
+        );
+    }
+
+    #[test]
+    fn test_profile_selection() {
+        let config = Config::from_table(parse(
+            r#"
+            [profiles.default]
+            concurrency = 2
+
+            [profiles.print.pdf]
+            colors = "grayscale"
+            "#,
+        ))
+        .unwrap();
+
+        assert_eq!(config.profile(None).unwrap().concurrency, Some(2));
+        assert!(matches!(
+            config.profile(Some("print")).unwrap().pdf.colors,
+            Some(Colors::Grayscale)
+        ));
+        assert!(config.profile(Some("missing")).is_err());
+        assert!(Config::default().profile(None).unwrap().year.is_none());
+    }
+
+    #[test]
+    fn test_unknown_fields_are_rejected() {
+        assert!(Config::from_table(parse("[profiles.default]\nyaer = 2024")).is_err());
+    }
+}
@@ -0,0 +1,189 @@
+use clap::ValueEnum;
+use collect::{Collect, MoocsUrl, Year};
+use dialoguer::console::{measure_text_width, pad_str, Alignment};
+use serde::Serialize;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ListKind {
+    Courses,
+    Lectures,
+    Pages,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CourseRow {
+    key: String,
+    year: u32,
+    name: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct LectureRow {
+    key: String,
+    course: String,
+    group: String,
+    name: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PageRow {
+    key: String,
+    course: String,
+    lecture: String,
+    name: String,
+    slides: usize,
+}
+
+// JSONは種類を問わず行の配列として出力する
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum Rows {
+    Courses(Vec<CourseRow>),
+    Lectures(Vec<LectureRow>),
+    Pages(Vec<PageRow>),
+}
+
+impl Rows {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub fn to_table(&self) -> String {
+        match self {
+            Self::Courses(rows) => table(
+                &["キー", "科目"],
+                rows.iter()
+                    .map(|row| vec![row.key.clone(), row.name.clone()]),
+            ),
+            Self::Lectures(rows) => table(
+                &["キー", "グループ", "授業"],
+                rows.iter()
+                    .map(|row| vec![row.key.clone(), row.group.clone(), row.name.clone()]),
+            ),
+            Self::Pages(rows) => table(
+                &["キー", "ページ", "スライド"],
+                rows.iter()
+                    .map(|row| vec![row.key.clone(), row.name.clone(), row.slides.to_string()]),
+            ),
+        }
+    }
+}
+
+// 全角文字を含んでも列が揃うように表示幅で埋める
+fn table(headers: &[&str], rows: impl Iterator<Item = Vec<String>>) -> String {
+    let rows = rows.collect::<Vec<_>>();
+    let widths = headers
+        .iter()
+        .enumerate()
+        .map(|(i, header)| {
+            rows.iter()
+                .map(|row| measure_text_width(&row[i]))
+                .fold(measure_text_width(header), usize::max)
+        })
+        .collect::<Vec<_>>();
+
+    let line = |cells: &[String]| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| pad_str(cell, width, Alignment::Left, None))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    let headers = headers
+        .iter()
+        .map(|header| header.to_string())
+        .collect::<Vec<_>>();
+    let separator = widths
+        .iter()
+        .map(|&width| "-".repeat(width))
+        .collect::<Vec<_>>();
+    let mut lines = vec![line(&headers), line(&separator)];
+    lines.extend(rows.iter().map(|row| line(row)));
+    lines.join("\n")
+}
+
+// 絞り込みのキーに年度が含まれていれば--yearより優先する
+pub async fn list(
+    collect: &Collect,
+    kind: ListKind,
+    filter: Option<&MoocsUrl>,
+    year: Option<Year>,
+) -> anyhow::Result<Rows> {
+    let year = filter
+        .map(|filter| filter.course_key().year.clone())
+        .or(year);
+    let courses = collect
+        .get_courses(year)
+        .await?
+        .into_iter()
+        .filter(|course| filter.map_or(true, |filter| filter.course_key() == &course.key))
+        .collect::<Vec<_>>();
+
+    if let ListKind::Courses = kind {
+        let rows = courses
+            .iter()
+            .map(|course| CourseRow {
+                key: course.key.to_string(),
+                year: course.key.year.value(),
+                name: course.display_name().to_string(),
+            })
+            .collect();
+        return Ok(Rows::Courses(rows));
+    }
+
+    let lecture_filter = filter.and_then(MoocsUrl::lecture_key);
+    let page_filter = filter.and_then(MoocsUrl::page_key);
+    let mut lectures = vec![];
+    let mut pages = vec![];
+    for course in &courses {
+        for group in collect.get_lecture_groups(&course.key).await? {
+            for lecture in &group.lectures {
+                if lecture_filter.is_some_and(|key| key != &lecture.key) {
+                    continue;
+                }
+                if let ListKind::Lectures = kind {
+                    lectures.push(LectureRow {
+                        key: lecture.key.to_string(),
+                        course: course.display_name().to_string(),
+                        group: group.display_name().to_string(),
+                        name: lecture.display_name().to_string(),
+                    });
+                    continue;
+                }
+
+                let lecture_pages = collect
+                    .get_pages(&lecture.key)
+                    .await?
+                    .into_iter()
+                    .filter(|page| page_filter.map_or(true, |key| key == &page.key))
+                    .collect::<Vec<_>>();
+                let slides = futures::future::join_all(
+                    lecture_pages
+                        .iter()
+                        .map(|page| collect.get_slides(&page.key)),
+                )
+                .await
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()?;
+                for (page, slides) in lecture_pages.iter().zip(slides) {
+                    pages.push(PageRow {
+                        key: page.key.to_string(),
+                        course: course.display_name().to_string(),
+                        lecture: lecture.display_name().to_string(),
+                        name: page.display_name().to_string(),
+                        slides: slides.len(),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(match kind {
+        ListKind::Lectures => Rows::Lectures(lectures),
+        _ => Rows::Pages(pages),
+    })
+}
@@ -1,40 +1,410 @@
-use clap::Parser;
+mod config;
+mod list;
+
+use anyhow::Context;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use collect::{
-    error::CollectError, pdf, Collect, Credentials, LecturePage, PageKey, SlideContent, Year,
+    error::CollectError, pdf, Collect, Credentials, LecturePage, MoocsUrl, PageKey, SlideContent,
+    Year,
 };
 use dialoguer::{console::Style, Input, Password, Select};
+use futures::StreamExt;
 use indicatif::{ProgressBar, ProgressStyle};
 use keyring::Entry;
 use rayon::prelude::*;
+use serde::Deserialize;
 use std::{
-    fs::create_dir_all,
+    env,
+    fs::{create_dir_all, File},
+    io::BufWriter,
     path::{Path, PathBuf},
-    sync::{Arc, LazyLock},
+    sync::Arc,
     time::Duration,
 };
 
-static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
-    reqwest::Client::builder()
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36 Edg/124.0.0.0")
-        .cookie_store(true)
-        .build().expect("Failed to create HTTP client")
-});
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36 Edg/124.0.0.0";
+// {year} {course} {group} {lecture} {slug} {page} {index}を置換し、/でフォルダーに分ける
+const FILENAME_TEMPLATE: &str = "{course}/{group} - {lecture}/{slug} - {page}";
+const DEFAULT_CONCURRENCY: usize = 8;
+
+const KEYRING_SERVICE: &str = "me.yu7400ki.moocs-collect";
+const USERNAME_ENV: &str = "MOOCS_USERNAME";
+const PASSWORD_ENV: &str = "MOOCS_PASSWORD";
 
 // Helper struct to hold slide information needed for file naming
 struct PageInfo {
+    year: u32,
     course_name: String,
     lecture_group: String,
     lecture_name: String,
     page_slug: String,
     page_title: String,
+    stamp: pdf::StampContext,
 }
 
 #[derive(Parser, Debug)]
 struct Cli {
-    #[arg(long)]
-    path: Option<PathBuf>,
-    #[arg(long)]
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// 対象年度（対話形式で科目を選ぶとき・listの一覧）
+    #[arg(long, global = true)]
     year: Option<u32>,
+    /// ユーザー名（省略時は環境変数MOOCS_USERNAME、プロファイルの順に探し、なければ入力を求める）
+    #[arg(long, global = true)]
+    username: Option<String>,
+    /// 設定ファイル（省略時は~/.config/moocs-collect/config.tomlとmoocs-collect.toml）
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// 設定ファイルのプロファイル
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    #[command(flatten)]
+    save: SaveArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// 指定した科目・授業・ページを対話なしで保存
+    Download {
+        /// MOOCsのURL、または「年度/科目/授業/ページ」形式のキー（複数指定可）
+        #[arg(required = true, value_parser = parse_target)]
+        targets: Vec<MoocsUrl>,
+    },
+    /// 科目・授業・ページの一覧を表示
+    List {
+        #[arg(value_enum)]
+        kind: list::ListKind,
+        /// 絞り込む科目・授業・ページのURLまたはキー
+        #[arg(value_parser = parse_target)]
+        filter: Option<MoocsUrl>,
+        /// JSONで出力
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+// サブコマンドの前後どちらにも指定できる
+// 指定しなかった項目はプロファイル、それもなければ既定値になる
+// オン・オフの項目は--no-で始まるオプションでプロファイルの設定を打ち消せる
+#[derive(Args, Debug)]
+#[command(next_help_heading = "保存の設定")]
+struct SaveArgs {
+    /// ダウンロード先ディレクトリ
+    #[arg(long, global = true)]
+    path: Option<PathBuf>,
+    /// 保存するファイルのパス（{year}, {course}, {group}, {lecture}, {slug}, {page}, {index}を置換）
+    #[arg(long, global = true)]
+    filename: Option<String>,
+    /// 同時に取得・保存するページ数
+    #[arg(long, global = true, value_parser = clap::value_parser!(u16).range(1..))]
+    concurrency: Option<u16>,
+    /// 配布資料形式で保存（1枚あたりのスライド数: 2, 4, 6, 9）
+    #[arg(long, global = true, value_parser = parse_slides_per_page)]
+    handout: Option<pdf::SlidesPerPage>,
+    /// 配布資料の用紙サイズ [既定: a4]
+    #[arg(long, global = true, value_enum)]
+    paper: Option<Paper>,
+    /// 配布資料にメモ用の罫線を入れる
+    #[arg(long, global = true, overrides_with = "no_notes")]
+    notes: bool,
+    #[arg(long, global = true, overrides_with = "notes", hide = true)]
+    no_notes: bool,
+    /// PDF/A-2b形式で保存
+    #[arg(
+        long,
+        global = true,
+        overrides_with = "no_pdfa",
+        conflicts_with = "handout"
+    )]
+    pdfa: bool,
+    #[arg(long, global = true, overrides_with = "pdfa", hide = true)]
+    no_pdfa: bool,
+    /// タグ付きPDF（見出し・段落・図の代替テキスト）で保存
+    #[arg(
+        long,
+        global = true,
+        overrides_with = "no_tagged",
+        conflicts_with = "handout"
+    )]
+    tagged: bool,
+    #[arg(long, global = true, overrides_with = "tagged", hide = true)]
+    no_tagged: bool,
+    /// タグ付きPDFの文書の言語 [既定: ja]
+    #[arg(long, global = true)]
+    lang: Option<String>,
+    /// 変換元のSVGをPDFに添付（後から再変換・再索引できる）
+    #[arg(long, global = true, overrides_with = "no_attach_svg", conflicts_with_all = ["pdfa", "handout"])]
+    attach_svg: bool,
+    #[arg(long, global = true, overrides_with = "attach_svg", hide = true)]
+    no_attach_svg: bool,
+    /// 追加のフォントディレクトリ（複数指定可）
+    #[arg(long, global = true)]
+    font_dir: Vec<PathBuf>,
+    /// 埋め込む画像を描画サイズに合わせて縮小・再圧縮する
+    #[arg(long, global = true, overrides_with = "no_optimize_images")]
+    optimize_images: bool,
+    #[arg(long, global = true, overrides_with = "optimize_images", hide = true)]
+    no_optimize_images: bool,
+    /// ページサイズ（nativeはスライドのサイズのまま） [既定: native]
+    #[arg(long, global = true, value_enum)]
+    page_size: Option<PageSize>,
+    /// ページへのスライドの収め方 [既定: fit]
+    #[arg(long, global = true, value_enum)]
+    fit: Option<Fit>,
+    /// ページの余白（pt） [既定: 0]
+    #[arg(long, global = true)]
+    margin: Option<f32>,
+    /// 背景色（#rrggbb）
+    #[arg(long, global = true, value_parser = parse_background)]
+    background: Option<[u8; 3]>,
+    /// 配色（print: 暗い背景のスライドを明るくする / grayscale: さらに白黒にする） [既定: original]
+    #[arg(long, global = true, value_enum)]
+    colors: Option<Colors>,
+    /// ページ上端に入れるテキスト（{course}, {lecture}, {page}, {slide}, {total}, {url}を置換）
+    #[arg(long, global = true)]
+    header: Option<String>,
+    /// ページ下端に入れるテキスト（{url}を含むと元のページへのリンクになる）
+    #[arg(long, global = true)]
+    footer: Option<String>,
+    /// 透かしを入れる（省略時は「学内限定」）
+    #[arg(
+        long,
+        global = true,
+        num_args = 0..=1,
+        default_missing_value = pdf::INTERNAL_USE_WATERMARK
+    )]
+    watermark: Option<String>,
+}
+
+impl SaveArgs {
+    fn save_options(
+        &self,
+        profile: &config::Profile,
+        client: reqwest::Client,
+    ) -> anyhow::Result<SaveOptions> {
+        let pdf = &profile.pdf;
+        let handout = match self.handout {
+            Some(handout) => Some(handout),
+            None => pdf
+                .handout
+                .map(|count| {
+                    pdf::SlidesPerPage::from_count(count)
+                        .context("handoutは2, 4, 6, 9のいずれかを指定してください")
+                })
+                .transpose()?,
+        };
+        let background = match self.background {
+            Some(background) => Some(background),
+            None => pdf
+                .background
+                .as_deref()
+                .map(parse_background)
+                .transpose()
+                .map_err(anyhow::Error::msg)?,
+        };
+        let pdfa = flag(self.pdfa, self.no_pdfa).or(pdf.pdfa).unwrap_or(false);
+        let tagged = flag(self.tagged, self.no_tagged)
+            .or(pdf.tagged)
+            .unwrap_or(false);
+        let attach_svg = flag(self.attach_svg, self.no_attach_svg)
+            .or(pdf.attach_svg)
+            .unwrap_or(false);
+
+        // コマンドラインの組み合わせはclapで弾くが、プロファイルと混ざる場合はここで確かめる
+        anyhow::ensure!(
+            !((pdfa || tagged) && handout.is_some()),
+            "PDF/A-2b・タグ付きPDFと配布資料は同時に指定できません"
+        );
+        anyhow::ensure!(
+            !(attach_svg && (pdfa || handout.is_some())),
+            "SVGの添付はPDF/A-2b・配布資料と同時に指定できません"
+        );
+
+        let font_dirs = if self.font_dir.is_empty() {
+            pdf.font_dirs.clone()
+        } else {
+            self.font_dir.clone()
+        };
+
+        let converter = pdf::Converter::new(pdf::ConvertConfig {
+            pdfa,
+            fonts: pdf::FontConfig {
+                font_dirs,
+                ..pdf::FontConfig::default()
+            },
+            page: pdf::PageConfig {
+                size: self
+                    .page_size
+                    .or(pdf.page_size)
+                    .unwrap_or(PageSize::Native)
+                    .into(),
+                fit: self.fit.or(pdf.fit).unwrap_or(Fit::Fit).into(),
+                margins: pdf::Margins::uniform(self.margin.or(pdf.margin).unwrap_or(0.0)),
+                background,
+            },
+            stamp: pdf::StampConfig {
+                header: self.header.clone().or_else(|| pdf.header.clone()),
+                footer: self.footer.clone().or_else(|| pdf.footer.clone()),
+                watermark: self.watermark.clone().or_else(|| pdf.watermark.clone()),
+                ..pdf::StampConfig::default()
+            },
+            colors: self
+                .colors
+                .or(pdf.colors)
+                .unwrap_or(Colors::Original)
+                .into(),
+            tags: tagged.then(|| pdf::TagConfig {
+                lang: self
+                    .lang
+                    .clone()
+                    .or_else(|| pdf.lang.clone())
+                    .unwrap_or_else(|| pdf::TagConfig::default().lang),
+            }),
+            attach_sources: attach_svg,
+        })?;
+        let optimize_images = flag(self.optimize_images, self.no_optimize_images)
+            .or(pdf.optimize_images)
+            .unwrap_or(false);
+        Ok(SaveOptions {
+            client,
+            filename: self
+                .filename
+                .clone()
+                .or_else(|| profile.filename.clone())
+                .unwrap_or_else(|| FILENAME_TEMPLATE.to_string()),
+            concurrency: self
+                .concurrency
+                .map(usize::from)
+                .or(profile.concurrency)
+                .unwrap_or(DEFAULT_CONCURRENCY)
+                .max(1),
+            preprocessor: pdf::PreProcessor::new(pdf::PreProcessConfig {
+                optimize_images: optimize_images.then(pdf::ImageOptimizeConfig::default),
+                ..pdf::PreProcessConfig::default()
+            }),
+            converter,
+            handout: handout.map(|slides_per_page| pdf::HandoutConfig {
+                slides_per_page,
+                paper: self.paper.or(pdf.paper).unwrap_or(Paper::A4).into(),
+                note_lines: flag(self.notes, self.no_notes)
+                    .or(pdf.notes)
+                    .unwrap_or(false),
+                ..pdf::HandoutConfig::default()
+            }),
+        })
+    }
+}
+
+// 保存時の設定
+struct SaveOptions {
+    client: reqwest::Client,
+    filename: String,
+    concurrency: usize,
+    // 画像キャッシュを全ページで共有し、スライド間で同じ画像を一度だけダウンロードする
+    preprocessor: pdf::PreProcessor,
+    // フォントの読み込みは重いため、一度だけ作成して全ページで共有する
+    converter: pdf::Converter,
+    handout: Option<pdf::HandoutConfig>,
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+enum Paper {
+    A4,
+    Letter,
+}
+
+impl From<Paper> for pdf::PaperSize {
+    fn from(paper: Paper) -> Self {
+        match paper {
+            Paper::A4 => pdf::PaperSize::A4,
+            Paper::Letter => pdf::PaperSize::Letter,
+        }
+    }
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+enum PageSize {
+    Native,
+    A4,
+    Letter,
+    #[value(name = "16x9", alias = "16:9")]
+    #[serde(rename = "16x9", alias = "16:9")]
+    Widescreen,
+}
+
+impl From<PageSize> for pdf::PageSize {
+    fn from(size: PageSize) -> Self {
+        match size {
+            PageSize::Native => pdf::PageSize::Native,
+            PageSize::A4 => pdf::PageSize::Landscape(pdf::PaperSize::A4),
+            PageSize::Letter => pdf::PageSize::Landscape(pdf::PaperSize::Letter),
+            PageSize::Widescreen => pdf::PageSize::Widescreen,
+        }
+    }
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+enum Fit {
+    Fit,
+    Fill,
+}
+
+impl From<Fit> for pdf::FitMode {
+    fn from(fit: Fit) -> Self {
+        match fit {
+            Fit::Fit => pdf::FitMode::Fit,
+            Fit::Fill => pdf::FitMode::Fill,
+        }
+    }
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+enum Colors {
+    Original,
+    Print,
+    Grayscale,
+}
+
+impl From<Colors> for pdf::ColorMode {
+    fn from(colors: Colors) -> Self {
+        match colors {
+            Colors::Original => pdf::ColorMode::Original,
+            Colors::Print => pdf::ColorMode::Print,
+            Colors::Grayscale => pdf::ColorMode::Grayscale,
+        }
+    }
+}
+
+fn parse_target(s: &str) -> Result<MoocsUrl, String> {
+    MoocsUrl::parse_moocs_url(s)
+        .or_else(|_| MoocsUrl::parse_moocs_url(&format!("courses/{}", s.trim_start_matches('/'))))
+        .map_err(|_| {
+            "MOOCsのURLまたは「年度/科目/授業/ページ」形式のキーを指定してください".to_string()
+        })
+}
+
+// --xと--no-xのうち後に指定した方（どちらもなければNone）
+fn flag(yes: bool, no: bool) -> Option<bool> {
+    match (yes, no) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_background(s: &str) -> Result<[u8; 3], String> {
+    pdf::parse_color(s).ok_or_else(|| "#rrggbb形式で指定してください".to_string())
+}
+
+fn parse_slides_per_page(s: &str) -> Result<pdf::SlidesPerPage, String> {
+    s.parse::<usize>()
+        .ok()
+        .and_then(pdf::SlidesPerPage::from_count)
+        .ok_or_else(|| "2, 4, 6, 9のいずれかを指定してください".to_string())
 }
 
 struct Spinner {
@@ -88,27 +458,57 @@ async fn get_page_info(collect: &Collect, page_key: &PageKey) -> anyhow::Result<
     };
 
     Ok(PageInfo {
+        year: course.key.year.value(),
         course_name: course.display_name().to_string(),
         lecture_group,
         lecture_name: lecture.display_name().to_string(),
         page_slug: page.key.slug.value().to_string(),
         page_title: page.display_name().to_string(),
+        stamp: pdf::StampContext::new(&course, &lecture, &page),
     })
 }
 
-fn slide_dir_from_info(info: &PageInfo) -> String {
-    format!(
-        "{}/{} - {}",
-        sanitize_filename(&info.course_name),
-        sanitize_filename(&info.lecture_group),
-        sanitize_filename(&info.lecture_name)
-    )
+// 複数のスライドがあるページで{index}がなければ末尾に「 (番号)」を付ける
+fn slide_path(template: &str, info: &PageInfo, index: Option<usize>) -> PathBuf {
+    let template = match index {
+        Some(_) if !template.contains("{index}") => format!("{template} ({{index}})"),
+        _ => template.to_string(),
+    };
+    let values = [
+        ("{year}", info.year.to_string()),
+        ("{course}", info.course_name.clone()),
+        ("{group}", info.lecture_group.clone()),
+        ("{lecture}", info.lecture_name.clone()),
+        ("{slug}", info.page_slug.clone()),
+        ("{page}", info.page_title.clone()),
+        (
+            "{index}",
+            index.map(|i| (i + 1).to_string()).unwrap_or_default(),
+        ),
+    ];
+
+    let mut segments = template
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| {
+            values
+                .iter()
+                .fold(segment.to_string(), |segment, (key, value)| {
+                    segment.replace(key, &sanitize_filename(value))
+                })
+        })
+        .collect::<Vec<_>>();
+    if let Some(last) = segments.last_mut() {
+        last.push_str(".pdf");
+    }
+    segments.into_iter().collect()
 }
 
 async fn save_slides<P: AsRef<Path> + Sync>(
     collect: &Collect,
     slide_contents: &[SlideContent],
     path: P,
+    options: &SaveOptions,
 ) -> anyhow::Result<()> {
     if slide_contents.is_empty() {
         return Ok(());
@@ -117,40 +517,60 @@ async fn save_slides<P: AsRef<Path> + Sync>(
     let path = path.as_ref();
 
     let page_info = get_page_info(collect, &slide_contents[0].page_key).await?;
-    let dir = slide_dir_from_info(&page_info);
-    let path = path.join(&dir);
-    create_dir_all(&path)?;
 
-    let preprocessor = pdf::PreProcessor::default();
+    let converter = options.converter.with_context(page_info.stamp.clone());
+    let handout = options.handout.as_ref().map(|config| pdf::HandoutConfig {
+        header: Some(pdf::HandoutHeader {
+            course: page_info.course_name.clone(),
+            lecture: page_info.lecture_name.clone(),
+        }),
+        ..config.clone()
+    });
+    let preprocessor = &options.preprocessor;
 
     let slide_contents = slide_contents
         .iter()
-        .map(|content| preprocessor.preprocess(&CLIENT, content))
+        .map(|content| preprocessor.preprocess_with_report(&options.client, content))
         .collect::<Vec<_>>();
     let slide_contents = futures::future::join_all(slide_contents)
         .await
         .into_iter()
+        .map(|result| {
+            result.map(|(content, report)| {
+                for warning in &report.warnings {
+                    eprintln!("警告: {warning}");
+                }
+                content
+            })
+        })
         .collect::<Result<Vec<_>, _>>()?;
 
     slide_contents.par_iter().enumerate().try_for_each(
         |(i, content)| -> Result<(), anyhow::Error> {
-            let filename = match slide_contents.len() {
-                1 => format!(
-                    "{} - {}.pdf",
-                    sanitize_filename(&page_info.page_slug),
-                    sanitize_filename(&page_info.page_title)
-                ),
-                _ => format!(
-                    "{} - {} ({}).pdf",
-                    sanitize_filename(&page_info.page_slug),
-                    sanitize_filename(&page_info.page_title),
-                    i + 1
-                ),
+            let index = (slide_contents.len() > 1).then_some(i);
+            let file_path = path.join(slide_path(&options.filename, &page_info, index));
+            if let Some(dir) = file_path.parent() {
+                create_dir_all(dir)?;
+            }
+            let report = match &handout {
+                Some(config) => {
+                    let (pdf, report) = converter.convert_with_report(content)?;
+                    pdf::handout(pdf, config)?.save(&file_path)?;
+                    report
+                }
+                // ページごとに書き出してメモリ使用量を抑える
+                None => {
+                    let output = BufWriter::new(File::create(&file_path)?);
+                    converter.write_to(content, output, &pdf::StreamConfig::default())?
+                }
             };
-
-            let mut pdf = pdf::convert(content)?;
-            let file_path = path.join(&filename);
-            pdf.save(&file_path)?;
+            if !report.is_empty() {
+                let characters = report.characters().into_iter().collect::<String>();
+                eprintln!(
+                    "警告: {} に表示できない文字があります: {characters}",
+                    file_path.display()
+                );
+            }
             Ok(())
         },
     )
@@ -160,8 +580,11 @@ async fn save_slides_from_pages<P: AsRef<Path> + Sync>(
     collect: &Collect,
     pages: &[LecturePage],
     path: P,
+    options: &SaveOptions,
 ) -> anyhow::Result<()> {
-    let slides = futures::future::join_all(pages.iter().map(|page| collect.get_slides(&page.key)))
+    let slides = futures::stream::iter(pages.iter().map(|page| collect.get_slides(&page.key)))
+        .buffered(options.concurrency)
+        .collect::<Vec<_>>()
         .await
         .into_iter()
         .collect::<Result<Vec<_>, _>>()?
@@ -173,17 +596,21 @@ async fn save_slides_from_pages<P: AsRef<Path> + Sync>(
         futures::future::join_all(slides.iter().map(|slide| collect.get_slide_content(slide)))
     });
 
-    let contents = futures::future::join_all(slide_contents)
+    let contents = futures::stream::iter(slide_contents)
+        .buffered(options.concurrency)
+        .collect::<Vec<_>>()
         .await
         .into_iter()
         .map(|slides| slides.into_iter().collect::<Result<Vec<_>, _>>())
         .collect::<Result<Vec<_>, _>>()?;
 
-    futures::future::join_all(
+    futures::stream::iter(
         contents
             .iter()
-            .map(|contents| save_slides(collect, contents, &path)),
+            .map(|contents| save_slides(collect, contents, &path, options)),
     )
+    .buffer_unordered(options.concurrency)
+    .collect::<Vec<_>>()
     .await
     .into_iter()
     .collect::<Result<(), _>>()?;
@@ -191,23 +618,102 @@ async fn save_slides_from_pages<P: AsRef<Path> + Sync>(
     Ok(())
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let args = Cli::parse();
+async fn save_page<P: AsRef<Path> + Sync>(
+    collect: &Collect,
+    page_key: &PageKey,
+    path: P,
+    options: &SaveOptions,
+) -> anyhow::Result<()> {
+    let slides = collect.get_slides(page_key).await?;
+    let content =
+        futures::future::join_all(slides.iter().map(|slide| collect.get_slide_content(slide)))
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
 
-    let collect = Collect::from(Arc::new(CLIENT.clone()));
+    save_slides(collect, &content, path, options).await
+}
 
-    let username: String = Input::new().with_prompt("ユーザー名").interact_text()?;
-    let entry = Entry::new("me.yu7400ki.moocs-collect", &username)?;
-    let password: String = match entry.get_password() {
+// 科目は全ての授業、授業は全てのページを保存する
+async fn download(
+    collect: &Collect,
+    targets: &[MoocsUrl],
+    path: &Path,
+    options: &SaveOptions,
+) -> anyhow::Result<()> {
+    for target in targets {
+        match target {
+            MoocsUrl::Course { course_key } => {
+                for lecture in collect.get_lectures(course_key).await? {
+                    let pages = collect.get_pages(&lecture.key).await?;
+                    save_slides_from_pages(collect, &pages, path, options).await?;
+                }
+            }
+            MoocsUrl::Lecture { lecture_key } => {
+                let pages = collect.get_pages(lecture_key).await?;
+                save_slides_from_pages(collect, &pages, path, options).await?;
+            }
+            MoocsUrl::Page { page_key } => save_page(collect, page_key, path, options).await?,
+        }
+    }
+    Ok(())
+}
+
+// 引数・環境変数・キーチェーンの順に探し、対話形式のときだけ入力を求める
+fn get_credentials(
+    username: Option<&str>,
+    profile_username: Option<&str>,
+    interactive: bool,
+) -> anyhow::Result<(Credentials, Entry)> {
+    let username = match username
+        .map(str::to_string)
+        .or_else(|| env::var(USERNAME_ENV).ok())
+        .or_else(|| profile_username.map(str::to_string))
+    {
+        Some(username) => username,
+        None if interactive => Input::new().with_prompt("ユーザー名").interact_text()?,
+        None => {
+            anyhow::bail!(
+                "ユーザー名を--username、環境変数{USERNAME_ENV}、またはプロファイルで指定してください"
+            )
+        }
+    };
+    let entry = Entry::new(KEYRING_SERVICE, &username)?;
+    let password = match env::var(PASSWORD_ENV).or_else(|_| entry.get_password()) {
         Ok(password) => password,
-        Err(_) => {
+        Err(_) if interactive => {
             let password: String = Password::new().with_prompt("パスワード").interact()?;
             entry.set_password(&password).ok();
             password
         }
+        Err(_) => anyhow::bail!(
+            "パスワードを環境変数{PASSWORD_ENV}で指定するか、対話形式で一度ログインしてください"
+        ),
     };
-    let credentials = Credentials { username, password };
+    Ok((Credentials { username, password }, entry))
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Cli::parse();
+
+    let config = config::Config::load(args.config.as_deref())?;
+    let profile = config.profile(args.profile.as_deref())?;
+
+    let client = reqwest::Client::builder()
+        .user_agent(profile.user_agent.as_deref().unwrap_or(USER_AGENT))
+        .cookie_store(true)
+        .build()?;
+    let collect = Collect::from(Arc::new(client.clone()));
+
+    // サブコマンドはcronやCIから実行するため入力を求めない
+    let interactive = args.command.is_none();
+    let (credentials, entry) = get_credentials(
+        args.username.as_deref(),
+        profile.username.as_deref(),
+        interactive,
+    )?;
+    let year = args.year.or(profile.year).map(Year::new).transpose()?;
 
     let result = {
         let s = Spinner::new();
@@ -229,7 +735,33 @@ async fn main() -> anyhow::Result<()> {
         Ok(_) => {}
     }
 
-    let path = args.path.unwrap_or_else(|| PathBuf::from("."));
+    if let Some(Command::List { kind, filter, json }) = &args.command {
+        let rows = {
+            let s = Spinner::new();
+            s.set_message("一覧を取得中...");
+            list::list(&collect, *kind, filter.as_ref(), year).await?
+        };
+        if *json {
+            println!("{}", rows.to_json()?);
+        } else {
+            println!("{}", rows.to_table());
+        }
+        return Ok(());
+    }
+
+    let options = args.save.save_options(&profile, client)?;
+    let path = args
+        .save
+        .path
+        .or_else(|| profile.path())
+        .unwrap_or_else(|| PathBuf::from("."));
+
+    if let Some(Command::Download { targets }) = &args.command {
+        let s = Spinner::new();
+        s.set_message("保存中...");
+        return download(&collect, targets, &path, &options).await;
+    }
+
     let underline = Style::new().underlined();
     let progress_template =
         ProgressStyle::with_template("{percent:>3}% {bar:40} {pos:>2}/{len:2} {msg}").unwrap();
@@ -237,7 +769,6 @@ async fn main() -> anyhow::Result<()> {
     let courses = {
         let s = Spinner::new();
         s.set_message("科目を取得中...");
-        let year = args.year.map(Year::new).transpose()?;
         collect.get_courses(year).await?
     };
 
@@ -261,7 +792,7 @@ async fn main() -> anyhow::Result<()> {
             for lecture in lectures.iter() {
                 bar.set_message(lecture.display_name().to_string());
                 let pages = collect.get_pages(&lecture.key).await?;
-                save_slides_from_pages(&collect, &pages, &path).await?;
+                save_slides_from_pages(&collect, &pages, &path, &options).await?;
                 bar.inc(1);
             }
             bar.finish();
@@ -295,7 +826,7 @@ async fn main() -> anyhow::Result<()> {
         for lecture in lectures.iter() {
             bar.set_message(lecture.display_name().to_string());
             let pages = collect.get_pages(&lecture.key).await?;
-            save_slides_from_pages(&collect, &pages, &path).await?;
+            save_slides_from_pages(&collect, &pages, &path, &options).await?;
             bar.inc(1);
         }
         bar.finish();
@@ -333,7 +864,7 @@ async fn main() -> anyhow::Result<()> {
     if page_selection == 0 {
         let s = Spinner::new();
         s.set_message("保存中...");
-        save_slides_from_pages(&collect, &pages, &path).await?;
+        save_slides_from_pages(&collect, &pages, &path, &options).await?;
         return Ok(());
     }
 
@@ -341,14 +872,7 @@ async fn main() -> anyhow::Result<()> {
 
     let s = Spinner::new();
     s.set_message("保存中...");
-    let slides = collect.get_slides(&page.key).await?;
-    let content =
-        futures::future::join_all(slides.iter().map(|slide| collect.get_slide_content(slide)))
-            .await
-            .into_iter()
-            .collect::<Result<Vec<_>, _>>()?;
-
-    save_slides(&collect, &content, &path).await?;
+    save_page(&collect, &page.key, &path, &options).await?;
 
     Ok(())
 }
@@ -47,6 +47,46 @@ pub struct DownloadParams {
     page_slug: String,
 }
 
+/// 設定画面の配布資料設定（store.jsonの`settings.handout`）
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HandoutSettings {
+    enabled: bool,
+    slides_per_page: usize,
+    paper: String,
+    note_lines: bool,
+    slide_numbers: bool,
+    /// Missing in settings saved before the print profile was added
+    #[serde(default)]
+    colors: String,
+}
+
+/// 設定画面のページ設定（store.jsonの`settings.page`）
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageSettings {
+    size: String,
+    fit: String,
+    margin: f32,
+    background: Option<String>,
+}
+
+/// 設定画面のヘッダー・フッター設定（store.jsonの`settings.stamp`）
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StampSettings {
+    header: String,
+    footer: String,
+    watermark: bool,
+}
+
+/// 設定画面のアクセシビリティ設定（store.jsonの`settings.accessibility`）
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccessibilitySettings {
+    tagged: bool,
+}
+
 #[tauri::command]
 pub async fn download_slides(
     app: tauri::AppHandle,
@@ -78,6 +118,21 @@ pub async fn download_slides(
     let page_key = PageKey::new(lecture_key, page_slug_obj);
 
     let download_dir = get_download_dir(&app)?;
+    let handout = get_handout_config(&app)?;
+    let page = get_page_config(&app)?;
+    let stamp = get_stamp_config(&app)?;
+    let colors = get_color_mode(&app)?;
+    // Handouts lay slides out as form XObjects, which drops the marked content tags refer to
+    let tags = match handout {
+        Some(_) => None,
+        None => get_tag_config(&app)?,
+    };
+    let converter = collect_state.converter.with_config(|config| {
+        config.page = page;
+        config.stamp = stamp;
+        config.colors = colors;
+        config.tags = tags;
+    });
 
     // Get page info for directory structure
     let page_info = collect.get_page_info(&page_key).await?;
@@ -85,6 +140,11 @@ pub async fn download_slides(
     let course_info = collect
         .get_course_info(&lecture_info.key.course_key)
         .await?;
+    let converter = converter.with_context(pdf::StampContext::new(
+        &course_info,
+        &lecture_info,
+        &page_info,
+    ));
 
     let lecture_dir = get_lecture_dir_from_info(
         &download_dir,
@@ -103,21 +163,40 @@ pub async fn download_slides(
 
     // Preprocess slide contents using shared HTTP client
     // This embeds images and text into SVGs for better PDF generation
-    let preprocessor = PreProcessor::default();
+    let preprocessor = PreProcessor::new(get_preprocess_config(&app)?)
+        .with_cache(collect_state.image_cache.clone());
     let preprocessed_contents = futures::future::join_all(
         contents
             .iter()
-            .map(|content| preprocessor.preprocess(&collect_state.client, content)),
+            .map(|content| preprocessor.preprocess_with_report(&collect_state.client, content)),
     )
     .await
     .into_iter()
+    .map(|result| {
+        result.map(|(content, report)| {
+            // Missing images are replaced with placeholders; the PDF is still written
+            for warning in &report.warnings {
+                log::warn!("{warning}");
+            }
+            content
+        })
+    })
     .collect::<Result<Vec<_>, PdfConversionError>>()?;
 
+    let handout = handout.map(|config| pdf::HandoutConfig {
+        header: Some(pdf::HandoutHeader {
+            course: course_info.display_name().to_string(),
+            lecture: lecture_info.display_name().to_string(),
+        }),
+        ..config
+    });
     let saved_paths = save_slides(
         &slides,
         &preprocessed_contents,
         &lecture_dir,
         page_info.display_name(),
+        &converter,
+        handout.as_ref(),
     )?;
 
     let db_pool = db_state.0.read().await;
@@ -167,6 +246,158 @@ fn get_download_dir(app: &tauri::AppHandle) -> Result<std::path::PathBuf, Downlo
     Ok(std::path::PathBuf::from(download_dir))
 }
 
+fn get_handout_config(app: &tauri::AppHandle) -> Result<Option<pdf::HandoutConfig>, DownloadError> {
+    let store = app
+        .store("store.json")
+        .map_err(|e| DownloadError::Store(format!("Failed to access store: {e}")))?;
+
+    let Some(settings) = store
+        .get("settings")
+        .and_then(|settings| settings.get("handout").cloned())
+        .and_then(|handout| serde_json::from_value::<HandoutSettings>(handout).ok())
+        .filter(|settings| settings.enabled)
+    else {
+        return Ok(None);
+    };
+
+    let slides_per_page =
+        pdf::SlidesPerPage::from_count(settings.slides_per_page).ok_or_else(|| {
+            DownloadError::InvalidInput(format!(
+                "Invalid slides per page: {}",
+                settings.slides_per_page
+            ))
+        })?;
+    let paper = match settings.paper.as_str() {
+        "letter" => pdf::PaperSize::Letter,
+        _ => pdf::PaperSize::A4,
+    };
+
+    Ok(Some(pdf::HandoutConfig {
+        slides_per_page,
+        paper,
+        note_lines: settings.note_lines,
+        slide_numbers: settings.slide_numbers,
+        header: None,
+    }))
+}
+
+fn get_preprocess_config(app: &tauri::AppHandle) -> Result<pdf::PreProcessConfig, DownloadError> {
+    let store = app
+        .store("store.json")
+        .map_err(|e| DownloadError::Store(format!("Failed to access store: {e}")))?;
+
+    let optimize_images = store
+        .get("settings")
+        .and_then(|settings| settings.get("optimizeImages").cloned())
+        .and_then(|optimize_images| optimize_images.as_bool())
+        .unwrap_or(false);
+
+    Ok(pdf::PreProcessConfig {
+        optimize_images: optimize_images.then(pdf::ImageOptimizeConfig::default),
+        ..pdf::PreProcessConfig::default()
+    })
+}
+
+/// Print-friendly colours are part of the handout profile
+fn get_color_mode(app: &tauri::AppHandle) -> Result<pdf::ColorMode, DownloadError> {
+    let store = app
+        .store("store.json")
+        .map_err(|e| DownloadError::Store(format!("Failed to access store: {e}")))?;
+
+    let colors = store
+        .get("settings")
+        .and_then(|settings| settings.get("handout").cloned())
+        .and_then(|handout| serde_json::from_value::<HandoutSettings>(handout).ok())
+        .filter(|settings| settings.enabled)
+        .map(|settings| settings.colors);
+
+    Ok(match colors.as_deref() {
+        Some("print") => pdf::ColorMode::Print,
+        Some("grayscale") => pdf::ColorMode::Grayscale,
+        _ => pdf::ColorMode::Original,
+    })
+}
+
+fn get_page_config(app: &tauri::AppHandle) -> Result<pdf::PageConfig, DownloadError> {
+    let store = app
+        .store("store.json")
+        .map_err(|e| DownloadError::Store(format!("Failed to access store: {e}")))?;
+
+    let Some(settings) = store
+        .get("settings")
+        .and_then(|settings| settings.get("page").cloned())
+        .and_then(|page| serde_json::from_value::<PageSettings>(page).ok())
+    else {
+        return Ok(pdf::PageConfig::default());
+    };
+
+    let size = match settings.size.as_str() {
+        "a4" => pdf::PageSize::Landscape(pdf::PaperSize::A4),
+        "letter" => pdf::PageSize::Landscape(pdf::PaperSize::Letter),
+        "16x9" => pdf::PageSize::Widescreen,
+        _ => pdf::PageSize::Native,
+    };
+    let fit = match settings.fit.as_str() {
+        "fill" => pdf::FitMode::Fill,
+        _ => pdf::FitMode::Fit,
+    };
+    let background = settings
+        .background
+        .map(|color| {
+            pdf::parse_color(&color)
+                .ok_or_else(|| DownloadError::InvalidInput(format!("Invalid color: {color}")))
+        })
+        .transpose()?;
+
+    Ok(pdf::PageConfig {
+        size,
+        fit,
+        margins: pdf::Margins::uniform(settings.margin.max(0.0)),
+        background,
+    })
+}
+
+fn get_stamp_config(app: &tauri::AppHandle) -> Result<pdf::StampConfig, DownloadError> {
+    let store = app
+        .store("store.json")
+        .map_err(|e| DownloadError::Store(format!("Failed to access store: {e}")))?;
+
+    let Some(settings) = store
+        .get("settings")
+        .and_then(|settings| settings.get("stamp").cloned())
+        .and_then(|stamp| serde_json::from_value::<StampSettings>(stamp).ok())
+    else {
+        return Ok(pdf::StampConfig::default());
+    };
+
+    // Empty templates mean the line is not stamped
+    let template = |value: String| Some(value).filter(|value| !value.trim().is_empty());
+    Ok(pdf::StampConfig {
+        header: template(settings.header),
+        footer: template(settings.footer),
+        watermark: settings
+            .watermark
+            .then(|| pdf::INTERNAL_USE_WATERMARK.to_string()),
+        ..pdf::StampConfig::default()
+    })
+}
+
+fn get_tag_config(app: &tauri::AppHandle) -> Result<Option<pdf::TagConfig>, DownloadError> {
+    let store = app
+        .store("store.json")
+        .map_err(|e| DownloadError::Store(format!("Failed to access store: {e}")))?;
+
+    let tagged = store
+        .get("settings")
+        .and_then(|settings| settings.get("accessibility").cloned())
+        .and_then(|accessibility| {
+            serde_json::from_value::<AccessibilitySettings>(accessibility).ok()
+        })
+        .is_some_and(|settings| settings.tagged);
+
+    Ok(tagged.then(pdf::TagConfig::default))
+}
+
 fn sanitize_filename(s: &str) -> String {
     #[cfg(windows)]
     const INVALID_CHARS: [char; 9] = ['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
@@ -197,6 +428,8 @@ fn save_slides<P: AsRef<std::path::Path>>(
     contents: &[SlideContent],
     lecture_path: P,
     page_title: &str,
+    converter: &pdf::Converter,
+    handout: Option<&pdf::HandoutConfig>,
 ) -> Result<Vec<PathBuf>, DownloadError> {
     assert_eq!(slides.len(), contents.len());
     let path = lecture_path.as_ref();
@@ -208,15 +441,29 @@ fn save_slides<P: AsRef<std::path::Path>>(
             1 => format!("{page_title}.pdf"),
             _ => format!("{} ({}).pdf", page_title, index + 1),
         };
-        let mut pdf = pdf::convert(content)?;
         let file_path = path.join(sanitize_filename(&filename));
-        pdf.save(&file_path).map_err(|e| {
+        let save_error = |e: std::io::Error| {
             DownloadError::Io(std::io::Error::other(format!(
                 "Failed to save PDF to {}: {}",
                 file_path.display(),
                 e
             )))
-        })?;
+        };
+        match handout {
+            Some(config) => {
+                let pdf = pdf::handout(converter.convert(content)?, config)?;
+                pdf.save(&file_path).map_err(save_error)?;
+            }
+            // Write page by page to keep memory usage bounded
+            None => {
+                let file = std::fs::File::create(&file_path).map_err(save_error)?;
+                converter.write_to(
+                    content,
+                    std::io::BufWriter::new(file),
+                    &pdf::StreamConfig::default(),
+                )?;
+            }
+        }
         saved_paths.push(file_path);
     }
 
@@ -1,5 +1,8 @@
 use crate::search::{SearchError, SearchService};
-use collect::Collect;
+use collect::{
+    pdf::{Converter, ImageCache},
+    Collect,
+};
 use reqwest::Client;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -7,6 +10,9 @@ use tokio::sync::RwLock;
 pub struct CollectState {
     pub collect: Arc<Collect>,
     pub client: Arc<Client>,
+    pub image_cache: ImageCache,
+    /// Loading system fonts is slow, so the converter is created once
+    pub converter: Arc<Converter>,
 }
 
 impl CollectState {
@@ -19,6 +25,8 @@ impl CollectState {
         Ok(Self {
             collect: Arc::new(Collect::from(client.clone())),
             client,
+            image_cache: ImageCache::default(),
+            converter: Arc::new(Converter::default()),
         })
     }
 }
@@ -0,0 +1,41 @@
+import { css } from "styled-system/css";
+import { Checkbox } from "@/components/ui/checkbox";
+import type {
+  AccessibilitySettings as AccessibilitySettingsValue,
+} from "../schemas/settings";
+
+type AccessibilitySettingsProps = {
+  value?: AccessibilitySettingsValue;
+  disabled?: boolean;
+  onChange: (value: AccessibilitySettingsValue) => void;
+};
+
+export function AccessibilitySettings({
+  value,
+  disabled = false,
+  onChange,
+}: AccessibilitySettingsProps) {
+  if (!value) {
+    return null;
+  }
+
+  return (
+    <div className={css({ display: "grid", gap: 3 })}>
+      <Checkbox
+        size="sm"
+        checked={value.tagged && !disabled}
+        disabled={disabled}
+        onCheckedChange={(details) =>
+          onChange({ ...value, tagged: details.checked === true })
+        }
+      >
+        タグ付きPDFで保存する
+      </Checkbox>
+      <p className={css({ fontSize: "sm", color: "fg.muted" })}>
+        {disabled
+          ? "配布資料形式で保存する場合はタグを付けません。"
+          : "スクリーンリーダーやリフロー表示で、見出し・本文・図の説明を読めるようになります。"}
+      </p>
+    </div>
+  );
+}
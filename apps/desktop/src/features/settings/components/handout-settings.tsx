@@ -0,0 +1,111 @@
+import { css } from "styled-system/css";
+import { Checkbox } from "@/components/ui/checkbox";
+import type {
+  HandoutSettings as HandoutSettingsValue,
+} from "../schemas/settings";
+import { OptionSelect } from "./option-select";
+
+const slidesPerPageItems = [2, 4, 6, 9].map((count) => ({
+  value: count.toString(),
+  label: `${count}枚`,
+}));
+
+const paperItems = [
+  { value: "a4", label: "A4" },
+  { value: "letter", label: "レター" },
+];
+
+const colorItems = [
+  { value: "original", label: "元の配色" },
+  { value: "print", label: "印刷向け" },
+  { value: "grayscale", label: "グレースケール" },
+];
+
+type HandoutSettingsProps = {
+  value?: HandoutSettingsValue;
+  onChange: (value: HandoutSettingsValue) => void;
+};
+
+export function HandoutSettings({ value, onChange }: HandoutSettingsProps) {
+  if (!value) {
+    return null;
+  }
+
+  const update = (patch: Partial<HandoutSettingsValue>) =>
+    onChange({ ...value, ...patch });
+
+  return (
+    <div className={css({ display: "grid", gap: 3 })}>
+      <Checkbox
+        size="sm"
+        checked={value.enabled}
+        onCheckedChange={(details) =>
+          update({ enabled: details.checked === true })
+        }
+      >
+        配布資料形式で保存する
+      </Checkbox>
+      <div
+        className={css({
+          display: "flex",
+          flexWrap: "wrap",
+          alignItems: "center",
+          gap: 3,
+        })}
+      >
+        <span>1枚あたり</span>
+        <OptionSelect
+          items={slidesPerPageItems}
+          value={value.slidesPerPage.toString()}
+          disabled={!value.enabled}
+          onChange={(count) =>
+            update({
+              slidesPerPage: Number.parseInt(
+                count,
+                10,
+              ) as HandoutSettingsValue["slidesPerPage"],
+            })
+          }
+        />
+        <span>用紙</span>
+        <OptionSelect
+          items={paperItems}
+          value={value.paper}
+          disabled={!value.enabled}
+          onChange={(paper) =>
+            update({ paper: paper as HandoutSettingsValue["paper"] })
+          }
+        />
+        <span>配色</span>
+        <OptionSelect
+          items={colorItems}
+          value={value.colors}
+          disabled={!value.enabled}
+          onChange={(colors) =>
+            update({ colors: colors as HandoutSettingsValue["colors"] })
+          }
+        />
+      </div>
+      <Checkbox
+        size="sm"
+        checked={value.noteLines}
+        disabled={!value.enabled}
+        onCheckedChange={(details) =>
+          update({ noteLines: details.checked === true })
+        }
+      >
+        メモ用の罫線を入れる
+      </Checkbox>
+      <Checkbox
+        size="sm"
+        checked={value.slideNumbers}
+        disabled={!value.enabled}
+        onCheckedChange={(details) =>
+          update({ slideNumbers: details.checked === true })
+        }
+      >
+        スライド番号を表示する
+      </Checkbox>
+    </div>
+  );
+}
@@ -0,0 +1,55 @@
+import { CheckIcon, ChevronsUpDownIcon } from "lucide-react";
+import { createListCollection, Select } from "@/components/ui/select";
+
+type OptionSelectProps = {
+  items: { value: string; label: string }[];
+  value: string;
+  disabled?: boolean;
+  onChange: (value: string) => void;
+};
+
+export function OptionSelect({
+  items,
+  value,
+  disabled,
+  onChange,
+}: OptionSelectProps) {
+  const collection = createListCollection({ items });
+
+  return (
+    <Select.Root
+      size="sm"
+      width="auto"
+      positioning={{ sameWidth: true }}
+      collection={collection}
+      value={[value]}
+      disabled={disabled}
+      onValueChange={(detail) => {
+        if (detail.value[0]) {
+          onChange(detail.value[0]);
+        }
+      }}
+    >
+      <Select.Control>
+        <Select.Trigger>
+          <Select.ValueText />
+          <ChevronsUpDownIcon />
+        </Select.Trigger>
+      </Select.Control>
+      <Select.Positioner>
+        <Select.Content>
+          <Select.ItemGroup>
+            {collection.items.map((item) => (
+              <Select.Item key={item.value} item={item}>
+                <Select.ItemText>{item.label}</Select.ItemText>
+                <Select.ItemIndicator>
+                  <CheckIcon />
+                </Select.ItemIndicator>
+              </Select.Item>
+            ))}
+          </Select.ItemGroup>
+        </Select.Content>
+      </Select.Positioner>
+    </Select.Root>
+  );
+}
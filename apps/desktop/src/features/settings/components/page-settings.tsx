@@ -0,0 +1,116 @@
+import { css } from "styled-system/css";
+import { Checkbox } from "@/components/ui/checkbox";
+import { NumberInput } from "@/components/ui/number-input";
+import type { PageSettings as PageSettingsValue } from "../schemas/settings";
+import { OptionSelect } from "./option-select";
+
+const sizeItems = [
+  { value: "native", label: "スライドのまま" },
+  { value: "a4", label: "A4（横）" },
+  { value: "letter", label: "レター（横）" },
+  { value: "16x9", label: "16:9" },
+];
+
+const fitItems = [
+  { value: "fit", label: "全体を表示" },
+  { value: "fill", label: "余白なく拡大" },
+];
+
+const defaultBackground = "#ffffff";
+
+type PageSettingsProps = {
+  value?: PageSettingsValue;
+  onChange: (value: PageSettingsValue) => void;
+};
+
+export function PageSettings({ value, onChange }: PageSettingsProps) {
+  if (!value) {
+    return null;
+  }
+
+  const update = (patch: Partial<PageSettingsValue>) =>
+    onChange({ ...value, ...patch });
+
+  return (
+    <div className={css({ display: "grid", gap: 3 })}>
+      <div
+        className={css({
+          display: "flex",
+          flexWrap: "wrap",
+          alignItems: "center",
+          gap: 3,
+        })}
+      >
+        <span>サイズ</span>
+        <OptionSelect
+          items={sizeItems}
+          value={value.size}
+          onChange={(size) =>
+            update({ size: size as PageSettingsValue["size"] })
+          }
+        />
+        <span>収め方</span>
+        <OptionSelect
+          items={fitItems}
+          value={value.fit}
+          onChange={(fit) => update({ fit: fit as PageSettingsValue["fit"] })}
+        />
+      </div>
+      <div
+        className={css({
+          display: "flex",
+          flexWrap: "wrap",
+          alignItems: "center",
+          gap: 3,
+        })}
+      >
+        <span>余白（pt）</span>
+        <NumberInput
+          size="sm"
+          width="32"
+          min={0}
+          max={144}
+          value={value.margin.toString()}
+          onValueChange={(details) => {
+            if (!Number.isNaN(details.valueAsNumber)) {
+              update({ margin: Math.max(0, details.valueAsNumber) });
+            }
+          }}
+        />
+      </div>
+      <div
+        className={css({
+          display: "flex",
+          flexWrap: "wrap",
+          alignItems: "center",
+          gap: 3,
+        })}
+      >
+        <Checkbox
+          size="sm"
+          checked={value.background !== null}
+          onCheckedChange={(details) =>
+            update({
+              background: details.checked === true ? defaultBackground : null,
+            })
+          }
+        >
+          背景色を塗る
+        </Checkbox>
+        <input
+          type="color"
+          aria-label="背景色"
+          value={value.background ?? defaultBackground}
+          disabled={value.background === null}
+          onChange={(e) => update({ background: e.target.value })}
+          className={css({
+            width: 8,
+            height: 8,
+            cursor: "pointer",
+            _disabled: { opacity: 0.5, cursor: "not-allowed" },
+          })}
+        />
+      </div>
+    </div>
+  );
+}
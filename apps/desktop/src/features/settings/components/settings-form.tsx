@@ -4,12 +4,23 @@ import { useCallback, useState, useTransition } from "react";
 import { css } from "styled-system/css";
 import { Divider, Stack } from "styled-system/jsx";
 import { Button } from "@/components/ui/button";
+import { Checkbox } from "@/components/ui/checkbox";
 import { Dialog } from "@/components/ui/dialog";
 import { IconButton } from "@/components/ui/icon-button";
 import { Toast } from "@/components/ui/toast";
 import { settingsAtom } from "../atoms/settings";
 import { purgeIndex } from "../services/purge-index";
+import type {
+  AccessibilitySettings as AccessibilitySettingsValue,
+  HandoutSettings as HandoutSettingsValue,
+  PageSettings as PageSettingsValue,
+  StampSettings as StampSettingsValue,
+} from "../schemas/settings";
+import { AccessibilitySettings } from "./accessibility-settings";
 import { DirSelector } from "./dir-selector";
+import { HandoutSettings } from "./handout-settings";
+import { PageSettings } from "./page-settings";
+import { StampSettings } from "./stamp-settings";
 import { ThemeSelector } from "./theme-selector";
 
 function event(f: (name: string) => (value: string) => void) {
@@ -45,6 +56,81 @@ export function SettingsForm() {
     [setSettings],
   );
 
+  const handleHandoutChange = useCallback(
+    (handout: HandoutSettingsValue) => {
+      setSettings((prev) => {
+        if (!prev) {
+          return prev;
+        }
+        return {
+          ...prev,
+          handout,
+        };
+      });
+    },
+    [setSettings],
+  );
+
+  const handlePageChange = useCallback(
+    (page: PageSettingsValue) => {
+      setSettings((prev) => {
+        if (!prev) {
+          return prev;
+        }
+        return {
+          ...prev,
+          page,
+        };
+      });
+    },
+    [setSettings],
+  );
+
+  const handleStampChange = useCallback(
+    (stamp: StampSettingsValue) => {
+      setSettings((prev) => {
+        if (!prev) {
+          return prev;
+        }
+        return {
+          ...prev,
+          stamp,
+        };
+      });
+    },
+    [setSettings],
+  );
+
+  const handleOptimizeImagesChange = useCallback(
+    (optimizeImages: boolean) => {
+      setSettings((prev) => {
+        if (!prev) {
+          return prev;
+        }
+        return {
+          ...prev,
+          optimizeImages,
+        };
+      });
+    },
+    [setSettings],
+  );
+
+  const handleAccessibilityChange = useCallback(
+    (accessibility: AccessibilitySettingsValue) => {
+      setSettings((prev) => {
+        if (!prev) {
+          return prev;
+        }
+        return {
+          ...prev,
+          accessibility,
+        };
+      });
+    },
+    [setSettings],
+  );
+
   const handlePurgeIndex = useCallback(() => {
     startTransition(async () => {
       try {
@@ -93,6 +179,49 @@ export function SettingsForm() {
           />
         </div>
         <Divider />
+        <div className={css({ display: "grid", gap: 1.5 })}>
+          <p>ページ</p>
+          <PageSettings value={settings?.page} onChange={handlePageChange} />
+        </div>
+        <Divider />
+        <div className={css({ display: "grid", gap: 1.5 })}>
+          <p>ヘッダー・フッター</p>
+          <StampSettings
+            value={settings?.stamp}
+            onChange={handleStampChange}
+          />
+        </div>
+        <Divider />
+        <div className={css({ display: "grid", gap: 1.5 })}>
+          <p>配布資料</p>
+          <HandoutSettings
+            value={settings?.handout}
+            onChange={handleHandoutChange}
+          />
+        </div>
+        <Divider />
+        <div className={css({ display: "grid", gap: 1.5 })}>
+          <p>画像</p>
+          <Checkbox
+            size="sm"
+            checked={settings?.optimizeImages ?? false}
+            onCheckedChange={(details) =>
+              handleOptimizeImagesChange(details.checked === true)
+            }
+          >
+            埋め込む画像を縮小・再圧縮する
+          </Checkbox>
+        </div>
+        <Divider />
+        <div className={css({ display: "grid", gap: 1.5 })}>
+          <p>アクセシビリティ</p>
+          <AccessibilitySettings
+            value={settings?.accessibility}
+            disabled={settings?.handout.enabled}
+            onChange={handleAccessibilityChange}
+          />
+        </div>
+        <Divider />
         <div
           className={css({
             display: "flex",
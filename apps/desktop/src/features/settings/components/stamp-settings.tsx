@@ -0,0 +1,61 @@
+import { css } from "styled-system/css";
+import { Checkbox } from "@/components/ui/checkbox";
+import { Input } from "@/components/ui/input";
+import type { StampSettings as StampSettingsValue } from "../schemas/settings";
+
+const placeholders = "{course} {lecture} {page} {slide} {total} {url}";
+
+type StampSettingsProps = {
+  value?: StampSettingsValue;
+  onChange: (value: StampSettingsValue) => void;
+};
+
+export function StampSettings({ value, onChange }: StampSettingsProps) {
+  if (!value) {
+    return null;
+  }
+
+  const update = (patch: Partial<StampSettingsValue>) =>
+    onChange({ ...value, ...patch });
+
+  return (
+    <div className={css({ display: "grid", gap: 3 })}>
+      <div
+        className={css({
+          display: "grid",
+          gridTemplateColumns: "auto 1fr",
+          alignItems: "center",
+          gap: 3,
+        })}
+      >
+        <span>ヘッダー</span>
+        <Input
+          size="sm"
+          value={value.header}
+          placeholder="{course} / {lecture} / {page}"
+          onChange={(e) => update({ header: e.target.value })}
+        />
+        <span>フッター</span>
+        <Input
+          size="sm"
+          value={value.footer}
+          placeholder="{slide}/{total} {url}"
+          onChange={(e) => update({ footer: e.target.value })}
+        />
+      </div>
+      <p className={css({ fontSize: "sm", color: "fg.muted" })}>
+        {placeholders} が置き換えられます。{"{url}"}{" "}
+        を含む行は元のページへのリンクになります。
+      </p>
+      <Checkbox
+        size="sm"
+        checked={value.watermark}
+        onCheckedChange={(details) =>
+          update({ watermark: details.checked === true })
+        }
+      >
+        「学内限定」の透かしを入れる
+      </Checkbox>
+    </div>
+  );
+}
@@ -1,9 +1,84 @@
 import { z } from "zod";
 
+export const handoutSettingsSchema = z.object({
+  enabled: z.boolean(),
+  slidesPerPage: z.union([
+    z.literal(2),
+    z.literal(4),
+    z.literal(6),
+    z.literal(9),
+  ]),
+  paper: z.enum(["a4", "letter"]),
+  noteLines: z.boolean(),
+  slideNumbers: z.boolean(),
+  colors: z.enum(["original", "print", "grayscale"]).default("original"),
+});
+
+export type HandoutSettings = z.infer<typeof handoutSettingsSchema>;
+
+export const defaultHandoutSettings: HandoutSettings = {
+  enabled: false,
+  slidesPerPage: 4,
+  paper: "a4",
+  noteLines: false,
+  slideNumbers: true,
+  colors: "original",
+};
+
+export const pageSettingsSchema = z.object({
+  size: z.enum(["native", "a4", "letter", "16x9"]),
+  fit: z.enum(["fit", "fill"]),
+  margin: z.number().min(0),
+  background: z
+    .string()
+    .regex(/^#[0-9a-fA-F]{6}$/)
+    .nullable(),
+});
+
+export type PageSettings = z.infer<typeof pageSettingsSchema>;
+
+export const defaultPageSettings: PageSettings = {
+  size: "native",
+  fit: "fit",
+  margin: 0,
+  background: null,
+};
+
+export const stampSettingsSchema = z.object({
+  header: z.string(),
+  footer: z.string(),
+  watermark: z.boolean(),
+});
+
+export type StampSettings = z.infer<typeof stampSettingsSchema>;
+
+export const defaultStampSettings: StampSettings = {
+  header: "",
+  footer: "",
+  watermark: false,
+};
+
+export const accessibilitySettingsSchema = z.object({
+  tagged: z.boolean(),
+});
+
+export type AccessibilitySettings = z.infer<typeof accessibilitySettingsSchema>;
+
+export const defaultAccessibilitySettings: AccessibilitySettings = {
+  tagged: false,
+};
+
 export const settingsSchema = z.object({
   version: z.literal(1).default(1),
   theme: z.enum(["system", "light", "dark"]),
   downloadDir: z.string(),
+  handout: handoutSettingsSchema.default(defaultHandoutSettings),
+  optimizeImages: z.boolean().default(false),
+  page: pageSettingsSchema.default(defaultPageSettings),
+  stamp: stampSettingsSchema.default(defaultStampSettings),
+  accessibility: accessibilitySettingsSchema.default(
+    defaultAccessibilitySettings,
+  ),
 });
 
 export type Settings = z.infer<typeof settingsSchema>;
@@ -1,6 +1,13 @@
 import * as path from "@tauri-apps/api/path";
 import { getStore } from "@/utils/store";
-import { type Settings, settingsSchema } from "../schemas/settings";
+import {
+  defaultAccessibilitySettings,
+  defaultHandoutSettings,
+  defaultPageSettings,
+  defaultStampSettings,
+  type Settings,
+  settingsSchema,
+} from "../schemas/settings";
 
 const key = "settings";
 
@@ -11,6 +18,11 @@ async function getDefaultSettings(): Promise<Settings> {
     version: 1,
     theme: "system",
     downloadDir,
+    handout: defaultHandoutSettings,
+    optimizeImages: false,
+    page: defaultPageSettings,
+    stamp: defaultStampSettings,
+    accessibility: defaultAccessibilitySettings,
   };
 }
 
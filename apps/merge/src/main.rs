@@ -1,10 +1,12 @@
 use anyhow::{Context, Result};
 use clap::{Parser, ValueEnum};
+use collect::pdf::{merge_to, MergeInput, PdfConversionError, StreamConfig};
 use dialoguer::Select;
 use indicatif::{ProgressBar, ProgressStyle};
-use lopdf::{Bookmark, Document, Object, ObjectId};
+use lopdf::Document;
 use regex::Regex;
-use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufWriter;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
@@ -156,195 +158,36 @@ fn filter_by_course(pdfs: Vec<PdfInfo>, course_name: &str) -> Vec<PdfInfo> {
     filtered
 }
 
-fn merge_pdfs(pdfs: &[PdfInfo], output_path: &Path, level: &MergeLevel) -> Result<()> {
-    if pdfs.is_empty() {
-        anyhow::bail!("結合するPDFファイルがありません");
-    }
-
-    let mut max_id = 1;
-    let mut documents_pages: BTreeMap<ObjectId, Object> = BTreeMap::new();
-    let mut documents_objects: BTreeMap<ObjectId, Object> = BTreeMap::new();
-    let mut document = Document::with_version("1.5");
-
-    match level {
-        MergeLevel::Lecture => {
-            let mut current_page_slug = String::new();
-            let mut page_bookmark = None;
-
-            for pdf_info in pdfs {
-                let mut doc = Document::load(&pdf_info.path)?;
-                doc.renumber_objects_with(max_id);
-                max_id = doc.max_id + 1;
-
-                let pages = doc.get_pages();
-                let first_object = pages.values().next().copied().unwrap_or((0, 0));
-
-                if current_page_slug != pdf_info.page_slug {
-                    let title = format!("{} - {}", pdf_info.page_slug, pdf_info.page_title);
-                    page_bookmark = Some(document.add_bookmark(
-                        Bookmark::new(title, [0.0, 0.0, 0.0], 0, first_object),
-                        None,
-                    ));
-                    current_page_slug = pdf_info.page_slug.clone();
-                }
-
-                if pdf_info.page_number > 1 {
-                    let title = format!("({}) {}", pdf_info.page_number, pdf_info.page_title);
-                    document.add_bookmark(
-                        Bookmark::new(title, [0.0, 0.0, 0.0], 0, first_object),
-                        page_bookmark,
-                    );
-                }
-
-                for (_, object_id) in doc.get_pages() {
-                    if let Ok(page_obj) = doc.get_object(object_id) {
-                        documents_pages.insert(object_id, page_obj.to_owned());
-                    }
-                }
-                documents_objects.extend(doc.objects);
-            }
-        }
-        MergeLevel::Course => {
-            let mut current_lecture = String::new();
-            let mut lecture_bookmark = None;
-            let mut current_page_slug = String::new();
-            let mut page_bookmark = None;
-
-            for pdf_info in pdfs {
-                let mut doc = Document::load(&pdf_info.path)?;
-                doc.renumber_objects_with(max_id);
-                max_id = doc.max_id + 1;
-
-                let pages = doc.get_pages();
-                let first_object = pages.values().next().copied().unwrap_or((0, 0));
-
-                if current_lecture != pdf_info.lecture_name {
-                    lecture_bookmark = Some(document.add_bookmark(
-                        Bookmark::new(
-                            pdf_info.lecture_name.clone(),
-                            [0.0, 0.0, 0.0],
-                            0,
-                            first_object,
-                        ),
-                        None,
-                    ));
-                    current_lecture = pdf_info.lecture_name.clone();
-                    current_page_slug.clear();
-                }
-
-                if current_page_slug != pdf_info.page_slug {
-                    let title = format!("{} - {}", pdf_info.page_slug, pdf_info.page_title);
-                    page_bookmark = Some(document.add_bookmark(
-                        Bookmark::new(title, [0.0, 0.0, 0.0], 0, first_object),
-                        lecture_bookmark,
-                    ));
-                    current_page_slug = pdf_info.page_slug.clone();
-                }
-
-                if pdf_info.page_number > 1 {
-                    let title = format!("({}) {}", pdf_info.page_number, pdf_info.page_title);
-                    document.add_bookmark(
-                        Bookmark::new(title, [0.0, 0.0, 0.0], 0, first_object),
-                        page_bookmark,
-                    );
-                }
-
-                for (_, object_id) in doc.get_pages() {
-                    if let Ok(page_obj) = doc.get_object(object_id) {
-                        documents_pages.insert(object_id, page_obj.to_owned());
-                    }
-                }
-                documents_objects.extend(doc.objects);
-            }
-        }
-    }
-
-    let mut catalog_object: Option<(ObjectId, Object)> = None;
-    let mut pages_object: Option<(ObjectId, Object)> = None;
-
-    for (object_id, object) in documents_objects {
-        match object.type_name().unwrap_or(b"") {
-            b"Catalog" => {
-                catalog_object = Some((
-                    catalog_object.map(|(id, _)| id).unwrap_or(object_id),
-                    object,
-                ));
-            }
-            b"Pages" => {
-                if let Ok(dictionary) = object.as_dict() {
-                    let mut dictionary = dictionary.clone();
-                    if let Some((_, ref obj)) = pages_object {
-                        if let Ok(old_dict) = obj.as_dict() {
-                            dictionary.extend(old_dict);
-                        }
-                    }
-                    pages_object = Some((
-                        pages_object.map(|(id, _)| id).unwrap_or(object_id),
-                        Object::Dictionary(dictionary),
-                    ));
-                }
-            }
-            b"Page" => {}
-            b"Outlines" => {}
-            b"Outline" => {}
-            _ => {
-                document.objects.insert(object_id, object);
-            }
-        }
+/// しおりの階層（講義 → ページ → 2つ目以降のファイル）
+fn outline_path(pdf_info: &PdfInfo, level: &MergeLevel) -> Vec<String> {
+    let mut path = vec![];
+    if matches!(level, MergeLevel::Course) {
+        path.push(pdf_info.lecture_name.clone());
     }
-
-    let (page_id, page_object) = pages_object.context("Pagesオブジェクトが見つかりません")?;
-
-    for (&object_id, object) in documents_pages.iter() {
-        if let Ok(dictionary) = object.as_dict() {
-            let mut dictionary = dictionary.clone();
-            dictionary.set("Parent", page_id);
-            document
-                .objects
-                .insert(object_id, Object::Dictionary(dictionary));
-        }
+    path.push(format!("{} - {}", pdf_info.page_slug, pdf_info.page_title));
+    if pdf_info.page_number > 1 {
+        path.push(format!(
+            "({}) {}",
+            pdf_info.page_number, pdf_info.page_title
+        ));
     }
+    path
+}
 
-    let (catalog_id, catalog_object) =
-        catalog_object.context("Catalogオブジェクトが見つかりません")?;
-
-    if let Ok(dictionary) = page_object.as_dict() {
-        let mut dictionary = dictionary.clone();
-        dictionary.set("Count", documents_pages.len() as u32);
-        dictionary.set(
-            "Kids",
-            documents_pages
-                .keys()
-                .map(|&id| Object::Reference(id))
-                .collect::<Vec<_>>(),
-        );
-        document
-            .objects
-            .insert(page_id, Object::Dictionary(dictionary));
-    }
-
-    if let Ok(dictionary) = catalog_object.as_dict() {
-        let mut dictionary = dictionary.clone();
-        dictionary.set("Pages", page_id);
-        dictionary.set("PageMode", "UseOutlines");
-        dictionary.remove(b"Outlines");
-        document
-            .objects
-            .insert(catalog_id, Object::Dictionary(dictionary));
-    }
-
-    document.trailer.set("Root", catalog_id);
-    document.max_id = document.objects.len() as u32;
-    document.renumber_objects();
-    document.adjust_zero_pages();
-
-    if let Some(outline_id) = document.build_outline() {
-        if let Ok(Object::Dictionary(dict)) = document.get_object_mut(catalog_id) {
-            dict.set("Outlines", Object::Reference(outline_id));
-        }
+fn merge_pdfs(pdfs: &[PdfInfo], output_path: &Path, level: &MergeLevel) -> Result<()> {
+    if pdfs.is_empty() {
+        anyhow::bail!("結合するPDFファイルがありません");
     }
 
-    document.save(output_path)?;
+    // 1ファイルずつ読み込んで書き出すため、メモリには1ファイル分しか展開しない
+    let inputs = pdfs
+        .iter()
+        .map(|pdf_info| -> Result<_, PdfConversionError> {
+            let document = Document::load(&pdf_info.path)?;
+            Ok(MergeInput::new(document, outline_path(pdf_info, level)))
+        });
+    let output = BufWriter::new(File::create(output_path)?);
+    merge_to(inputs, output, &StreamConfig::default())?;
     Ok(())
 }
 
@@ -601,6 +444,27 @@ mod tests {
         assert_eq!(result.page_number, 1);
     }
 
+    #[test]
+    fn test_outline_path() {
+        let path = Path::new(
+            "コンピュータ・システム論D/08 - 機械語プログラミング入門/1 - 機械語の基礎 (2).pdf",
+        );
+        let info = parse_pdf_path(path).unwrap();
+
+        assert_eq!(
+            outline_path(&info, &MergeLevel::Course),
+            [
+                "機械語プログラミング入門",
+                "1 - 機械語の基礎",
+                "(2) 機械語の基礎"
+            ]
+        );
+        assert_eq!(
+            outline_path(&info, &MergeLevel::Lecture),
+            ["1 - 機械語の基礎", "(2) 機械語の基礎"]
+        );
+    }
+
     #[test]
     fn test_parse_invalid_format() {
         let path = Path::new("invalid/path.pdf");
@@ -0,0 +1,224 @@
+use super::error::ExportError;
+use crate::{domain::models::PageKey, pdf::SlideImage};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// 書き出し先に置く対応表のファイル名
+pub const ASSET_MAP_FILE: &str = "assets.json";
+
+/// スライドの画像1件と書き出したファイルの対応
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetEntry {
+    pub page_key: String,
+    /// ページ内の埋め込み（`Slide`）の番号
+    pub embed_index: usize,
+    /// 画像を参照していたSVG（スライド）の番号
+    pub slide_index: usize,
+    /// 書き出し先のフォルダー内のファイル名
+    pub file: String,
+    /// 取得元のURL（SVGに埋め込まれていた画像はNone）
+    pub url: Option<String>,
+}
+
+/// スライド・ページと画像ファイルの対応表
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetMap {
+    pub entries: Vec<AssetEntry>,
+}
+
+impl AssetMap {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// JSONに変換
+    pub fn to_json(&self) -> Result<String, ExportError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// スライドの画像を内容のハッシュごとに1回だけ書き出す
+///
+/// ファイル名は`<SHA-256>.<拡張子>`。既に同じ名前のファイルがあれば書き出さないため、
+/// 同じフォルダーに繰り返し書き出しても画像は重複しない。対応表も既存のものに追記する。
+#[derive(Debug)]
+pub struct AssetWriter {
+    dir: PathBuf,
+    map: AssetMap,
+}
+
+impl AssetWriter {
+    /// 書き出し先のフォルダーを作成
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self, ExportError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            map: AssetMap::default(),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// `PreProcessor::collect_images`で取得した画像を書き出して対応表に追加
+    ///
+    /// `embed_index`はページ内の埋め込み（`Slide`）の番号。
+    /// 形式を判定できない画像は書き出さない。
+    pub fn push(
+        &mut self,
+        page_key: &PageKey,
+        embed_index: usize,
+        images: &[SlideImage],
+    ) -> Result<(), ExportError> {
+        let page_key = page_key.to_string();
+        for image in images {
+            let Some(extension) = image.extension() else {
+                continue;
+            };
+            let file = format!("{}.{extension}", image.hash());
+            let path = self.dir.join(&file);
+            if !path.exists() {
+                fs::write(&path, &image.bytes)?;
+            }
+            self.map.entries.push(AssetEntry {
+                page_key: page_key.clone(),
+                embed_index,
+                slide_index: image.index,
+                file,
+                url: image.url.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn map(&self) -> &AssetMap {
+        &self.map
+    }
+
+    /// 既存の`assets.json`に対応表を統合して書き出し、統合した対応表を返す
+    ///
+    /// 書き出した埋め込みの既存の項目は置き換え、それ以外の項目は残す。
+    pub fn finish(self) -> Result<AssetMap, ExportError> {
+        let path = self.dir.join(ASSET_MAP_FILE);
+        let mut map = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str::<AssetMap>(&json)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => AssetMap::default(),
+            Err(e) => return Err(e.into()),
+        };
+        let written = self
+            .map
+            .entries
+            .iter()
+            .map(|entry| (entry.page_key.as_str(), entry.embed_index))
+            .collect::<HashSet<_>>();
+        map.entries
+            .retain(|entry| !written.contains(&(entry.page_key.as_str(), entry.embed_index)));
+        map.entries.extend(self.map.entries);
+
+        fs::write(&path, map.to_json()?)?;
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pdf::ContentHash, testing};
+    use bytes::Bytes;
+
+    const PNG: &[u8] = &[
+        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44,
+        0x52,
+    ];
+
+    fn image(index: usize, url: Option<&str>, bytes: &'static [u8]) -> SlideImage {
+        SlideImage {
+            index,
+            url: url.map(str::to_string),
+            bytes: Bytes::from_static(bytes),
+        }
+    }
+
+    #[test]
+    fn test_assets_are_written_once_per_hash() {
+        let dir = std::env::temp_dir().join(format!("collect-assets-{}", std::process::id()));
+        let page_key = testing::page_key();
+
+        let mut writer = AssetWriter::new(&dir).unwrap();
+        writer
+            .push(
+                &page_key,
+                0,
+                &[
+                    image(0, Some("https://example.com/a.png"), PNG),
+                    image(1, None, PNG),
+                    image(1, None, b"<svg></svg>"),
+                    image(2, None, b"unknown"),
+                ],
+            )
+            .unwrap();
+        let map = writer.finish().unwrap();
+
+        let png = format!("{}.png", ContentHash::of(PNG));
+        let svg = format!("{}.svg", ContentHash::of(b"<svg></svg>"));
+        let files = map
+            .entries
+            .iter()
+            .map(|entry| (entry.slide_index, entry.file.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            vec![(0, png.as_str()), (1, png.as_str()), (1, svg.as_str())]
+        );
+        assert_eq!(map.entries[0].page_key, "2024/CS101/01/01-1");
+        assert_eq!(
+            map.entries[0].url.as_deref(),
+            Some("https://example.com/a.png")
+        );
+
+        let mut written = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        written.sort();
+        let mut expected = vec![png, svg, ASSET_MAP_FILE.to_string()];
+        expected.sort();
+        assert_eq!(written, expected);
+
+        let json = fs::read_to_string(dir.join(ASSET_MAP_FILE)).unwrap();
+        assert_eq!(serde_json::from_str::<AssetMap>(&json).unwrap(), map);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_finish_merges_existing_map() {
+        let dir = std::env::temp_dir().join(format!("collect-assets-merge-{}", std::process::id()));
+        let page_key = testing::page_key();
+        let write = |embed_index: usize, images: &[SlideImage]| {
+            let mut writer = AssetWriter::new(&dir).unwrap();
+            writer.push(&page_key, embed_index, images).unwrap();
+            writer.finish().unwrap()
+        };
+
+        write(0, &[image(0, None, PNG)]);
+        write(1, &[image(0, None, b"<svg></svg>")]);
+        // 同じ埋め込みを書き出し直すと、その項目だけ置き換わる
+        let map = write(0, &[image(2, None, PNG)]);
+
+        let entries = map
+            .entries
+            .iter()
+            .map(|entry| (entry.embed_index, entry.slide_index))
+            .collect::<Vec<_>>();
+        assert_eq!(entries, vec![(1, 0), (0, 2)]);
+        let json = fs::read_to_string(dir.join(ASSET_MAP_FILE)).unwrap();
+        assert_eq!(serde_json::from_str::<AssetMap>(&json).unwrap(), map);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,641 @@
+use super::{
+    error::ExportError,
+    raster::{rasterize, SVG_DPI},
+};
+use crate::{
+    domain::models::{ProcessedSvg, SlideContent},
+    pdf::Converter,
+};
+use lol_html::{element, html_content::ContentType, HtmlRewriter, Settings};
+use lopdf::Document;
+use rayon::prelude::*;
+use resvg::{
+    tiny_skia::Pixmap,
+    usvg::{Options, Tree},
+};
+use serde::{Deserialize, Serialize};
+
+/// 変化した範囲の塗り
+const CHANGED_COLOR: &str = "#e53935";
+/// 追加されたページの枠の色
+const INSERTED_COLOR: &str = "#43a047";
+
+/// スライドの比較設定
+#[derive(Debug, Clone)]
+pub struct DiffConfig {
+    /// 比較のために描画する解像度
+    pub dpi: f32,
+    /// 変化とみなす色の差（チャンネルごと、0〜255）
+    pub tolerance: u8,
+    /// 変化したピクセルを範囲にまとめる升目の大きさ（描画したピクセル単位）
+    pub cell_size: u32,
+}
+
+impl Default for DiffConfig {
+    fn default() -> Self {
+        Self {
+            dpi: 48.0,
+            tolerance: 32,
+            cell_size: 8,
+        }
+    }
+}
+
+/// ページの変化の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageChange {
+    Unchanged,
+    Modified,
+    Inserted,
+    Removed,
+}
+
+/// テキストの行の変化
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "text", rename_all = "snake_case")]
+pub enum LineChange {
+    Added(String),
+    Removed(String),
+}
+
+/// 描画結果が変化した範囲（新しい版のスライドの座標、左上が原点）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Region {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// 1ページ（SVG）の比較結果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PageDiff {
+    pub change: PageChange,
+    /// 古い版のSVGのインデックス（追加されたページはNone）
+    pub old_index: Option<usize>,
+    /// 新しい版のSVGのインデックス（削除されたページはNone）
+    pub new_index: Option<usize>,
+    pub lines: Vec<LineChange>,
+    /// 描画結果で色が変わったピクセルの割合（0〜1）
+    pub changed_pixels: f32,
+    pub regions: Vec<Region>,
+}
+
+/// 2つの版のSlideContentの比較結果
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SlideDiff {
+    pub pages: Vec<PageDiff>,
+}
+
+/// 2つの版のSlideContentを比較
+///
+/// ページは`get_texts`のテキストが一致するものを順序を保って対応付け、
+/// 対応しなかったページは前から順に組にして比較する。組にならなかったページは追加・削除とする。
+/// 対応したページは行単位のテキストの差分と、描画結果のピクセルの差分を求める。
+pub fn diff(
+    old: &SlideContent,
+    new: &SlideContent,
+    config: &DiffConfig,
+) -> Result<SlideDiff, ExportError> {
+    let old_texts = old.svgs.iter().map(lines).collect::<Vec<_>>();
+    let new_texts = new.svgs.iter().map(lines).collect::<Vec<_>>();
+
+    let mut pairs = vec![];
+    let mut removed = vec![];
+    let mut inserted = vec![];
+    let mut steps = vec![];
+    let (mut i, mut j) = (0, 0);
+    let end = (old.svgs.len(), new.svgs.len());
+    for (matched_i, matched_j) in lcs(&old_texts, &new_texts).into_iter().chain([end]) {
+        let paired = (matched_i - i).min(matched_j - j);
+        for k in 0..paired {
+            steps.push(Step::Pair(pairs.len()));
+            pairs.push((i + k, j + k));
+        }
+        for index in i + paired..matched_i {
+            steps.push(Step::Removed(removed.len()));
+            removed.push(index);
+        }
+        for index in j + paired..matched_j {
+            steps.push(Step::Inserted(inserted.len()));
+            inserted.push(index);
+        }
+        if (matched_i, matched_j) != end {
+            steps.push(Step::Pair(pairs.len()));
+            pairs.push((matched_i, matched_j));
+        }
+        (i, j) = (matched_i + 1, matched_j + 1);
+    }
+
+    let compared = pairs
+        .par_iter()
+        .map(|&(i, j)| {
+            let (changed_pixels, regions) = compare_pixels(&old.svgs[i], &new.svgs[j], config)?;
+            let lines = diff_lines(&old_texts[i], &new_texts[j]);
+            let change = if lines.is_empty() && regions.is_empty() {
+                PageChange::Unchanged
+            } else {
+                PageChange::Modified
+            };
+            Ok(PageDiff {
+                change,
+                old_index: Some(old.svgs[i].index),
+                new_index: Some(new.svgs[j].index),
+                lines,
+                changed_pixels,
+                regions,
+            })
+        })
+        .collect::<Result<Vec<_>, ExportError>>()?;
+
+    let pages = steps
+        .into_iter()
+        .map(|step| match step {
+            Step::Pair(k) => compared[k].clone(),
+            Step::Removed(k) => PageDiff {
+                change: PageChange::Removed,
+                old_index: Some(old.svgs[removed[k]].index),
+                new_index: None,
+                lines: old_texts[removed[k]]
+                    .iter()
+                    .cloned()
+                    .map(LineChange::Removed)
+                    .collect(),
+                changed_pixels: 1.0,
+                regions: vec![],
+            },
+            Step::Inserted(k) => PageDiff {
+                change: PageChange::Inserted,
+                old_index: None,
+                new_index: Some(new.svgs[inserted[k]].index),
+                lines: new_texts[inserted[k]]
+                    .iter()
+                    .cloned()
+                    .map(LineChange::Added)
+                    .collect(),
+                changed_pixels: 1.0,
+                regions: vec![],
+            },
+        })
+        .collect();
+
+    Ok(SlideDiff { pages })
+}
+
+/// 比較結果を並べる順序
+enum Step {
+    Pair(usize),
+    Removed(usize),
+    Inserted(usize),
+}
+
+impl SlideDiff {
+    /// 変化したページがあればtrue
+    pub fn has_changes(&self) -> bool {
+        self.pages
+            .iter()
+            .any(|page| page.change != PageChange::Unchanged)
+    }
+
+    /// JSONに変換
+    pub fn to_json(&self) -> Result<String, ExportError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// 変化した範囲を強調したSlideContentを作成
+    ///
+    /// 新しい版のページに変化した範囲を赤く重ね、追加されたページは緑の枠で囲む。
+    /// 削除されたページは古い版のページを薄くして赤い枠で囲み、元の位置に挟む。
+    pub fn highlight(
+        &self,
+        old: &SlideContent,
+        new: &SlideContent,
+    ) -> Result<SlideContent, ExportError> {
+        fn find(content: &SlideContent, index: Option<usize>) -> Option<&ProcessedSvg> {
+            index.and_then(|index| content.svgs.iter().find(|svg| svg.index == index))
+        }
+
+        let mut svgs = vec![];
+        for page in &self.pages {
+            let (svg, marks) = match page.change {
+                PageChange::Removed => (find(old, page.old_index), vec![Mark::Removed]),
+                PageChange::Inserted => (find(new, page.new_index), vec![Mark::Inserted]),
+                PageChange::Unchanged | PageChange::Modified => (
+                    find(new, page.new_index),
+                    page.regions.iter().copied().map(Mark::Changed).collect(),
+                ),
+            };
+            let Some(svg) = svg else {
+                continue;
+            };
+            let content = overlay(&svg.content, &marks)?;
+            svgs.push(ProcessedSvg::new(content, svgs.len()));
+        }
+        Ok(SlideContent::new(new.page_key.clone(), svgs))
+    }
+
+    /// 変化した範囲を強調したPDFを作成
+    pub fn to_pdf(
+        &self,
+        old: &SlideContent,
+        new: &SlideContent,
+        converter: &Converter,
+    ) -> Result<Document, ExportError> {
+        Ok(converter.convert(&self.highlight(old, new)?)?)
+    }
+}
+
+/// ページに重ねる強調
+#[derive(Debug, Clone, Copy)]
+enum Mark {
+    Changed(Region),
+    Inserted,
+    Removed,
+}
+
+/// ページのテキストを行に分ける
+fn lines(svg: &ProcessedSvg) -> Vec<String> {
+    svg.get_texts()
+        .iter()
+        .flat_map(|text| text.lines())
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// 最長共通部分列となる要素の組
+fn lcs<T: PartialEq>(a: &[T], b: &[T]) -> Vec<(usize, usize)> {
+    let mut table = vec![vec![0_usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            table[i][j] = if a[i] == b[j] {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            };
+        }
+    }
+
+    let mut pairs = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if table[i + 1][j] >= table[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/// 行単位の差分（削除した行を追加した行より先に並べる）
+fn diff_lines(old: &[String], new: &[String]) -> Vec<LineChange> {
+    let mut changes = vec![];
+    let (mut i, mut j) = (0, 0);
+    for (matched_i, matched_j) in lcs(old, new).into_iter().chain([(old.len(), new.len())]) {
+        changes.extend(old[i..matched_i].iter().cloned().map(LineChange::Removed));
+        changes.extend(new[j..matched_j].iter().cloned().map(LineChange::Added));
+        (i, j) = (matched_i + 1, matched_j + 1);
+    }
+    changes
+}
+
+fn render(svg: &ProcessedSvg, dpi: f32) -> Result<Pixmap, ExportError> {
+    let tree = Tree::from_str(&svg.content, &Options::default())
+        .map_err(|e| ExportError::SvgParsing(e.to_string()))?;
+    rasterize(&tree, dpi)
+}
+
+/// 描画結果を比較し、色が変わったピクセルの割合と範囲を返す
+fn compare_pixels(
+    old: &ProcessedSvg,
+    new: &ProcessedSvg,
+    config: &DiffConfig,
+) -> Result<(f32, Vec<Region>), ExportError> {
+    let old = render(old, config.dpi)?;
+    let new = render(new, config.dpi)?;
+    let scale = config.dpi / SVG_DPI;
+    let (width, height) = (new.width(), new.height());
+    if (old.width(), old.height()) != (width, height) {
+        let region = Region {
+            x: 0.0,
+            y: 0.0,
+            width: width as f32 / scale,
+            height: height as f32 / scale,
+        };
+        return Ok((1.0, vec![region]));
+    }
+
+    let cell = config.cell_size.max(1);
+    let (columns, rows) = (width.div_ceil(cell), height.div_ceil(cell));
+    let mut cells = vec![false; (columns * rows) as usize];
+    let mut changed = 0_usize;
+    for (i, (a, b)) in old.pixels().iter().zip(new.pixels()).enumerate() {
+        let differs = [
+            a.red().abs_diff(b.red()),
+            a.green().abs_diff(b.green()),
+            a.blue().abs_diff(b.blue()),
+            a.alpha().abs_diff(b.alpha()),
+        ]
+        .into_iter()
+        .any(|difference| difference > config.tolerance);
+        if differs {
+            changed += 1;
+            let (x, y) = (i as u32 % width, i as u32 / width);
+            cells[(y / cell * columns + x / cell) as usize] = true;
+        }
+    }
+
+    let regions = group_cells(&cells, columns, rows)
+        .into_iter()
+        .map(|(left, top, right, bottom)| {
+            let (x0, y0) = (left * cell, top * cell);
+            let (x1, y1) = (
+                ((right + 1) * cell).min(width),
+                ((bottom + 1) * cell).min(height),
+            );
+            Region {
+                x: x0 as f32 / scale,
+                y: y0 as f32 / scale,
+                width: (x1 - x0) as f32 / scale,
+                height: (y1 - y0) as f32 / scale,
+            }
+        })
+        .collect();
+    Ok((changed as f32 / (width * height) as f32, regions))
+}
+
+/// 隣接する（斜めを含む）升目をまとめ、まとまりごとの升目の範囲を返す
+fn group_cells(cells: &[bool], columns: u32, rows: u32) -> Vec<(u32, u32, u32, u32)> {
+    let mut visited = vec![false; cells.len()];
+    let mut groups = vec![];
+    for start in 0..cells.len() {
+        if !cells[start] || visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let (column, row) = (start as u32 % columns, start as u32 / columns);
+        let mut bounds = (column, row, column, row);
+        let mut stack = vec![(column, row)];
+        while let Some((column, row)) = stack.pop() {
+            bounds = (
+                bounds.0.min(column),
+                bounds.1.min(row),
+                bounds.2.max(column),
+                bounds.3.max(row),
+            );
+            for (dx, dy) in (-1..=1).flat_map(|dx| (-1..=1).map(move |dy| (dx, dy))) {
+                let (Some(x), Some(y)) = (
+                    column.checked_add_signed(dx).filter(|&x| x < columns),
+                    row.checked_add_signed(dy).filter(|&y| y < rows),
+                ) else {
+                    continue;
+                };
+                let index = (y * columns + x) as usize;
+                if cells[index] && !visited[index] {
+                    visited[index] = true;
+                    stack.push((x, y));
+                }
+            }
+        }
+        groups.push(bounds);
+    }
+    groups
+}
+
+/// SVGの最上位の`<svg>`要素の末尾に強調を重ねる
+fn overlay(svg: &str, marks: &[Mark]) -> Result<String, ExportError> {
+    if marks.is_empty() {
+        return Ok(svg.to_string());
+    }
+    let tree = Tree::from_str(svg, &Options::default())
+        .map_err(|e| ExportError::SvgParsing(e.to_string()))?;
+    let size = tree.size();
+    let (width, height) = (size.width(), size.height());
+
+    let mut root = true;
+    let mut output = vec![];
+    let mut rewriter = HtmlRewriter::new(
+        Settings {
+            element_content_handlers: vec![element!("svg", |el| {
+                if !root {
+                    return Ok(());
+                }
+                root = false;
+
+                // 描画時の座標（width・height）をviewBoxの座標に変換する
+                let [vx, vy, vw, vh] = el
+                    .get_attribute("viewBox")
+                    .and_then(|view_box| parse_view_box(&view_box))
+                    .unwrap_or([0.0, 0.0, width, height]);
+                let (sx, sy) = (vw / width, vh / height);
+                let rect = |region: Region, style: &str| {
+                    format!(
+                        r#"<rect x="{}" y="{}" width="{}" height="{}" {style}/>"#,
+                        vx + region.x * sx,
+                        vy + region.y * sy,
+                        region.width * sx,
+                        region.height * sy,
+                    )
+                };
+                let page = Region {
+                    x: 0.0,
+                    y: 0.0,
+                    width,
+                    height,
+                };
+                let frame = 8.0 * sx;
+
+                let markup = marks
+                    .iter()
+                    .map(|mark| match *mark {
+                        Mark::Changed(region) => rect(
+                            region,
+                            &format!(
+                                r#"fill="{CHANGED_COLOR}" fill-opacity="0.2" stroke="{CHANGED_COLOR}" stroke-width="{}""#,
+                                2.0 * sx
+                            ),
+                        ),
+                        Mark::Inserted => rect(
+                            page,
+                            &format!(
+                                r#"fill="none" stroke="{INSERTED_COLOR}" stroke-width="{frame}""#
+                            ),
+                        ),
+                        Mark::Removed => rect(
+                            page,
+                            &format!(
+                                r##"fill="#ffffff" fill-opacity="0.6" stroke="{CHANGED_COLOR}" stroke-width="{frame}""##
+                            ),
+                        ),
+                    })
+                    .collect::<String>();
+                el.append(&markup, ContentType::Html);
+                Ok(())
+            })],
+            ..Settings::default()
+        },
+        |c: &[u8]| output.extend_from_slice(c),
+    );
+
+    rewriter.write(svg.as_bytes())?;
+    rewriter.end()?;
+
+    Ok(String::from_utf8(output)?)
+}
+
+fn parse_view_box(value: &str) -> Option<[f32; 4]> {
+    let numbers = value
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse::<f32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let view_box: [f32; 4] = numbers.try_into().ok()?;
+    (view_box[2] > 0.0 && view_box[3] > 0.0).then_some(view_box)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn svg(label: &str, fill: &str) -> String {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="96" height="48"><rect x="48" width="48" height="48" fill="{fill}"/><g role="img" aria-label="{label}"></g></svg>"#
+        )
+    }
+
+    fn content(svgs: &[String]) -> SlideContent {
+        SlideContent::new(
+            testing::page_key(),
+            svgs.iter()
+                .enumerate()
+                .map(|(index, svg)| ProcessedSvg::new(svg.clone(), index))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_diff_pages() {
+        let old = content(&[
+            svg("表紙", "#ffffff"),
+            svg("定義&#10;旧い説明", "#ffffff"),
+            svg("削除", "#ffffff"),
+            svg("まとめ", "#ffffff"),
+        ]);
+        let new = content(&[
+            svg("表紙", "#ffffff"),
+            svg("定義&#10;新しい説明", "#ff0000"),
+            svg("まとめ", "#ffffff"),
+            svg("追加", "#ffffff"),
+        ]);
+
+        let diff = diff(&old, &new, &DiffConfig::default()).unwrap();
+
+        let changes = diff
+            .pages
+            .iter()
+            .map(|page| (page.change, page.old_index, page.new_index))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                (PageChange::Unchanged, Some(0), Some(0)),
+                (PageChange::Modified, Some(1), Some(1)),
+                (PageChange::Removed, Some(2), None),
+                (PageChange::Unchanged, Some(3), Some(2)),
+                (PageChange::Inserted, None, Some(3)),
+            ]
+        );
+        assert!(diff.has_changes());
+
+        let modified = &diff.pages[1];
+        assert_eq!(
+            modified.lines,
+            vec![
+                LineChange::Removed("旧い説明".to_string()),
+                LineChange::Added("新しい説明".to_string()),
+            ]
+        );
+        // 右半分の四角だけが変わった
+        assert!((modified.changed_pixels - 0.5).abs() < 0.01);
+        assert_eq!(
+            modified.regions,
+            vec![Region {
+                x: 48.0,
+                y: 0.0,
+                width: 48.0,
+                height: 48.0,
+            }]
+        );
+
+        let json = diff.to_json().unwrap();
+        assert_eq!(serde_json::from_str::<SlideDiff>(&json).unwrap(), diff);
+    }
+
+    #[test]
+    fn test_highlight() {
+        let old = content(&[svg("A", "#ffffff"), svg("B", "#ffffff")]);
+        let new = content(&[svg("A", "#000000")]);
+
+        let diff = diff(&old, &new, &DiffConfig::default()).unwrap();
+        let highlighted = diff.highlight(&old, &new).unwrap();
+
+        assert_eq!(highlighted.svgs.len(), 2);
+        assert!(highlighted.svgs[0].content.contains(&format!(
+            r#"<rect x="48" y="0" width="48" height="48" fill="{CHANGED_COLOR}""#
+        )));
+        // 削除されたページは古い版から挟む
+        assert!(highlighted.svgs[1].content.contains(r#"aria-label="B""#));
+        assert!(highlighted.svgs[1]
+            .content
+            .contains(r##"<rect x="0" y="0" width="96" height="48" fill="#ffffff""##));
+    }
+
+    #[test]
+    fn test_overlay_uses_view_box() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50" viewBox="10 20 200 100"><svg width="1" height="1"/></svg>"#;
+        let region = Region {
+            x: 50.0,
+            y: 25.0,
+            width: 10.0,
+            height: 5.0,
+        };
+
+        let content = overlay(svg, &[Mark::Changed(region)]).unwrap();
+
+        // 入れ子の`<svg>`には重ねない
+        assert_eq!(content.matches("<rect").count(), 1);
+        assert!(content.contains(r#"<rect x="110" y="70" width="20" height="10""#));
+        assert!(content.ends_with("/></svg>"));
+    }
+
+    #[test]
+    fn test_group_cells() {
+        #[rustfmt::skip]
+        let cells = [
+            true,  true,  false, false,
+            false, true,  false, false,
+            false, false, false, true,
+        ];
+
+        assert_eq!(group_cells(&cells, 4, 3), vec![(0, 0, 1, 1), (3, 2, 3, 2)]);
+    }
+
+    #[test]
+    fn test_parse_view_box() {
+        assert_eq!(
+            parse_view_box("0 0 960 540"),
+            Some([0.0, 0.0, 960.0, 540.0])
+        );
+        assert_eq!(parse_view_box("0,0, 10 10"), Some([0.0, 0.0, 10.0, 10.0]));
+        assert_eq!(parse_view_box("0 0 0 10"), None);
+        assert_eq!(parse_view_box("0 0 10"), None);
+    }
+}
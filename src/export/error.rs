@@ -0,0 +1,23 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("SVG parsing failed: {0}")]
+    SvgParsing(String),
+    #[error("Invalid output size: {width}x{height}")]
+    InvalidSize { width: f32, height: f32 },
+    #[error("Image encoding failed: {0}")]
+    Encoding(String),
+    #[error("External reference is not embedded: {0}")]
+    ExternalReference(String),
+    #[error("HTML rewriting failed: {0}")]
+    HtmlRewriting(#[from] lol_html::errors::RewritingError),
+    #[error("UTF-8 conversion failed: {0}")]
+    Utf8Conversion(#[from] std::string::FromUtf8Error),
+    #[error("JSON serialization failed: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("File writing failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("PDF conversion failed: {0}")]
+    Pdf(#[from] crate::pdf::PdfConversionError),
+}
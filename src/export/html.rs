@@ -0,0 +1,129 @@
+use super::{error::ExportError, svg::with_namespaces};
+use crate::domain::models::SlideContent;
+use base64::{engine::general_purpose, Engine};
+use html_escape::{encode_double_quoted_attribute, encode_text};
+
+/// HTMLスライドショーの1区切り（1ページ分のスライド）
+#[derive(Debug, Clone)]
+pub struct HtmlSection<'a> {
+    pub title: String,
+    pub content: &'a SlideContent,
+}
+
+impl<'a> HtmlSection<'a> {
+    pub fn new(title: impl Into<String>, content: &'a SlideContent) -> Self {
+        Self {
+            title: title.into(),
+            content,
+        }
+    }
+}
+
+const STYLE: &str = r#"
+*{box-sizing:border-box}
+body{margin:0;font-family:system-ui,-apple-system,"Hiragino Sans","Noto Sans JP",sans-serif;background:#1e1e1e;color:#eee;display:flex}
+nav{position:sticky;top:0;height:100vh;width:16rem;flex-shrink:0;overflow-y:auto;padding:1rem;background:#111;border-right:1px solid #333}
+nav h1{font-size:1rem;margin:0 0 1rem}
+nav ol{margin:0;padding-left:1.25rem}
+nav a{color:#ccc;text-decoration:none;display:block;padding:.25rem 0}
+nav a:hover,nav a.current{color:#fff;text-decoration:underline}
+main{flex:1;min-width:0;height:100vh;overflow-y:auto;scroll-snap-type:y mandatory}
+.slide{scroll-snap-align:start;min-height:100vh;display:flex;flex-direction:column;align-items:center;justify-content:center;padding:1rem}
+.slide img{max-width:100%;max-height:calc(100vh - 6rem);background:#fff;box-shadow:0 2px 12px #000}
+.slide .text{width:100%;max-width:60rem;margin-top:.5rem;font-size:.75rem;color:#999;white-space:pre-wrap}
+.slide .number{font-size:.75rem;color:#777;margin-top:.25rem}
+@media (max-width:48rem){body{display:block}nav{position:static;height:auto;width:auto;border-right:none;border-bottom:1px solid #333}main{height:auto}}
+"#;
+
+const SCRIPT: &str = r#"
+(()=>{
+const main=document.querySelector("main");
+const slides=[...document.querySelectorAll(".slide")];
+const links=[...document.querySelectorAll("nav a")];
+const current=()=>{const top=main.scrollTop;let i=0;slides.forEach((s,j)=>{if(s.offsetTop-main.offsetTop<=top+1)i=j});return i};
+const go=i=>{const s=slides[Math.max(0,Math.min(slides.length-1,i))];if(s)s.scrollIntoView({block:"start"})};
+document.addEventListener("keydown",e=>{
+if(e.target instanceof HTMLInputElement||e.ctrlKey||e.metaKey||e.altKey)return;
+switch(e.key){
+case "ArrowRight":case "ArrowDown":case "PageDown":case " ":case "j":go(current()+1);break;
+case "ArrowLeft":case "ArrowUp":case "PageUp":case "k":go(current()-1);break;
+case "Home":go(0);break;
+case "End":go(slides.length-1);break;
+default:return}
+e.preventDefault()});
+main.addEventListener("scroll",()=>{const slide=slides[current()];if(!slide)return;const section=slide.dataset.section;links.forEach(a=>a.classList.toggle("current",a.dataset.section===section))},{passive:true});
+})();
+"#;
+
+/// スライドを1つのオフラインHTMLファイルに変換
+///
+/// 画像は`PreProcessor::preprocess`で埋め込み済みである必要がある。
+pub fn to_html(title: &str, sections: &[HtmlSection]) -> Result<String, ExportError> {
+    let mut nav = String::new();
+    let mut slides = String::new();
+
+    for (section_index, section) in sections.iter().enumerate() {
+        let section_id = format!("s{section_index}");
+        let section_title = encode_text(&section.title);
+        nav.push_str(&format!(
+            r##"<li><a href="#{section_id}-0" data-section="{section_id}">{section_title}</a></li>"##
+        ));
+
+        let count = section.content.slide_count();
+        for (slide_index, svg) in section.content.svgs.iter().enumerate() {
+            let content = with_namespaces(svg)?;
+            let data = general_purpose::STANDARD.encode(content.as_bytes());
+            let texts = svg.get_texts();
+            let alt = encode_double_quoted_attribute(&texts.join(" ")).to_string();
+            let text = encode_text(&texts.join("\n")).to_string();
+
+            slides.push_str(&format!(
+                r#"<section class="slide" id="{section_id}-{slide_index}" data-section="{section_id}"><img src="data:image/svg+xml;base64,{data}" alt="{alt}"><div class="number">{section_title} {number}/{count}</div><div class="text">{text}</div></section>"#,
+                number = slide_index + 1,
+            ));
+        }
+    }
+
+    let title = encode_text(title);
+    Ok(format!(
+        r#"<!DOCTYPE html>
+<html lang="ja">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width,initial-scale=1">
+<title>{title}</title>
+<style>{STYLE}</style>
+</head>
+<body>
+<nav><h1>{title}</h1><ol>{nav}</ol></nav>
+<main>{slides}</main>
+<script>{SCRIPT}</script>
+</body>
+</html>
+"#
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{domain::models::ProcessedSvg, testing};
+
+    #[test]
+    fn test_to_html() {
+        let content = SlideContent::new(
+            testing::page_key(),
+            vec![
+                ProcessedSvg::new(r#"<svg><g role="img" aria-label="a &lt; b"></g></svg>"#, 0),
+                ProcessedSvg::new("<svg></svg>", 1),
+            ],
+        );
+
+        let html = to_html("講義", &[HtmlSection::new("ページ", &content)]).unwrap();
+
+        assert_eq!(html.matches(r#"class="slide""#).count(), 2);
+        assert!(html.contains(r##"href="#s0-0""##));
+        assert!(html.contains("a &lt; b"));
+        assert!(html.contains("data:image/svg+xml;base64,"));
+    }
+}
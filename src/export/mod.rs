@@ -0,0 +1,15 @@
+mod assets;
+mod diff;
+mod error;
+mod html;
+mod raster;
+mod svg;
+mod transcript;
+
+pub use assets::{AssetEntry, AssetMap, AssetWriter, ASSET_MAP_FILE};
+pub use diff::{diff, DiffConfig, LineChange, PageChange, PageDiff, Region, SlideDiff};
+pub use error::ExportError;
+pub use html::{to_html, HtmlSection};
+pub use raster::{render, render_svg, RasterFormat, RasterOptions};
+pub use svg::{standalone_svgs, to_standalone_svg};
+pub use transcript::{Transcript, TranscriptEntry};
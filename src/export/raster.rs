@@ -0,0 +1,153 @@
+use super::error::ExportError;
+use crate::domain::models::{ProcessedSvg, SlideContent};
+use image::{codecs::webp::WebPEncoder, ExtendedColorType};
+use rayon::prelude::*;
+use resvg::{
+    tiny_skia::{Pixmap, Transform},
+    usvg::{Options, Tree},
+};
+
+/// SVGのユーザー単位あたりのDPI
+pub(super) const SVG_DPI: f32 = 96.0;
+
+/// ラスター画像の出力形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RasterFormat {
+    #[default]
+    Png,
+    Webp,
+}
+
+impl RasterFormat {
+    /// ファイル拡張子
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RasterFormat::Png => "png",
+            RasterFormat::Webp => "webp",
+        }
+    }
+}
+
+/// ラスター画像出力設定
+#[derive(Debug, Clone)]
+pub struct RasterOptions {
+    pub format: RasterFormat,
+    pub dpi: f32,
+}
+
+impl Default for RasterOptions {
+    fn default() -> Self {
+        Self {
+            format: RasterFormat::Png,
+            dpi: 192.0,
+        }
+    }
+}
+
+/// SlideContentの各ページをラスター画像に変換
+pub fn render(slide: &SlideContent, options: &RasterOptions) -> Result<Vec<Vec<u8>>, ExportError> {
+    slide
+        .svgs
+        .par_iter()
+        .map(|svg| render_svg(svg, options))
+        .collect()
+}
+
+/// 単一のSVGをラスター画像に変換
+pub fn render_svg(svg: &ProcessedSvg, options: &RasterOptions) -> Result<Vec<u8>, ExportError> {
+    let tree = Tree::from_str(&svg.content, &Options::default())
+        .map_err(|e| ExportError::SvgParsing(e.to_string()))?;
+    let pixmap = rasterize(&tree, options.dpi)?;
+
+    match options.format {
+        RasterFormat::Png => pixmap
+            .encode_png()
+            .map_err(|e| ExportError::Encoding(e.to_string())),
+        RasterFormat::Webp => encode_webp(&pixmap),
+    }
+}
+
+/// usvgのツリーを指定DPIでPixmapに描画
+pub(super) fn rasterize(tree: &Tree, dpi: f32) -> Result<Pixmap, ExportError> {
+    let scale = dpi / SVG_DPI;
+    let size = tree.size();
+    let invalid_size = || ExportError::InvalidSize {
+        width: size.width() * scale,
+        height: size.height() * scale,
+    };
+
+    let pixel_size = size
+        .to_int_size()
+        .scale_by(scale)
+        .ok_or_else(invalid_size)?;
+    let mut pixmap =
+        Pixmap::new(pixel_size.width(), pixel_size.height()).ok_or_else(invalid_size)?;
+    resvg::render(
+        tree,
+        Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+
+    Ok(pixmap)
+}
+
+/// PixmapをロスレスWebPにエンコード
+fn encode_webp(pixmap: &Pixmap) -> Result<Vec<u8>, ExportError> {
+    // tiny-skiaは乗算済みアルファで保持しているため戻す
+    let rgba = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect::<Vec<_>>();
+
+    let mut output = vec![];
+    WebPEncoder::new_lossless(&mut output)
+        .encode(
+            &rgba,
+            pixmap.width(),
+            pixmap.height(),
+            ExtendedColorType::Rgba8,
+        )
+        .map_err(|e| ExportError::Encoding(e.to_string()))?;
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SVG: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" width="40" height="30"><rect width="40" height="30" fill="red"/></svg>"#;
+
+    #[test]
+    fn test_render_png_scales_with_dpi() {
+        let svg = ProcessedSvg::new(SVG, 0);
+        let options = RasterOptions {
+            format: RasterFormat::Png,
+            dpi: 192.0,
+        };
+
+        let png = render_svg(&svg, &options).unwrap();
+        let image = image::load_from_memory(&png).unwrap();
+
+        assert!(png.starts_with(b"\x89PNG"));
+        assert_eq!((image.width(), image.height()), (80, 60));
+    }
+
+    #[test]
+    fn test_render_webp() {
+        let svg = ProcessedSvg::new(SVG, 0);
+        let options = RasterOptions {
+            format: RasterFormat::Webp,
+            dpi: 96.0,
+        };
+
+        let webp = render_svg(&svg, &options).unwrap();
+
+        assert_eq!(&webp[0..4], b"RIFF");
+        assert_eq!(&webp[8..12], b"WEBP");
+    }
+}
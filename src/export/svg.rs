@@ -0,0 +1,96 @@
+use super::error::ExportError;
+use crate::domain::models::{ProcessedSvg, SlideContent};
+use lol_html::{element, HtmlRewriter, Settings};
+
+const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+const XLINK_NAMESPACE: &str = "http://www.w3.org/1999/xlink";
+const XML_DECLARATION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+
+/// SlideContentの各ページを単体で開けるSVGに変換
+///
+/// 画像は`PreProcessor::preprocess`で埋め込み済みである必要がある。
+pub fn standalone_svgs(slide: &SlideContent) -> Result<Vec<String>, ExportError> {
+    slide.svgs.iter().map(to_standalone_svg).collect()
+}
+
+/// 単一のSVGを単体で開けるSVGに変換
+pub fn to_standalone_svg(svg: &ProcessedSvg) -> Result<String, ExportError> {
+    let content = with_namespaces(svg)?;
+    if content.starts_with("<?xml") {
+        Ok(content)
+    } else {
+        Ok(format!("{XML_DECLARATION}\n{content}"))
+    }
+}
+
+/// 名前空間を補い、外部参照が残っていないことを確認
+pub(super) fn with_namespaces(svg: &ProcessedSvg) -> Result<String, ExportError> {
+    let mut external = None;
+    let mut output = vec![];
+    let mut rewriter = HtmlRewriter::new(
+        Settings {
+            element_content_handlers: vec![
+                element!("svg", |el| {
+                    if !el.has_attribute("xmlns") {
+                        el.set_attribute("xmlns", SVG_NAMESPACE)?;
+                    }
+                    if !el.has_attribute("xmlns:xlink") {
+                        el.set_attribute("xmlns:xlink", XLINK_NAMESPACE)?;
+                    }
+                    Ok(())
+                }),
+                element!("image", |el| {
+                    let href = el
+                        .get_attribute("xlink:href")
+                        .or_else(|| el.get_attribute("href"));
+                    if let Some(href) = href.filter(|href| !href.starts_with("data:")) {
+                        external.get_or_insert(href);
+                    }
+                    Ok(())
+                }),
+            ],
+            ..Settings::default()
+        },
+        |c: &[u8]| output.extend_from_slice(c),
+    );
+
+    rewriter.write(svg.content.trim_start().as_bytes())?;
+    rewriter.end()?;
+
+    if let Some(href) = external {
+        return Err(ExportError::ExternalReference(href));
+    }
+
+    Ok(String::from_utf8(output)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_standalone_svg_adds_namespaces() {
+        let svg = ProcessedSvg::new(
+            r#"<svg width="10" height="10"><image xlink:href="data:image/png;base64,AAAA"/></svg>"#,
+            0,
+        );
+
+        let standalone = to_standalone_svg(&svg).unwrap();
+
+        assert!(standalone.starts_with(XML_DECLARATION));
+        assert!(standalone.contains(r#"xmlns="http://www.w3.org/2000/svg""#));
+        assert!(standalone.contains(r#"xmlns:xlink="http://www.w3.org/1999/xlink""#));
+    }
+
+    #[test]
+    fn test_standalone_svg_rejects_external_images() {
+        let svg = ProcessedSvg::new(
+            r#"<svg><image xlink:href="https://example.com/a.png"/></svg>"#,
+            0,
+        );
+
+        let result = to_standalone_svg(&svg);
+
+        assert!(matches!(result, Err(ExportError::ExternalReference(_))));
+    }
+}
@@ -0,0 +1,108 @@
+use super::error::ExportError;
+use crate::domain::models::SlideContent;
+use serde::{Deserialize, Serialize};
+
+/// スライド1ページ分のテキスト
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    pub page_key: String,
+    pub slide_index: usize,
+    pub page_index: usize,
+    pub title: String,
+    pub lines: Vec<String>,
+}
+
+/// ページまたは講義単位のテキスト書き起こし
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transcript {
+    pub title: String,
+    pub entries: Vec<TranscriptEntry>,
+}
+
+impl Transcript {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            entries: vec![],
+        }
+    }
+
+    /// SlideContentのテキストを追加
+    ///
+    /// `slide_index`はページ内のスライド（埋め込み）の番号。
+    pub fn push(&mut self, title: impl Into<String>, slide_index: usize, content: &SlideContent) {
+        let title = title.into();
+        let page_key = content.page_key.to_string();
+        self.entries
+            .extend(content.svgs.iter().map(|svg| TranscriptEntry {
+                page_key: page_key.clone(),
+                slide_index,
+                page_index: svg.index,
+                title: title.clone(),
+                lines: svg.get_texts(),
+            }));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// プレーンテキストに変換
+    pub fn to_text(&self) -> String {
+        let mut text = format!("# {}\n", self.title);
+        for entry in &self.entries {
+            text.push_str(&format!(
+                "\n## {} [{}] ({}-{})\n",
+                entry.title,
+                entry.page_key,
+                entry.slide_index + 1,
+                entry.page_index + 1
+            ));
+            for line in &entry.lines {
+                text.push_str(line);
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    /// JSONに変換
+    pub fn to_json(&self) -> Result<String, ExportError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{domain::models::ProcessedSvg, testing};
+
+    #[test]
+    fn test_transcript() {
+        let content = SlideContent::new(
+            testing::page_key(),
+            vec![
+                ProcessedSvg::new(
+                    r#"<g role="img" aria-label="Line 1"></g><g role="img" aria-label="Line 2"></g>"#,
+                    0,
+                ),
+                ProcessedSvg::new(r#"<g role="img" aria-label="Line 3"></g>"#, 1),
+            ],
+        );
+
+        let mut transcript = Transcript::new("Lecture");
+        transcript.push("Page", 0, &content);
+
+        assert_eq!(transcript.entries.len(), 2);
+        assert_eq!(transcript.entries[0].page_key, "2024/CS101/01/01-1");
+        assert_eq!(transcript.entries[0].lines, vec!["Line 1", "Line 2"]);
+        assert_eq!(transcript.entries[1].page_index, 1);
+
+        let text = transcript.to_text();
+        assert!(text.contains("## Page [2024/CS101/01/01-1] (1-2)\nLine 3\n"));
+
+        let json = transcript.to_json().unwrap();
+        let parsed: Transcript = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, transcript);
+    }
+}
@@ -1,8 +1,11 @@
+#[cfg(test)]
+mod testing;
 mod utils;
 
 pub mod cache;
 pub mod domain;
 pub mod error;
+pub mod export;
 pub mod pdf;
 pub mod repository;
 pub mod service;
@@ -0,0 +1,250 @@
+use super::PdfConversionError;
+use lol_html::{element, HtmlRewriter, Settings};
+use std::borrow::Cow;
+use svg2pdf::usvg::{Group, Node, Options, Paint, Tree};
+
+/// 色を指定する属性・プロパティ
+const COLOR_PROPERTIES: [&str; 6] = [
+    "fill",
+    "stroke",
+    "stop-color",
+    "flood-color",
+    "lighting-color",
+    "color",
+];
+
+/// 背景とみなす塗りがページを覆う割合
+const BACKGROUND_COVERAGE: f32 = 0.9;
+
+/// 暗い背景とみなす輝度（0〜255）
+const DARK_LUMA: f32 = 96.0;
+
+/// スライドの配色
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    /// 元の配色のまま
+    #[default]
+    Original,
+    /// 暗い背景のスライドの明暗を反転し、明るい背景にする（色味は保つ）
+    Print,
+    /// `Print`に加えてグレースケールにする
+    Grayscale,
+}
+
+/// SVGの塗りと線の色を印刷向けに書き換える
+///
+/// 色は属性と`style`属性のものだけを書き換え、`<image>`（写真など）には手を加えない。
+pub(crate) fn apply<'a>(
+    svg: &'a str,
+    mode: ColorMode,
+    options: &Options,
+) -> Result<Cow<'a, str>, PdfConversionError> {
+    if mode == ColorMode::Original {
+        return Ok(Cow::Borrowed(svg));
+    }
+    let tree =
+        Tree::from_str(svg, options).map_err(|e| PdfConversionError::SvgParsing(e.to_string()))?;
+    let invert = background(&tree).is_some_and(|color| luma(color) < DARK_LUMA);
+    let grayscale = mode == ColorMode::Grayscale;
+    if !invert && !grayscale {
+        return Ok(Cow::Borrowed(svg));
+    }
+
+    let convert = |color: [u8; 3]| {
+        let color = if invert { invert_luma(color) } else { color };
+        if grayscale {
+            let value = luma(color).round() as u8;
+            [value; 3]
+        } else {
+            color
+        }
+    };
+    let map_value = |value: &str| parse(value).map(|color| hex(convert(color)));
+
+    let mut output = vec![];
+    let mut rewriter = HtmlRewriter::new(
+        Settings {
+            element_content_handlers: vec![element!("*", |el| {
+                if el.tag_name().eq_ignore_ascii_case("image") {
+                    return Ok(());
+                }
+                for name in COLOR_PROPERTIES {
+                    if let Some(value) = el.get_attribute(name).as_deref().and_then(map_value) {
+                        el.set_attribute(name, &value)?;
+                    }
+                }
+                if let Some(style) = el.get_attribute("style") {
+                    let declarations = style
+                        .split(';')
+                        .map(|declaration| match declaration.split_once(':') {
+                            Some((property, value))
+                                if COLOR_PROPERTIES
+                                    .contains(&property.trim().to_ascii_lowercase().as_str()) =>
+                            {
+                                map_value(value)
+                                    .map(|value| format!("{}:{value}", property.trim()))
+                                    .unwrap_or_else(|| declaration.to_string())
+                            }
+                            _ => declaration.to_string(),
+                        })
+                        .collect::<Vec<_>>();
+                    el.set_attribute("style", &declarations.join(";"))?;
+                }
+                Ok(())
+            })],
+            ..Settings::default()
+        },
+        |c: &[u8]| output.extend_from_slice(c),
+    );
+
+    rewriter.write(svg.as_bytes())?;
+    rewriter.end()?;
+
+    Ok(Cow::Owned(String::from_utf8(output)?))
+}
+
+/// 最背面でページのほぼ全体を覆う単色の塗り
+fn background(tree: &Tree) -> Option<[u8; 3]> {
+    let size = tree.size();
+    find_background(
+        tree.root(),
+        size.width() * size.height() * BACKGROUND_COVERAGE,
+    )
+}
+
+fn find_background(group: &Group, min_area: f32) -> Option<[u8; 3]> {
+    for node in group.children() {
+        match node {
+            Node::Group(group) => {
+                if let Some(color) = find_background(group, min_area) {
+                    return Some(color);
+                }
+            }
+            Node::Path(path) => {
+                let rect = path.abs_bounding_box();
+                let Some(fill) = path.fill() else {
+                    continue;
+                };
+                // Google スライドは透明な塗りでページ全体を覆うことがある
+                if fill.opacity().get() < 0.5 || rect.width() * rect.height() < min_area {
+                    continue;
+                }
+                if let Paint::Color(color) = fill.paint() {
+                    return Some([color.red, color.green, color.blue]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// ITU-R BT.601の輝度
+fn luma([r, g, b]: [u8; 3]) -> f32 {
+    0.299 * f32::from(r) + 0.587 * f32::from(g) + 0.114 * f32::from(b)
+}
+
+/// 色差を保ったまま輝度を反転
+fn invert_luma(color: [u8; 3]) -> [u8; 3] {
+    let [r, _, b] = color.map(f32::from);
+    let y = luma(color);
+    let inverted = 255.0 - y;
+    let (r, b) = (inverted + (r - y), inverted + (b - y));
+    let g = (inverted - 0.299 * r - 0.114 * b) / 0.587;
+    [r, g, b].map(|channel| channel.round().clamp(0.0, 255.0) as u8)
+}
+
+/// `#rgb`・`#rrggbb`・`rgb(r, g, b)`・`black`・`white`を解析（それ以外はNone）
+fn parse(value: &str) -> Option<[u8; 3]> {
+    let value = value.trim().to_ascii_lowercase();
+    match value.as_str() {
+        "black" => return Some([0, 0, 0]),
+        "white" => return Some([255, 255, 255]),
+        _ => {}
+    }
+    if let Some(hex) = value.strip_prefix('#') {
+        let digits = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<Vec<_>>>()?;
+        return match digits[..] {
+            [r, g, b] => Some([r * 17, g * 17, b * 17]),
+            [r1, r0, g1, g0, b1, b0] => Some([r1 * 16 + r0, g1 * 16 + g0, b1 * 16 + b0]),
+            _ => None,
+        };
+    }
+    let channels = value
+        .strip_prefix("rgb(")?
+        .strip_suffix(')')?
+        .split(',')
+        .map(|channel| channel.trim().parse::<u8>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    channels.try_into().ok()
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DARK: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="160" height="90"><path fill="#000000" fill-opacity="0.0" d="M0 0H160V90H0Z"/><path fill="#1a1a2e" d="M0 0H160V90H0Z"/><text fill="#ffffff" style="stroke: rgb(255, 255, 0); opacity: 0.5">Title</text><rect fill="url(#g)" width="10" height="10"/><image fill="#ffffff" width="10" height="10"/></svg>"##;
+
+    fn convert(svg: &str, mode: ColorMode) -> String {
+        apply(svg, mode, &Options::default()).unwrap().into_owned()
+    }
+
+    #[test]
+    fn test_dark_background_is_inverted() {
+        let svg = convert(DARK, ColorMode::Print);
+
+        assert!(svg.contains(r##"<path fill="#ffffff" fill-opacity="0.0""##));
+        assert!(svg.contains(r##"<path fill="#e0e0f4""##));
+        assert!(svg.contains(r##"<text fill="#000000" style="stroke:#3a3a00; opacity: 0.5">"##));
+        assert!(svg.contains(r##"fill="url(#g)""##));
+        // 写真などの画像はそのまま
+        assert!(svg.contains(r##"<image fill="#ffffff""##));
+    }
+
+    #[test]
+    fn test_light_background_is_kept() {
+        let light = DARK.replace("#1a1a2e", "#fafafa");
+
+        assert_eq!(convert(&light, ColorMode::Print), light);
+        assert_eq!(convert(DARK, ColorMode::Original), DARK);
+        // 背景がなければ反転しない
+        let transparent = r##"<svg xmlns="http://www.w3.org/2000/svg" width="160" height="90"><rect fill="#000000" width="10" height="10"/></svg>"##;
+        assert_eq!(convert(transparent, ColorMode::Print), transparent);
+    }
+
+    #[test]
+    fn test_grayscale() {
+        let light = DARK.replace("#1a1a2e", "#3366cc");
+
+        let svg = convert(&light, ColorMode::Grayscale);
+
+        // 背景の輝度は約98なので反転しない
+        assert!(svg.contains(r##"<path fill="#626262""##));
+        assert!(svg.contains(r##"<text fill="#ffffff" style="stroke:#e2e2e2; opacity: 0.5">"##));
+    }
+
+    #[test]
+    fn test_invert_luma() {
+        assert_eq!(invert_luma([255, 255, 255]), [0, 0, 0]);
+        assert_eq!(invert_luma([0, 0, 0]), [255, 255, 255]);
+        assert_eq!(invert_luma([26, 26, 46]), [224, 224, 244]);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("#fff"), Some([255, 255, 255]));
+        assert_eq!(parse(" #1A1a2e "), Some([26, 26, 46]));
+        assert_eq!(parse("rgb(1, 2, 3)"), Some([1, 2, 3]));
+        assert_eq!(parse("Black"), Some([0, 0, 0]));
+        assert_eq!(parse("url(#abc)"), None);
+        assert_eq!(parse("none"), None);
+        assert_eq!(parse("#ffff"), None);
+    }
+}
@@ -0,0 +1,230 @@
+use lopdf::{Dictionary, Object, ObjectId};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+
+/// 同一内容の画像XObjectを1つにまとめる
+///
+/// svg2pdfはページごとに画像を書き出すため、結合したドキュメントでは
+/// 同じロゴや背景が何度も含まれる。既出の画像を記憶しているため、
+/// 複数回に分けて渡されたオブジェクトの間でも重複を除ける。
+#[derive(Debug, Default)]
+pub(crate) struct ImageDeduplicator {
+    canonical: HashMap<[u8; 32], ObjectId>,
+}
+
+impl ImageDeduplicator {
+    /// 既出の画像と同じ内容の画像を削除し、参照を置き換える
+    pub(crate) fn apply(&mut self, objects: &mut BTreeMap<ObjectId, Object>) {
+        // SMaskを参照する画像は、先にSMask自体をまとめてから比較する
+        self.deduplicate(objects, false);
+        self.deduplicate(objects, true);
+    }
+
+    fn deduplicate(&mut self, objects: &mut BTreeMap<ObjectId, Object>, with_mask: bool) {
+        let mut replacements = BTreeMap::new();
+
+        for (&id, object) in objects.iter() {
+            let Ok(stream) = object.as_stream() else {
+                continue;
+            };
+            if !is_image(&stream.dict) || stream.dict.has(b"SMask") != with_mask {
+                continue;
+            }
+
+            let key = image_key(&stream.dict, &stream.content);
+            match self.canonical.get(&key) {
+                Some(&first) => {
+                    replacements.insert(id, first);
+                }
+                None => {
+                    self.canonical.insert(key, id);
+                }
+            }
+        }
+
+        if replacements.is_empty() {
+            return;
+        }
+        for object in objects.values_mut() {
+            replace_references(object, &replacements);
+        }
+        for id in replacements.keys() {
+            objects.remove(id);
+        }
+    }
+}
+
+fn is_image(dict: &Dictionary) -> bool {
+    dict.get(b"Subtype").and_then(Object::as_name).ok() == Some(&b"Image"[..])
+}
+
+/// 画像の内容と描画に関わる属性のハッシュ
+fn image_key(dict: &Dictionary, content: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hash_dictionary(&mut hasher, dict, |key| key != b"Length");
+    hash_bytes(&mut hasher, content);
+    hasher.finalize().into()
+}
+
+/// 型のタグと長さを付けてオブジェクトをハッシュに加える
+///
+/// 異なるオブジェクトが同じバイト列にならないよう、値は長さ付きで区切る。
+fn hash_object(hasher: &mut Sha256, object: &Object) {
+    match object {
+        Object::Null => hasher.update(b"n"),
+        Object::Boolean(value) => hasher.update([b'b', u8::from(*value)]),
+        Object::Integer(value) => {
+            hasher.update(b"i");
+            hasher.update(value.to_be_bytes());
+        }
+        Object::Real(value) => {
+            hasher.update(b"r");
+            hasher.update(value.to_bits().to_be_bytes());
+        }
+        Object::Name(name) => {
+            hasher.update(b"N");
+            hash_bytes(hasher, name);
+        }
+        // リテラルか16進かは表記の違いなので区別しない
+        Object::String(bytes, _) => {
+            hasher.update(b"s");
+            hash_bytes(hasher, bytes);
+        }
+        Object::Array(items) => {
+            hasher.update(b"a");
+            hasher.update((items.len() as u64).to_be_bytes());
+            items.iter().for_each(|item| hash_object(hasher, item));
+        }
+        Object::Dictionary(dict) => hash_dictionary(hasher, dict, |_| true),
+        Object::Stream(stream) => {
+            hasher.update(b"S");
+            hash_dictionary(hasher, &stream.dict, |key| key != b"Length");
+            hash_bytes(hasher, &stream.content);
+        }
+        Object::Reference((id, generation)) => {
+            hasher.update(b"R");
+            hasher.update(id.to_be_bytes());
+            hasher.update(generation.to_be_bytes());
+        }
+    }
+}
+
+/// キーの順に辞書をハッシュに加える（`include`が偽のキーは除く）
+fn hash_dictionary(hasher: &mut Sha256, dict: &Dictionary, include: impl Fn(&[u8]) -> bool) {
+    let mut entries = dict
+        .iter()
+        .filter(|(key, _)| include(key))
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    hasher.update(b"d");
+    hasher.update((entries.len() as u64).to_be_bytes());
+    for (key, value) in entries {
+        hash_bytes(hasher, key);
+        hash_object(hasher, value);
+    }
+}
+
+fn hash_bytes(hasher: &mut Sha256, bytes: &[u8]) {
+    hasher.update((bytes.len() as u64).to_be_bytes());
+    hasher.update(bytes);
+}
+
+fn replace_references(object: &mut Object, replacements: &BTreeMap<ObjectId, ObjectId>) {
+    match object {
+        Object::Reference(id) => {
+            if let Some(&replacement) = replacements.get(id) {
+                *id = replacement;
+            }
+        }
+        Object::Array(items) => items
+            .iter_mut()
+            .for_each(|item| replace_references(item, replacements)),
+        Object::Dictionary(dict) => dict
+            .iter_mut()
+            .for_each(|(_, value)| replace_references(value, replacements)),
+        Object::Stream(stream) => stream
+            .dict
+            .iter_mut()
+            .for_each(|(_, value)| replace_references(value, replacements)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, Document, Stream};
+
+    fn image(document: &mut Document, data: &[u8], smask: Option<ObjectId>) -> ObjectId {
+        let mut dict = dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => 1,
+            "Height" => 1,
+            "ColorSpace" => "DeviceRGB",
+            "BitsPerComponent" => 8,
+        };
+        if let Some(smask) = smask {
+            dict.set("SMask", smask);
+        }
+        document.add_object(Stream::new(dict, data.to_vec()))
+    }
+
+    #[test]
+    fn test_deduplicate_images() {
+        let mut document = Document::with_version("1.5");
+        let mask_a = image(&mut document, &[255], None);
+        let mask_b = image(&mut document, &[255], None);
+        let a = image(&mut document, &[1, 2, 3], Some(mask_a));
+        let b = image(&mut document, &[1, 2, 3], Some(mask_b));
+        let c = image(&mut document, &[4, 5, 6], None);
+        let page_a = document.add_object(dictionary! { "XObject" => dictionary! { "Im0" => a } });
+        let page_b = document
+            .add_object(dictionary! { "XObject" => dictionary! { "Im0" => b, "Im1" => c } });
+
+        ImageDeduplicator::default().apply(&mut document.objects);
+
+        let xobject = |page| {
+            document
+                .get_dictionary(page)
+                .unwrap()
+                .get(b"XObject")
+                .unwrap()
+                .as_dict()
+                .unwrap()
+                .get(b"Im0")
+                .unwrap()
+                .as_reference()
+                .unwrap()
+        };
+        assert_eq!(xobject(page_a), xobject(page_b));
+        assert!(document.get_object(b).is_err());
+        assert!(document.get_object(mask_b).is_err());
+        assert!(document.get_object(c).is_ok());
+    }
+
+    #[test]
+    fn test_image_key_distinguishes_attributes() {
+        let dict = |color_space: Object| {
+            dictionary! {
+                "Subtype" => "Image",
+                "ColorSpace" => color_space,
+                "Length" => 3,
+            }
+        };
+        let rgb = image_key(&dict("DeviceRGB".into()), &[1, 2, 3]);
+
+        // Lengthと辞書の順序は結果に影響しない
+        let mut reordered = dictionary! { "Length" => 4, "ColorSpace" => "DeviceRGB" };
+        reordered.set("Subtype", "Image");
+        assert_eq!(image_key(&reordered, &[1, 2, 3]), rgb);
+
+        assert_ne!(image_key(&dict("DeviceGray".into()), &[1, 2, 3]), rgb);
+        assert_ne!(image_key(&dict("DeviceRGB".into()), &[1, 2, 4]), rgb);
+        // 名前と文字列は同じバイト列でも区別する
+        assert_ne!(
+            image_key(&dict(Object::string_literal("DeviceRGB")), &[1, 2, 3]),
+            rgb
+        );
+    }
+}
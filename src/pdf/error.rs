@@ -18,6 +18,36 @@ pub enum PdfConversionError {
     Utf8Conversion(#[from] std::string::FromUtf8Error),
     #[error("PDF document loading failed: {0}")]
     PdfLoading(#[from] lopdf::Error),
+    #[error("Page layout failed: {0}")]
+    Layout(String),
+    #[error("PDF/A conformance failed: {0}")]
+    Conformance(String),
+    #[error("PDF writing failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Unsafe SVG: {0}")]
+    Sanitize(#[from] SanitizeError),
+}
+
+#[derive(Error, Debug)]
+pub enum SanitizeError {
+    #[error("SVG is too large ({size} bytes, limit {limit})")]
+    TooLarge { size: usize, limit: usize },
+    #[error("SVG has more than {limit} elements")]
+    TooManyElements { limit: usize },
+    #[error("SVG has more than {limit} paths")]
+    TooManyPaths { limit: usize },
+    #[error("SVG elements are nested deeper than {limit}")]
+    TooDeep { limit: usize },
+    #[error("Entity declarations are not allowed")]
+    EntityDeclaration,
+    #[error("Data URI is too large ({size} bytes, limit {limit})")]
+    DataUriTooLarge { size: usize, limit: usize },
+    #[error("Embedded image is too large ({width}x{height})")]
+    ImageTooLarge { width: u32, height: u32 },
+    #[error("Embedded SVG is nested deeper than {limit}")]
+    NestingTooDeep { limit: usize },
+    #[error("Malformed SVG: {0}")]
+    Malformed(String),
 }
 
 #[derive(Error, Debug)]
@@ -0,0 +1,143 @@
+use super::PdfConversionError;
+use scraper::{Html, Selector};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
+use svg2pdf::usvg::{fontdb::Database, Group, Node, Tree};
+
+/// SVG描画に使うフォントの設定
+///
+/// CJKフォントは同梱していない。OSにインストールするか、
+/// フォントファイルを置いたディレクトリを`font_dirs`に指定する。
+#[derive(Debug, Clone)]
+pub struct FontConfig {
+    /// OSにインストールされたフォントを読み込む
+    pub system_fonts: bool,
+    pub font_dirs: Vec<PathBuf>,
+    pub font_files: Vec<PathBuf>,
+}
+
+impl Default for FontConfig {
+    fn default() -> Self {
+        Self {
+            system_fonts: true,
+            font_dirs: vec![],
+            font_files: vec![],
+        }
+    }
+}
+
+impl FontConfig {
+    /// フォントデータベースを作成
+    pub(crate) fn database(&self) -> Result<Database, PdfConversionError> {
+        let mut database = Database::new();
+        if self.system_fonts {
+            database.load_system_fonts();
+        }
+        for dir in &self.font_dirs {
+            database.load_fonts_dir(dir);
+        }
+        for file in &self.font_files {
+            database
+                .load_font_file(file)
+                .map_err(|_| PdfConversionError::FontLoading)?;
+        }
+        Ok(database)
+    }
+}
+
+/// フォントに含まれず描画できなかった文字の報告
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FontReport {
+    /// SVGのインデックスごとの欠落文字
+    pub missing_glyphs: BTreeMap<usize, BTreeSet<char>>,
+}
+
+impl FontReport {
+    pub fn is_empty(&self) -> bool {
+        self.missing_glyphs.is_empty()
+    }
+
+    /// すべてのSVGで欠落した文字
+    pub fn characters(&self) -> BTreeSet<char> {
+        self.missing_glyphs.values().flatten().copied().collect()
+    }
+
+    pub(crate) fn insert(&mut self, index: usize, characters: BTreeSet<char>) {
+        if !characters.is_empty() {
+            self.missing_glyphs.insert(index, characters);
+        }
+    }
+}
+
+/// SVGのテキストのうちグリフが見つからなかった文字を収集
+///
+/// フォントが1つも見つからないテキストはusvgのツリーから除かれるため、元のSVGと比較する。
+pub(crate) fn missing_glyphs(source: &str, tree: &Tree) -> BTreeSet<char> {
+    let selector = Selector::parse("text").unwrap();
+    let mut rendered = BTreeSet::new();
+    collect_rendered_chars(tree.root(), &mut rendered);
+
+    Html::parse_fragment(source)
+        .select(&selector)
+        .flat_map(|element| element.text())
+        .flat_map(str::chars)
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .filter(|c| !rendered.contains(c))
+        .collect()
+}
+
+/// .notdef（ID 0）以外のグリフで描画された文字を収集
+fn collect_rendered_chars(group: &Group, rendered: &mut BTreeSet<char>) {
+    for node in group.children() {
+        match node {
+            Node::Group(group) => collect_rendered_chars(group, rendered),
+            Node::Text(text) => rendered.extend(
+                text.layouted()
+                    .iter()
+                    .flat_map(|span| &span.positioned_glyphs)
+                    .filter(|glyph| glyph.id.0 != 0)
+                    .flat_map(|glyph| glyph.text.chars()),
+            ),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use svg2pdf::usvg::Options;
+
+    #[test]
+    fn test_missing_glyphs_without_fonts() {
+        let config = FontConfig {
+            system_fonts: false,
+            ..FontConfig::default()
+        };
+        let options = Options {
+            fontdb: Arc::new(config.database().unwrap()),
+            ..Options::default()
+        };
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100"><text x="0" y="50">あ A</text></svg>"#;
+        let tree = Tree::from_str(svg, &options).unwrap();
+
+        assert_eq!(missing_glyphs(svg, &tree), BTreeSet::from(['あ', 'A']));
+    }
+
+    #[test]
+    fn test_missing_font_file() {
+        let config = FontConfig {
+            system_fonts: false,
+            font_files: vec![PathBuf::from("/nonexistent/font.otf")],
+            ..FontConfig::default()
+        };
+
+        assert!(matches!(
+            config.database(),
+            Err(PdfConversionError::FontLoading)
+        ));
+    }
+}
@@ -0,0 +1,82 @@
+use super::PdfConversionError;
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+
+/// ページを再利用可能なForm XObjectに変換
+///
+/// 戻り値はXObjectのIDとBBox（`[x0, y0, x1, y1]`）。
+pub(crate) fn page_to_form(
+    document: &mut Document,
+    page_id: ObjectId,
+) -> Result<(ObjectId, [f32; 4]), PdfConversionError> {
+    let bbox = page_box(document, page_id)?;
+    let resources = inherited(document, page_id, b"Resources")?
+        .unwrap_or_else(|| Object::Dictionary(dictionary! {}));
+    let content = document.get_page_content(page_id)?;
+
+    let mut stream = Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => bbox.iter().map(|&v| v.into()).collect::<Vec<Object>>(),
+            "Resources" => resources,
+        },
+        content,
+    );
+    // 圧縮できなくても内容は有効なので無視する
+    let _ = stream.compress();
+
+    Ok((document.add_object(stream), bbox))
+}
+
+/// ページのMediaBoxを取得（親から継承された値も含む）
+pub(crate) fn page_box(
+    document: &Document,
+    page_id: ObjectId,
+) -> Result<[f32; 4], PdfConversionError> {
+    let media_box = inherited(document, page_id, b"MediaBox")?
+        .ok_or_else(|| PdfConversionError::Layout("MediaBox not found".to_string()))?;
+    let values = document
+        .dereference(&media_box)?
+        .1
+        .as_array()?
+        .iter()
+        .map(|value| value.as_float())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match values[..] {
+        [x0, y0, x1, y1] => Ok([x0, y0, x1, y1]),
+        _ => Err(PdfConversionError::Layout(format!(
+            "invalid MediaBox: {values:?}"
+        ))),
+    }
+}
+
+/// ページツリーを遡って継承可能な属性を取得
+pub(crate) fn inherited(
+    document: &Document,
+    page_id: ObjectId,
+    key: &[u8],
+) -> Result<Option<Object>, PdfConversionError> {
+    let mut node = document.get_dictionary(page_id)?;
+    loop {
+        if let Ok(value) = node.get(key) {
+            return Ok(Some(document.dereference(value)?.1.clone()));
+        }
+        match node.get(b"Parent").and_then(Object::as_reference) {
+            Ok(parent_id) => node = document.get_dictionary(parent_id)?,
+            Err(_) => return Ok(None),
+        }
+    }
+}
+
+/// リソース辞書の下位の辞書（参照なら解決したもの）を複製して取得
+pub(crate) fn sub_dictionary(
+    document: &Document,
+    resources: &Dictionary,
+    key: &[u8],
+) -> Result<Dictionary, PdfConversionError> {
+    match resources.get(key) {
+        Ok(value) => Ok(document.dereference(value)?.1.as_dict()?.clone()),
+        Err(_) => Ok(Dictionary::new()),
+    }
+}
@@ -0,0 +1,325 @@
+use super::PdfConversionError;
+use crate::cache::Cache;
+use bytes::Bytes;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::PathBuf,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+use tokio::sync::{OnceCell, Semaphore};
+
+/// 画像データのSHA-256
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ContentHash([u8; 32]);
+
+impl ContentHash {
+    pub fn of(bytes: &[u8]) -> Self {
+        Self(Sha256::digest(bytes).into())
+    }
+
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        if hex.len() != 64 || !hex.is_ascii() {
+            return None;
+        }
+        let mut hash = [0; 32];
+        for (i, byte) in hash.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+        }
+        Some(Self(hash))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Display for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+/// 画像キャッシュの設定
+#[derive(Debug, Clone)]
+pub struct ImageCacheConfig {
+    /// 同時に取得する画像の最大数
+    pub max_concurrency: usize,
+    /// メモリ上に保持する期間
+    pub ttl: Duration,
+    /// ディスク上の保存先（Noneならメモリのみ）
+    pub disk_store: Option<PathBuf>,
+}
+
+impl Default for ImageCacheConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrency: 8,
+            ttl: Duration::from_secs(60 * 60),
+            disk_store: None,
+        }
+    }
+}
+
+/// URLと内容のハッシュで引ける画像キャッシュ
+///
+/// クローンしたキャッシュは同じ保存領域と同時取得数の上限を共有する。
+#[derive(Debug, Clone)]
+pub struct ImageCache {
+    urls: Cache<String, ContentHash>,
+    contents: Cache<ContentHash, Bytes>,
+    /// 取得中のURLごとの結果（同じURLの同時取得をまとめる）
+    in_flight: Arc<Mutex<HashMap<String, Arc<OnceCell<Bytes>>>>>,
+    semaphore: Arc<Semaphore>,
+    disk_store: Option<PathBuf>,
+}
+
+impl Default for ImageCache {
+    fn default() -> Self {
+        Self::new(ImageCacheConfig::default())
+    }
+}
+
+impl ImageCache {
+    pub fn new(config: ImageCacheConfig) -> Self {
+        Self {
+            urls: Cache::new(config.ttl),
+            contents: Cache::new(config.ttl),
+            in_flight: Arc::default(),
+            semaphore: Arc::new(Semaphore::new(config.max_concurrency.max(1))),
+            disk_store: config.disk_store,
+        }
+    }
+
+    /// キャッシュから取得し、なければダウンロード
+    ///
+    /// 同じURLを同時に取得した場合、ダウンロードは1回だけ行い結果を共有する。
+    pub async fn fetch(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+    ) -> Result<Bytes, PdfConversionError> {
+        if let Some(bytes) = self.get(url) {
+            return Ok(bytes);
+        }
+
+        let cell = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(url.to_string())
+            .or_default()
+            .clone();
+        let result = cell
+            .get_or_try_init(|| self.download(client, url))
+            .await
+            .cloned();
+
+        // 完了した取得を取り除く（その後に始まった取得は残す）
+        let mut in_flight = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if in_flight
+            .get(url)
+            .is_some_and(|current| Arc::ptr_eq(current, &cell))
+        {
+            in_flight.remove(url);
+        }
+        result
+    }
+
+    async fn download(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+    ) -> Result<Bytes, PdfConversionError> {
+        // 直前に完了した取得がキャッシュに登録している場合がある
+        if let Some(bytes) = self.get(url) {
+            return Ok(bytes);
+        }
+
+        // セマフォは閉じないため取得に失敗しない
+        let _permit = self.semaphore.acquire().await.ok();
+        // エラーページを画像としてキャッシュしない
+        let bytes = client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        self.insert(url, bytes.clone());
+        Ok(bytes)
+    }
+
+    /// URLに対応する画像を取得
+    pub fn get(&self, url: &str) -> Option<Bytes> {
+        if let Some(bytes) = self
+            .urls
+            .get(&url.to_string())
+            .and_then(|hash| self.get_by_hash(&hash))
+        {
+            return Some(bytes);
+        }
+
+        let hash = self.read_url_index(url)?;
+        let bytes = self.get_by_hash(&hash)?;
+        self.urls.insert(url.to_string(), hash);
+        Some(bytes)
+    }
+
+    /// 内容のハッシュに対応する画像を取得
+    pub fn get_by_hash(&self, hash: &ContentHash) -> Option<Bytes> {
+        if let Some(bytes) = self.contents.get(hash) {
+            return Some(bytes);
+        }
+
+        let bytes = Bytes::from(fs::read(self.object_path(hash)?).ok()?);
+        // 破損したファイルは使わない
+        if ContentHash::of(&bytes) != *hash {
+            return None;
+        }
+        self.contents.insert(*hash, bytes.clone());
+        Some(bytes)
+    }
+
+    /// 画像を登録し、内容のハッシュを返す
+    pub fn insert(&self, url: &str, bytes: Bytes) -> ContentHash {
+        let hash = ContentHash::of(&bytes);
+        self.write_to_disk(url, &hash, &bytes);
+        self.urls.insert(url.to_string(), hash);
+        self.contents.insert(hash, bytes);
+        hash
+    }
+
+    /// ディスクへの保存（キャッシュなので失敗は無視する）
+    fn write_to_disk(&self, url: &str, hash: &ContentHash, bytes: &[u8]) {
+        let (Some(object_path), Some(url_path)) = (self.object_path(hash), self.url_path(url))
+        else {
+            return;
+        };
+        for path in [&object_path, &url_path] {
+            if let Some(parent) = path.parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+        }
+        if !object_path.exists() {
+            let _ = fs::write(&object_path, bytes);
+        }
+        let _ = fs::write(&url_path, hash.to_string());
+    }
+
+    fn read_url_index(&self, url: &str) -> Option<ContentHash> {
+        let hex = fs::read_to_string(self.url_path(url)?).ok()?;
+        ContentHash::from_hex(hex.trim())
+    }
+
+    fn object_path(&self, hash: &ContentHash) -> Option<PathBuf> {
+        let hex = hash.to_string();
+        Some(
+            self.disk_store
+                .as_ref()?
+                .join("objects")
+                .join(&hex[..2])
+                .join(hex),
+        )
+    }
+
+    fn url_path(&self, url: &str) -> Option<PathBuf> {
+        let hex = ContentHash::of(url.as_bytes()).to_string();
+        Some(
+            self.disk_store
+                .as_ref()?
+                .join("urls")
+                .join(&hex[..2])
+                .join(hex),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_hash_hex_roundtrip() {
+        let hash = ContentHash::of(b"image");
+        let hex = hash.to_string();
+
+        assert_eq!(hex.len(), 64);
+        assert_eq!(ContentHash::from_hex(&hex), Some(hash));
+        assert_eq!(ContentHash::from_hex("zz"), None);
+    }
+
+    #[test]
+    fn test_memory_cache_by_url_and_hash() {
+        let cache = ImageCache::default();
+        let hash = cache.insert("https://example.com/a.png", Bytes::from_static(b"png"));
+
+        assert_eq!(
+            cache.get("https://example.com/a.png"),
+            Some(Bytes::from_static(b"png"))
+        );
+        assert_eq!(cache.get_by_hash(&hash), Some(Bytes::from_static(b"png")));
+        assert_eq!(cache.get("https://example.com/b.png"), None);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_concurrent_requests_once() {
+        use std::{
+            io::{Read, Write},
+            net::TcpListener,
+            sync::atomic::{AtomicUsize, Ordering},
+            thread,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/a.png", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request);
+                // 他の取得が待機するまで応答を遅らせる
+                thread::sleep(Duration::from_millis(100));
+                let _ = stream.write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\nConnection: close\r\n\r\npng",
+                );
+            }
+        });
+
+        let cache = ImageCache::default();
+        let client = reqwest::Client::new();
+        let results = futures::future::join_all((0..8).map(|_| cache.fetch(&client, &url))).await;
+
+        for result in results {
+            assert_eq!(result.unwrap(), Bytes::from_static(b"png"));
+        }
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_disk_store() {
+        let dir = std::env::temp_dir().join(format!("collect-image-cache-{}", std::process::id()));
+        let config = ImageCacheConfig {
+            disk_store: Some(dir.clone()),
+            ..ImageCacheConfig::default()
+        };
+        ImageCache::new(config.clone())
+            .insert("https://example.com/a.png", Bytes::from_static(b"png"));
+
+        // 別のインスタンスからディスク経由で読める
+        let cache = ImageCache::new(config);
+        assert_eq!(
+            cache.get("https://example.com/a.png"),
+            Some(Bytes::from_static(b"png"))
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,437 @@
+use super::{form::page_to_form, text::TextFont, PdfConversionError};
+use lopdf::{
+    content::{Content, Operation},
+    dictionary, Dictionary, Document, Object, Stream,
+};
+
+/// 用紙の余白（pt）
+const MARGIN: f32 = 36.0;
+/// スライド間の間隔（pt）
+const GAP: f32 = 14.0;
+/// ヘッダーの高さ（pt）
+const HEADER_HEIGHT: f32 = 20.0;
+const HEADER_FONT_SIZE: f32 = 10.0;
+/// スライド番号の高さ（pt）
+const CAPTION_HEIGHT: f32 = 12.0;
+const CAPTION_FONT_SIZE: f32 = 8.0;
+/// メモ用罫線の間隔（pt）
+const LINE_SPACING: f32 = 18.0;
+const FONT_NAME: &str = "F1";
+
+/// 配布資料1枚あたりのスライド数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SlidesPerPage {
+    Two,
+    #[default]
+    Four,
+    Six,
+    Nine,
+}
+
+impl SlidesPerPage {
+    /// 枚数から変換（2, 4, 6, 9のみ）
+    pub fn from_count(count: usize) -> Option<Self> {
+        match count {
+            2 => Some(Self::Two),
+            4 => Some(Self::Four),
+            6 => Some(Self::Six),
+            9 => Some(Self::Nine),
+            _ => None,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        match self {
+            Self::Two => 2,
+            Self::Four => 4,
+            Self::Six => 6,
+            Self::Nine => 9,
+        }
+    }
+
+    /// (列数, 行数)
+    fn grid(&self) -> (usize, usize) {
+        match self {
+            Self::Two => (1, 2),
+            Self::Four => (2, 2),
+            Self::Six => (2, 3),
+            Self::Nine => (3, 3),
+        }
+    }
+}
+
+/// 用紙サイズ（縦向き）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PaperSize {
+    #[default]
+    A4,
+    Letter,
+}
+
+impl PaperSize {
+    /// (幅, 高さ)（pt）
+    pub fn dimensions(&self) -> (f32, f32) {
+        match self {
+            Self::A4 => (595.276, 841.89),
+            Self::Letter => (612.0, 792.0),
+        }
+    }
+}
+
+/// 配布資料のヘッダー
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandoutHeader {
+    pub course: String,
+    pub lecture: String,
+}
+
+/// 配布資料の設定
+#[derive(Debug, Clone)]
+pub struct HandoutConfig {
+    pub slides_per_page: SlidesPerPage,
+    pub paper: PaperSize,
+    /// メモ用の罫線を入れる
+    pub note_lines: bool,
+    /// スライド番号を表示する
+    pub slide_numbers: bool,
+    pub header: Option<HandoutHeader>,
+}
+
+impl Default for HandoutConfig {
+    fn default() -> Self {
+        Self {
+            slides_per_page: SlidesPerPage::Four,
+            paper: PaperSize::A4,
+            note_lines: false,
+            slide_numbers: true,
+            header: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+impl Rect {
+    fn top(&self) -> f32 {
+        self.y + self.height
+    }
+}
+
+/// 1ページ1スライドのPDFを配布資料形式（N-up）に変換
+///
+/// 元のページはForm XObjectとして配置されるため、ベクターのまま縮小される。
+/// ページを指すしおりは無効になるため削除する。
+pub fn handout(
+    mut document: Document,
+    config: &HandoutConfig,
+) -> Result<Document, PdfConversionError> {
+    let page_ids = document.get_pages().into_values().collect::<Vec<_>>();
+    let forms = page_ids
+        .into_iter()
+        .map(|page_id| page_to_form(&mut document, page_id))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let pages_id = document.catalog()?.get(b"Pages")?.as_reference()?;
+    // 配布資料は埋め込みなしのフォントで描画する
+    let mut font = TextFont::new(&mut document, None, []);
+    let (paper_width, paper_height) = config.paper.dimensions();
+    let per_page = config.slides_per_page.count();
+    let (columns, rows) = config.slides_per_page.grid();
+    let sheet_count = forms.len().div_ceil(per_page);
+
+    let header_height = if config.header.is_some() {
+        HEADER_HEIGHT
+    } else {
+        0.0
+    };
+    let area = Rect {
+        x: MARGIN,
+        y: MARGIN,
+        width: paper_width - MARGIN * 2.0,
+        height: paper_height - MARGIN * 2.0 - header_height,
+    };
+    let cell_width = (area.width - GAP * (columns - 1) as f32) / columns as f32;
+    let cell_height = (area.height - GAP * (rows - 1) as f32) / rows as f32;
+
+    let mut kids = vec![];
+    for (sheet_index, sheet) in forms.chunks(per_page).enumerate() {
+        let mut operations = vec![];
+        let mut xobjects = Dictionary::new();
+
+        if let Some(header) = &config.header {
+            operations.extend(header_operations(
+                &mut font,
+                header,
+                sheet_index + 1,
+                sheet_count,
+                &area,
+                paper_height,
+            ));
+        }
+
+        for (index, (form_id, bbox)) in sheet.iter().enumerate() {
+            let (column, row) = (index % columns, index / columns);
+            let cell = Rect {
+                x: area.x + column as f32 * (cell_width + GAP),
+                y: area.top() - row as f32 * (cell_height + GAP) - cell_height,
+                width: cell_width,
+                height: cell_height,
+            };
+            let (slide_area, notes_area) = split_cell(&cell, config.note_lines);
+
+            let name = format!("S{index}");
+            xobjects.set(name.clone(), *form_id);
+            operations.extend(slide_operations(
+                &mut font,
+                &name,
+                bbox,
+                &slide_area,
+                config
+                    .slide_numbers
+                    .then(|| sheet_index * per_page + index + 1),
+            ));
+            if let Some(notes_area) = notes_area {
+                operations.extend(note_line_operations(&notes_area));
+            }
+        }
+
+        let content = Content { operations }.encode()?;
+        let mut stream = Stream::new(dictionary! {}, content);
+        let _ = stream.compress();
+        let content_id = document.add_object(stream);
+
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), paper_width.into(), paper_height.into()],
+            "Resources" => dictionary! {
+                "XObject" => xobjects,
+                "Font" => dictionary! { FONT_NAME => font.id() },
+            },
+            "Contents" => content_id,
+        });
+        kids.push(Object::Reference(page_id));
+    }
+
+    let count = kids.len() as u32;
+    let pages = document.get_dictionary_mut(pages_id)?;
+    pages.set("Kids", kids);
+    pages.set("Count", count);
+    document.catalog_mut()?.remove(b"Outlines");
+    font.finish(&mut document)?;
+    document.prune_objects();
+
+    Ok(document)
+}
+
+/// セルをスライド領域とメモ領域に分割
+fn split_cell(cell: &Rect, note_lines: bool) -> (Rect, Option<Rect>) {
+    if !note_lines {
+        return (*cell, None);
+    }
+
+    if cell.width > cell.height * 1.2 {
+        // 横長のセルは左にスライド、右にメモ
+        let half = (cell.width - GAP) / 2.0;
+        let slide = Rect {
+            width: half,
+            ..*cell
+        };
+        let notes = Rect {
+            x: cell.x + half + GAP,
+            width: half,
+            ..*cell
+        };
+        (slide, Some(notes))
+    } else {
+        // 縦長のセルは上にスライド、下にメモ
+        let slide_height = cell.height * 0.55;
+        let slide = Rect {
+            y: cell.top() - slide_height,
+            height: slide_height,
+            ..*cell
+        };
+        let notes = Rect {
+            height: cell.height - slide_height - GAP / 2.0,
+            ..*cell
+        };
+        (slide, Some(notes))
+    }
+}
+
+/// 講義名とシート番号のヘッダー
+fn header_operations(
+    font: &mut TextFont,
+    header: &HandoutHeader,
+    sheet: usize,
+    sheet_count: usize,
+    area: &Rect,
+    paper_height: f32,
+) -> Vec<Operation> {
+    let baseline = paper_height - MARGIN - HEADER_FONT_SIZE;
+    let number = format!("{sheet}/{sheet_count}");
+    let number_width = font.width(&number, HEADER_FONT_SIZE);
+    let title = font.fit(
+        &format!("{} / {}", header.course, header.lecture),
+        HEADER_FONT_SIZE,
+        area.width - number_width - GAP,
+    );
+
+    let mut operations = vec![Operation::new("q", vec![])];
+    operations.extend(font.show(FONT_NAME, HEADER_FONT_SIZE, area.x, baseline, &title));
+    operations.extend(font.show(
+        FONT_NAME,
+        HEADER_FONT_SIZE,
+        area.x + area.width - number_width,
+        baseline,
+        &number,
+    ));
+    // ヘッダー下の区切り線
+    let rule = baseline - 5.0;
+    operations.extend([
+        Operation::new("w", vec![0.5.into()]),
+        Operation::new("G", vec![0.5.into()]),
+        Operation::new("m", vec![area.x.into(), rule.into()]),
+        Operation::new("l", vec![(area.x + area.width).into(), rule.into()]),
+        Operation::new("S", vec![]),
+        Operation::new("Q", vec![]),
+    ]);
+    operations
+}
+
+/// スライドを縦横比を保って領域内に配置
+fn slide_operations(
+    font: &mut TextFont,
+    name: &str,
+    bbox: &[f32; 4],
+    area: &Rect,
+    number: Option<usize>,
+) -> Vec<Operation> {
+    let caption_height = if number.is_some() {
+        CAPTION_HEIGHT
+    } else {
+        0.0
+    };
+    let (form_width, form_height) = (bbox[2] - bbox[0], bbox[3] - bbox[1]);
+    let available_height = area.height - caption_height;
+    let scale = (area.width / form_width).min(available_height / form_height);
+    let (slide_width, slide_height) = (form_width * scale, form_height * scale);
+    let x = area.x + (area.width - slide_width) / 2.0;
+    let y = area.y + caption_height + (available_height - slide_height) / 2.0;
+
+    let mut operations = vec![
+        Operation::new("q", vec![]),
+        Operation::new(
+            "cm",
+            vec![
+                scale.into(),
+                0.into(),
+                0.into(),
+                scale.into(),
+                (x - bbox[0] * scale).into(),
+                (y - bbox[1] * scale).into(),
+            ],
+        ),
+        Operation::new("Do", vec![Object::Name(name.as_bytes().to_vec())]),
+        Operation::new("Q", vec![]),
+        // 白背景のスライドが紙面と区別できるように枠線を引く
+        Operation::new("q", vec![]),
+        Operation::new("w", vec![0.5.into()]),
+        Operation::new("G", vec![0.6.into()]),
+        Operation::new(
+            "re",
+            vec![x.into(), y.into(), slide_width.into(), slide_height.into()],
+        ),
+        Operation::new("S", vec![]),
+        Operation::new("Q", vec![]),
+    ];
+
+    if let Some(number) = number {
+        let caption = number.to_string();
+        let caption_x = x + (slide_width - font.width(&caption, CAPTION_FONT_SIZE)) / 2.0;
+        operations.extend(font.show(
+            FONT_NAME,
+            CAPTION_FONT_SIZE,
+            caption_x,
+            y - CAPTION_HEIGHT + 3.0,
+            &caption,
+        ));
+    }
+
+    operations
+}
+
+/// メモ用の罫線
+fn note_line_operations(area: &Rect) -> Vec<Operation> {
+    let mut operations = vec![
+        Operation::new("q", vec![]),
+        Operation::new("w", vec![0.4.into()]),
+        Operation::new("G", vec![0.7.into()]),
+    ];
+
+    let mut y = area.top() - LINE_SPACING;
+    while y >= area.y {
+        operations.extend([
+            Operation::new("m", vec![area.x.into(), y.into()]),
+            Operation::new("l", vec![(area.x + area.width).into(), y.into()]),
+        ]);
+        y -= LINE_SPACING;
+    }
+    operations.extend([Operation::new("S", vec![]), Operation::new("Q", vec![])]);
+    operations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pdf::form::page_box, testing};
+
+    #[test]
+    fn test_handout_page_count() {
+        let config = HandoutConfig {
+            slides_per_page: SlidesPerPage::Six,
+            note_lines: true,
+            header: Some(HandoutHeader {
+                course: "コース".to_string(),
+                lecture: "講義".to_string(),
+            }),
+            ..HandoutConfig::default()
+        };
+
+        let mut document = handout(
+            testing::document(7, 160, 90, b"0 0 1 rg 0 0 160 90 re f"),
+            &config,
+        )
+        .unwrap();
+        let pages = document.get_pages();
+
+        assert_eq!(pages.len(), 2);
+        let first = *pages.get(&1).unwrap();
+        assert_eq!(
+            page_box(&document, first).unwrap(),
+            [0.0, 0.0, 595.276, 841.89]
+        );
+        let xobjects = document
+            .get_dictionary(first)
+            .and_then(|page| page.get(b"Resources"))
+            .and_then(Object::as_dict)
+            .and_then(|resources| resources.get(b"XObject"))
+            .and_then(Object::as_dict)
+            .unwrap();
+        assert_eq!(xobjects.len(), 6);
+
+        document.save_to(&mut vec![]).unwrap();
+    }
+
+    #[test]
+    fn test_slides_per_page_from_count() {
+        assert_eq!(SlidesPerPage::from_count(9), Some(SlidesPerPage::Nine));
+        assert_eq!(SlidesPerPage::from_count(3), None);
+    }
+}
@@ -0,0 +1,203 @@
+use super::{form::page_box, PdfConversionError};
+use lol_html::{element, html_content::ContentType, HtmlRewriter, Settings};
+use lopdf::{dictionary, Document, Object};
+use svg2pdf::usvg::Tree;
+
+/// `<a>`要素の中身を囲む`<g>`のIDの接頭辞（スライドのIDと衝突しないようにする）
+const ID_PREFIX: &str = "collect-link-";
+
+/// SVGの`<a>`要素のリンク先
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Anchor {
+    /// 中身を囲んだ`<g>`のID
+    id: String,
+    uri: String,
+}
+
+/// `<a>`要素の中身をIDを付けた`<g>`で囲み、リンク先を抽出
+///
+/// usvgは`<a>`を通常のグループとして扱いリンク先を捨てるため、
+/// 描画後に範囲を求められるように目印を付けておく。
+pub(crate) fn mark_anchors(svg: &str) -> Result<(String, Vec<Anchor>), PdfConversionError> {
+    let mut anchors = vec![];
+    let mut output = vec![];
+    let mut rewriter = HtmlRewriter::new(
+        Settings {
+            element_content_handlers: vec![element!("a", |el| {
+                let href = el
+                    .get_attribute("xlink:href")
+                    .or_else(|| el.get_attribute("href"));
+                let Some(uri) = href.map(|href| href.trim().to_string()) else {
+                    return Ok(());
+                };
+                if el.is_self_closing() || !is_external(&uri) {
+                    return Ok(());
+                }
+
+                let id = format!("{ID_PREFIX}{}", anchors.len());
+                el.prepend(&format!(r#"<g id="{id}">"#), ContentType::Html);
+                el.append("</g>", ContentType::Html);
+                anchors.push(Anchor { id, uri });
+                Ok(())
+            })],
+            ..Settings::default()
+        },
+        |c: &[u8]| output.extend_from_slice(c),
+    );
+
+    rewriter.write(svg.as_bytes())?;
+    rewriter.end()?;
+
+    Ok((String::from_utf8(output)?, anchors))
+}
+
+/// PDFのビューアーで開けるリンク先かどうか（文書内の参照は対象外）
+fn is_external(uri: &str) -> bool {
+    let scheme = uri
+        .split_once(':')
+        .map(|(scheme, _)| scheme.to_ascii_lowercase());
+    matches!(scheme.as_deref(), Some("http" | "https" | "mailto"))
+}
+
+/// `<a>`要素の描画範囲にURIのリンク注釈を追加
+///
+/// `document`は`tree`を変換した1ページのドキュメント。範囲はページ内に切り詰め、
+/// 描画されなかった`<a>`要素は無視する。
+pub(crate) fn annotate(
+    document: &mut Document,
+    tree: &Tree,
+    anchors: &[Anchor],
+) -> Result<(), PdfConversionError> {
+    if anchors.is_empty() {
+        return Ok(());
+    }
+    let Some(&page_id) = document.get_pages().values().next() else {
+        return Ok(());
+    };
+    let bbox = page_box(document, page_id)?;
+    // svg2pdfはSVGの1pxを1ptとして、上下を反転して描画する
+    let height = tree.size().height();
+
+    let mut links = vec![];
+    for anchor in anchors {
+        let Some(node) = tree.node_by_id(&anchor.id) else {
+            continue;
+        };
+        let rect = node.abs_bounding_box();
+        let x0 = rect.left().max(bbox[0]);
+        let y0 = (height - rect.bottom()).max(bbox[1]);
+        let x1 = rect.right().min(bbox[2]);
+        let y1 = (height - rect.top()).min(bbox[3]);
+        if x0 >= x1 || y0 >= y1 {
+            continue;
+        }
+
+        links.push(Object::Reference(document.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Link",
+            "Rect" => vec![x0.into(), y0.into(), x1.into(), y1.into()],
+            "Border" => vec![0.into(), 0.into(), 0.into()],
+            "A" => dictionary! {
+                "S" => "URI",
+                "URI" => Object::string_literal(anchor.uri.as_str()),
+            },
+        })));
+    }
+
+    if !links.is_empty() {
+        document.get_dictionary_mut(page_id)?.set("Annots", links);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use svg2pdf::usvg::Options;
+
+    const SVG: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="200" height="100"><a xlink:href="https://example.com/docs"><rect x="10" y="20" width="30" height="10"/></a><a href="#slide"><rect width="5" height="5"/></a><a xlink:href="mailto:ta@example.com"><rect x="150" y="90" width="100" height="50"/></a><a xlink:href="https://example.com/empty"></a></svg>"##;
+
+    #[test]
+    fn test_mark_anchors() {
+        let (svg, anchors) = mark_anchors(SVG).unwrap();
+
+        assert_eq!(
+            anchors,
+            vec![
+                Anchor {
+                    id: "collect-link-0".to_string(),
+                    uri: "https://example.com/docs".to_string(),
+                },
+                Anchor {
+                    id: "collect-link-1".to_string(),
+                    uri: "mailto:ta@example.com".to_string(),
+                },
+                Anchor {
+                    id: "collect-link-2".to_string(),
+                    uri: "https://example.com/empty".to_string(),
+                },
+            ]
+        );
+        assert!(svg.contains(
+            r#"<a xlink:href="https://example.com/docs"><g id="collect-link-0"><rect x="10" y="20" width="30" height="10"/></g></a>"#
+        ));
+        assert!(svg.contains(r##"<a href="#slide"><rect"##));
+    }
+
+    #[test]
+    fn test_annotate() {
+        let (svg, anchors) = mark_anchors(SVG).unwrap();
+        let tree = Tree::from_str(&svg, &Options::default()).unwrap();
+        let mut document = testing::document(1, 200, 100, b"");
+
+        annotate(&mut document, &tree, &anchors).unwrap();
+
+        let page_id = *document.get_pages().values().next().unwrap();
+        let annots = document
+            .get_dictionary(page_id)
+            .unwrap()
+            .get(b"Annots")
+            .unwrap()
+            .as_array()
+            .unwrap();
+        // 中身のない`<a>`は描画されないため注釈を作らない
+        assert_eq!(annots.len(), 2);
+
+        let link = |index: usize| {
+            let id = annots[index].as_reference().unwrap();
+            let annot = document.get_dictionary(id).unwrap();
+            let rect = annot
+                .get(b"Rect")
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|v| v.as_float().unwrap())
+                .collect::<Vec<_>>();
+            let uri = annot
+                .get(b"A")
+                .and_then(Object::as_dict)
+                .and_then(|action| action.get(b"URI"))
+                .and_then(Object::as_str)
+                .unwrap()
+                .to_vec();
+            (rect, uri)
+        };
+        assert_eq!(
+            link(0),
+            (
+                vec![10.0, 70.0, 40.0, 80.0],
+                b"https://example.com/docs".to_vec()
+            )
+        );
+        // ページからはみ出した部分は切り詰める
+        assert_eq!(
+            link(1),
+            (
+                vec![150.0, 0.0, 200.0, 10.0],
+                b"mailto:ta@example.com".to_vec()
+            )
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use super::{
+    writer::{BookmarkId, PdfWriter, StreamConfig},
+    PdfConversionError,
+};
+use lopdf::{Document, ObjectId};
+use std::io::Write;
+
+/// 結合するドキュメントとしおりの階層
+///
+/// `outline`は上位から順にしおりのタイトルを並べたもの（例: コース → 講義 → ページ → スライド）。
+/// 直前のドキュメントと共通する上位の階層は同じしおりにまとめられ、
+/// 新しい階層のしおりはこのドキュメントの最初のページを指す。
+#[derive(Debug, Clone)]
+pub struct MergeInput {
+    pub document: Document,
+    pub outline: Vec<String>,
+}
+
+impl MergeInput {
+    pub fn new(document: Document, outline: Vec<String>) -> Self {
+        Self { document, outline }
+    }
+}
+
+impl From<Document> for MergeInput {
+    fn from(document: Document) -> Self {
+        Self::new(document, vec![])
+    }
+}
+
+/// 複数のドキュメントを順に結合し、しおりの階層を付ける
+///
+/// 結果はメモリ上に展開される。大量のファイルを結合する場合は`merge_to`を使う。
+pub fn merge(inputs: impl IntoIterator<Item = MergeInput>) -> Result<Document, PdfConversionError> {
+    let bytes = merge_to(inputs.into_iter().map(Ok), vec![], &StreamConfig::default())?;
+    Ok(Document::load_mem(&bytes)?)
+}
+
+/// 複数のドキュメントを順に結合しながら書き出す
+///
+/// 入力は1つずつ取り出して書き出すため、ファイルの読み込みをイテレーターの中で行えば
+/// メモリには1ファイル分しか展開されない。
+pub fn merge_to<W: Write>(
+    inputs: impl IntoIterator<Item = Result<MergeInput, PdfConversionError>>,
+    output: W,
+    config: &StreamConfig,
+) -> Result<W, PdfConversionError> {
+    let mut writer = PdfWriter::new(output, config)?;
+    let mut outline = OutlineBuilder::default();
+
+    for input in inputs {
+        let MergeInput {
+            document,
+            outline: path,
+        } = input?;
+        let pages = writer.add_document(document)?;
+        if let Some(&first_page) = pages.first() {
+            outline.add(&mut writer, &path, first_page);
+        }
+    }
+
+    writer.finish()
+}
+
+/// 直前の入力のしおりの階層を保持し、共通する階層を再利用する
+#[derive(Debug, Default)]
+struct OutlineBuilder {
+    stack: Vec<(String, BookmarkId)>,
+}
+
+impl OutlineBuilder {
+    fn add<W: Write>(&mut self, writer: &mut PdfWriter<W>, path: &[String], page: ObjectId) {
+        let common = self
+            .stack
+            .iter()
+            .zip(path)
+            .take_while(|((title, _), other)| title == *other)
+            .count();
+        self.stack.truncate(common);
+
+        for title in &path[common..] {
+            let parent = self.stack.last().map(|&(_, id)| id);
+            let id = writer.add_bookmark(title.clone(), page, parent);
+            self.stack.push((title.clone(), id));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use lopdf::{Dictionary, Object};
+
+    /// 100×100ptのページを指定枚数持つドキュメント（内容はページごとに変える）
+    fn document(pages: usize) -> Document {
+        let mut document = Document::with_version("1.7");
+        let pages = (0..pages)
+            .map(|i| testing::page(&mut document, 100, 100, format!("% page {i}").as_bytes()))
+            .collect();
+        testing::with_pages(document, pages)
+    }
+
+    fn outline(path: &[&str]) -> Vec<String> {
+        path.iter().map(ToString::to_string).collect()
+    }
+
+    /// しおりのタイトルを階層ごとに収集
+    fn titles(document: &Document, dict: &Dictionary, depth: usize, out: &mut Vec<String>) {
+        let mut next = dict.get(b"First").and_then(Object::as_reference).ok();
+        while let Some(id) = next {
+            let item = document.get_dictionary(id).unwrap();
+            let title = item.get(b"Title").unwrap().as_str().unwrap();
+            let title = String::from_utf16(
+                &title[2..]
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                    .collect::<Vec<_>>(),
+            )
+            .unwrap();
+            out.push(format!("{}{title}", "  ".repeat(depth)));
+            titles(document, item, depth + 1, out);
+            next = item.get(b"Next").and_then(Object::as_reference).ok();
+        }
+    }
+
+    #[test]
+    fn test_merge_with_hierarchical_outline() {
+        let merged = merge([
+            MergeInput::new(document(2), outline(&["講義1", "1 - 導入"])),
+            MergeInput::new(document(1), outline(&["講義1", "1 - 導入", "(2) 導入"])),
+            MergeInput::new(document(1), outline(&["講義1", "2 - 本題"])),
+            MergeInput::new(document(3), outline(&["講義2", "1 - まとめ"])),
+            MergeInput::from(document(1)),
+        ])
+        .unwrap();
+
+        assert_eq!(merged.get_pages().len(), 8);
+
+        let catalog = merged.catalog().unwrap();
+        let outlines_id = catalog.get(b"Outlines").unwrap().as_reference().unwrap();
+        let outlines = merged.get_dictionary(outlines_id).unwrap();
+        let mut found = vec![];
+        titles(&merged, outlines, 0, &mut found);
+        assert_eq!(
+            found,
+            [
+                "講義1",
+                "  1 - 導入",
+                "    (2) 導入",
+                "  2 - 本題",
+                "講義2",
+                "  1 - まとめ",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_without_outline() {
+        let merged = merge([document(1), document(2)].map(MergeInput::from)).unwrap();
+
+        assert_eq!(merged.get_pages().len(), 3);
+        assert!(!merged.catalog().unwrap().has(b"Outlines"));
+    }
+}
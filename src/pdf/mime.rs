@@ -9,6 +9,8 @@ pub enum Mime {
     Webp,
     Bmp,
     Avif,
+    Tiff,
+    Ico,
 }
 
 impl From<Mime> for &'static str {
@@ -21,6 +23,8 @@ impl From<Mime> for &'static str {
             Mime::Webp => "image/webp",
             Mime::Bmp => "image/bmp",
             Mime::Avif => "image/avif",
+            Mime::Tiff => "image/tiff",
+            Mime::Ico => "image/vnd.microsoft.icon",
         }
     }
 }
@@ -37,6 +41,8 @@ impl TryFrom<&'static str> for Mime {
             "image/webp" => Ok(Mime::Webp),
             "image/bmp" => Ok(Mime::Bmp),
             "image/avif" => Ok(Mime::Avif),
+            "image/tiff" => Ok(Mime::Tiff),
+            "image/vnd.microsoft.icon" | "image/x-icon" => Ok(Mime::Ico),
             _ => Err(ImageConvertError::UnsupportedFormat),
         }
     }
@@ -60,6 +66,11 @@ impl TryFrom<&[u8]> for Mime {
 }
 
 impl Mime {
+    /// svg2pdfがそのまま描画できる形式かどうか
+    pub fn is_renderable(&self) -> bool {
+        matches!(self, Mime::Svg | Mime::Png | Mime::Jpeg | Mime::Gif)
+    }
+
     /// SVGかどうかを判定（パブリック関数）
     pub fn is_svg(bytes: &[u8]) -> bool {
         let content = std::str::from_utf8(bytes).unwrap_or("");
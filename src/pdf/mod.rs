@@ -1,29 +1,92 @@
+mod color;
+mod dedup;
 mod error;
+mod font;
+mod form;
+mod image_cache;
+mod layout;
+mod link;
+mod merge;
 mod mime;
+mod optimize;
+mod page;
+mod pdfa;
+mod report;
+mod sanitize;
+mod source;
+mod stamp;
+mod tag;
+mod text;
+mod transcode;
+mod writer;
 
+pub use color::ColorMode;
 use error::ImageConvertError;
-pub use error::PdfConversionError;
+pub use error::{PdfConversionError, SanitizeError};
+pub use font::{FontConfig, FontReport};
+pub use image_cache::{ContentHash, ImageCache, ImageCacheConfig};
+pub use layout::{handout, HandoutConfig, HandoutHeader, PaperSize, SlidesPerPage};
+pub use merge::{merge, merge_to, MergeInput};
+pub use optimize::{ImageOptimizeConfig, ImageSizeReport};
+pub use page::{parse_color, FitMode, Margins, PageConfig, PageSize};
+pub use pdfa::to_pdfa;
+pub use report::{PreprocessReport, PreprocessWarning};
+pub use sanitize::{sanitize, SanitizeConfig, Sanitized};
+pub use source::extract_sources;
+pub use stamp::{render_template, stamp, StampConfig, StampContext, INTERNAL_USE_WATERMARK};
+pub use tag::TagConfig;
+pub use writer::{BookmarkId, PdfWriter, StreamConfig, Timestamp};
 
 use crate::domain::models::{ProcessedSvg, SlideContent};
 use base64::{engine::general_purpose, Engine};
 use bytes::Bytes;
-use lol_html::{element, html_content::ContentType, HtmlRewriter, Settings};
-use lopdf::{dictionary, Document, Object};
+use lol_html::{
+    element,
+    html_content::{ContentType, Element},
+    HtmlRewriter, Settings,
+};
+use lopdf::Document;
 use mime::Mime;
 use rayon::prelude::*;
 use regex::Regex;
-use std::collections::HashMap;
+use std::{
+    borrow::Cow,
+    collections::{BTreeSet, HashMap, HashSet},
+    io::Write,
+    sync::{Arc, LazyLock},
+};
 use svg2pdf::{
     to_pdf,
-    usvg::{Options, Tree},
-    ConversionOptions, PageOptions,
+    usvg::{fontdb::Database, ImageHrefResolver, Options, Tree},
+    ConversionError, ConversionOptions, PageOptions,
 };
 
+/// SVGの長さに対する変換後のドキュメントの推定メモリ使用量の倍率（経験的な値）
+const SVG_EXPANSION: usize = 8;
+
+/// 取得・埋め込みに失敗した画像の扱い
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ImageFallback {
+    /// 灰色の枠に置き換える
+    #[default]
+    Placeholder,
+    /// 元のURLのまま残す（PDFには描画されない）
+    Keep,
+}
+
+/// 画像の代わりに埋め込むSVG
+const PLACEHOLDER_SVG: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100" preserveAspectRatio="none"><rect width="100" height="100" fill="#eeeeee" stroke="#aaaaaa" stroke-width="2"/><path d="M0 0L100 100M100 0L0 100" stroke="#aaaaaa" stroke-width="1"/></svg>"##;
+
 /// PDF変換設定
 #[derive(Debug, Clone)]
 pub struct PreProcessConfig {
     pub embed_images: bool,
     pub embed_text: bool,
+    /// 埋め込む画像の最適化（既定のNoneなら元の画像のまま埋め込む）
+    pub optimize_images: Option<ImageOptimizeConfig>,
+    pub image_fallback: ImageFallback,
+    /// 取得したSVGの制限（許可していない要素は取り除き、制限を超えればエラー）
+    pub sanitize: SanitizeConfig,
 }
 
 impl Default for PreProcessConfig {
@@ -31,90 +94,233 @@ impl Default for PreProcessConfig {
         Self {
             embed_images: true,
             embed_text: true,
+            optimize_images: None,
+            image_fallback: ImageFallback::default(),
+            sanitize: SanitizeConfig::default(),
         }
     }
 }
 
+/// スライドのSVGが参照する画像
+#[derive(Debug, Clone)]
+pub struct SlideImage {
+    /// 画像を参照していたSVGのインデックス
+    pub index: usize,
+    /// 取得元のURL（データURIで埋め込まれていた画像はNone）
+    pub url: Option<String>,
+    pub bytes: Bytes,
+}
+
+impl SlideImage {
+    /// 内容のハッシュ
+    pub fn hash(&self) -> ContentHash {
+        ContentHash::of(&self.bytes)
+    }
+
+    /// 内容から判定した拡張子（判定できなければNone）
+    pub fn extension(&self) -> Option<&'static str> {
+        let mime = Mime::try_from(self.bytes.as_ref()).ok()?;
+        Some(match mime {
+            Mime::Svg => "svg",
+            Mime::Png => "png",
+            Mime::Jpeg => "jpg",
+            Mime::Gif => "gif",
+            Mime::Webp => "webp",
+            Mime::Bmp => "bmp",
+            Mime::Avif => "avif",
+            Mime::Tiff => "tiff",
+            Mime::Ico => "ico",
+        })
+    }
+}
+
 /// 統合PDFコンバーター
 #[derive(Default)]
 pub struct PreProcessor {
     config: PreProcessConfig,
+    cache: ImageCache,
 }
 
 impl PreProcessor {
     /// 新しいコンバーターを作成
     pub fn new(config: PreProcessConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            cache: ImageCache::default(),
+        }
+    }
+
+    /// 画像キャッシュを設定（複数のPreProcessorで共有できる）
+    pub fn with_cache(mut self, cache: ImageCache) -> Self {
+        self.cache = cache;
+        self
     }
+
     /// SVGの前処理
     pub async fn preprocess(
         &self,
         client: &reqwest::Client,
         slide_content: &SlideContent,
     ) -> Result<SlideContent, PdfConversionError> {
+        self.preprocess_with_report(client, slide_content)
+            .await
+            .map(|(content, _)| content)
+    }
+
+    /// SVGの前処理を行い、埋め込んだ画像のサイズと警告を報告
+    ///
+    /// 画像の取得や埋め込みに失敗しても処理は続行し、`image_fallback`に従って置き換える。
+    /// 安全でない要素や属性は最初に取り除き、警告として報告する。
+    pub async fn preprocess_with_report(
+        &self,
+        client: &reqwest::Client,
+        slide_content: &SlideContent,
+    ) -> Result<(SlideContent, PreprocessReport), PdfConversionError> {
         let futures = slide_content.svgs.iter().map(|svg| async move {
-            let mut content = svg.content.clone();
+            let sanitized = sanitize::sanitize(&svg.content, &self.config.sanitize)?;
+            let mut content = sanitized.content;
+            let mut report = PreprocessReport::default();
 
             if self.config.embed_images {
-                content = self.embed_images_in_svg(client, content).await?;
+                (content, report) = self.embed_images_in_svg(client, svg.index, content).await?;
+            }
+            if !sanitized.removed.is_empty() {
+                report.warnings.push(PreprocessWarning::UnsafeContent {
+                    index: svg.index,
+                    removed: sanitized.removed.into_iter().collect(),
+                });
             }
 
             if self.config.embed_text {
                 content = self.embed_text_in_svg(content)?;
             }
 
-            Ok(content)
+            Ok::<_, PdfConversionError>((content, report))
         });
 
-        futures::future::try_join_all(futures)
-            .await
-            .map(|contents| {
-                slide_content
-                    .svgs
-                    .iter()
-                    .zip(contents)
-                    .map(|(svg, content)| ProcessedSvg {
-                        content,
-                        index: svg.index,
-                    })
-                    .collect()
+        let results = futures::future::try_join_all(futures).await?;
+
+        let mut report = PreprocessReport::default();
+        let svgs = slide_content
+            .svgs
+            .iter()
+            .zip(results)
+            .map(|(svg, (content, svg_report))| {
+                report.merge(svg_report);
+                ProcessedSvg {
+                    content,
+                    index: svg.index,
+                }
             })
-            .map(|svgs| SlideContent {
+            .collect();
+
+        Ok((
+            SlideContent {
                 svgs,
                 ..slide_content.clone()
-            })
+            },
+            report,
+        ))
+    }
+
+    /// SVGが参照する画像を、前処理と同じキャッシュを使って取得
+    ///
+    /// 画像はSVGの順、SVG内では出現順に並べる。データURIで埋め込み済みの画像も含め、
+    /// 同じSVG内で同じ参照は1回だけ返す。取得できなかった画像は警告として報告する。
+    pub async fn collect_images(
+        &self,
+        client: &reqwest::Client,
+        slide_content: &SlideContent,
+    ) -> Result<(Vec<SlideImage>, PreprocessReport), PdfConversionError> {
+        let futures = slide_content.svgs.iter().map(|svg| async move {
+            let sanitized = sanitize::sanitize(&svg.content, &self.config.sanitize)?;
+            let hrefs = self.extract_image_hrefs(&sanitized.content)?;
+            let urls = hrefs
+                .iter()
+                .filter(|href| href.starts_with("http"))
+                .cloned()
+                .collect::<Vec<_>>();
+            let (mut fetched, failed) = self.fetch_images(client, &urls).await;
+
+            let images = hrefs
+                .into_iter()
+                .filter_map(|href| match decode_data_uri(&href) {
+                    Some(bytes) => Some(SlideImage {
+                        index: svg.index,
+                        url: None,
+                        bytes,
+                    }),
+                    None => fetched.remove(&href).map(|bytes| SlideImage {
+                        index: svg.index,
+                        url: Some(href),
+                        bytes,
+                    }),
+                })
+                .collect::<Vec<_>>();
+            let mut warnings = failed
+                .into_iter()
+                .map(|(url, message)| PreprocessWarning::ImageFetch {
+                    index: svg.index,
+                    url,
+                    message,
+                })
+                .collect::<Vec<_>>();
+            warnings.sort_by(|a, b| a.url().cmp(&b.url()));
+            Ok::<_, PdfConversionError>((images, warnings))
+        });
+
+        let mut images = vec![];
+        let mut report = PreprocessReport::default();
+        for (svg_images, warnings) in futures::future::try_join_all(futures).await? {
+            images.extend(svg_images);
+            report.warnings.extend(warnings);
+        }
+        Ok((images, report))
     }
 
     /// SVG内の画像を埋め込み
     async fn embed_images_in_svg(
         &self,
         client: &reqwest::Client,
+        index: usize,
         svg_content: String,
-    ) -> Result<String, PdfConversionError> {
+    ) -> Result<(String, PreprocessReport), PdfConversionError> {
         let image_urls = self.extract_image_urls(&svg_content);
-        let images = self.fetch_images(client, &image_urls).await?;
+        let (images, failed) = self.fetch_images(client, &image_urls).await;
+        let mut sizes = ImageSizeReport::default();
+        let mut unsupported = BTreeSet::new();
 
         let mut output = vec![];
         let mut rewriter = HtmlRewriter::new(
             Settings {
                 element_content_handlers: vec![element!("image", |el| {
+                    let width_attr = el.get_attribute("width");
+                    let height_attr = el.get_attribute("height");
+
+                    let width = width_attr.as_ref().and_then(|w| w.parse::<f64>().ok());
+                    let height = height_attr.as_ref().and_then(|h| h.parse::<f64>().ok());
+
+                    let mut fallback = false;
                     if let Some(src) = el.get_attribute("xlink:href") {
-                        if let Some(bytes) = images.get(&src) {
-                            if let Ok(base64) = self.encode_base64(bytes.as_ref()) {
-                                el.set_attribute("xlink:href", &base64)?;
+                        if let Some(original) = images.get(&src) {
+                            match self.encode_image(original, width.zip(height)) {
+                                Ok((base64, output_len)) => {
+                                    sizes.add(original.len(), output_len);
+                                    el.set_attribute("xlink:href", &base64)?;
+                                }
+                                Err(_) => {
+                                    unsupported.insert(src);
+                                    fallback = true;
+                                }
                             }
+                        } else if failed.contains_key(&src) {
+                            fallback = true;
                         }
                     }
 
                     // width/heightのサニタイズ
                     const MAX_DIMENSION: f64 = 4096.0;
 
-                    let width_attr = el.get_attribute("width");
-                    let height_attr = el.get_attribute("height");
-
-                    let width = width_attr.as_ref().and_then(|w| w.parse::<f64>().ok());
-                    let height = height_attr.as_ref().and_then(|h| h.parse::<f64>().ok());
-
                     match (width, height) {
                         (Some(w), Some(h)) => {
                             if w > MAX_DIMENSION || h > MAX_DIMENSION {
@@ -144,6 +350,9 @@ impl PreProcessor {
                         (None, None) => { /* 何もしない */ }
                     }
 
+                    if fallback {
+                        self.apply_fallback(el);
+                    }
                     Ok(())
                 })],
                 ..Settings::default()
@@ -154,7 +363,34 @@ impl PreProcessor {
         rewriter.write(svg_content.as_bytes())?;
         rewriter.end()?;
 
-        Ok(String::from_utf8(output)?)
+        let mut warnings = failed
+            .into_iter()
+            .map(|(url, message)| PreprocessWarning::ImageFetch {
+                index,
+                url,
+                message,
+            })
+            .collect::<Vec<_>>();
+        warnings.sort_by(|a, b| a.url().cmp(&b.url()));
+        warnings.extend(
+            unsupported
+                .into_iter()
+                .map(|url| PreprocessWarning::UnsupportedImage { index, url }),
+        );
+        let report = PreprocessReport {
+            images: sizes,
+            warnings,
+        };
+
+        Ok((String::from_utf8(output)?, report))
+    }
+
+    /// 埋め込めなかった画像を設定に従って置き換える
+    fn apply_fallback(&self, el: &mut Element<'_, '_>) {
+        match self.config.image_fallback {
+            ImageFallback::Placeholder => el.replace(&placeholder_image(el), ContentType::Html),
+            ImageFallback::Keep => {}
+        }
     }
 
     /// SVG内にテキストを埋め込み
@@ -184,6 +420,31 @@ impl PreProcessor {
         Ok(String::from_utf8(output)?)
     }
 
+    /// SVGの`<image>`要素の参照を出現順に重複なく抽出
+    fn extract_image_hrefs(&self, svg_content: &str) -> Result<Vec<String>, PdfConversionError> {
+        let mut hrefs = vec![];
+        let mut rewriter = HtmlRewriter::new(
+            Settings {
+                element_content_handlers: vec![element!("image", |el| {
+                    let href = el
+                        .get_attribute("xlink:href")
+                        .or_else(|| el.get_attribute("href"));
+                    if let Some(href) = href.filter(|href| !hrefs.contains(href)) {
+                        hrefs.push(href);
+                    }
+                    Ok(())
+                })],
+                ..Settings::default()
+            },
+            |_: &[u8]| {},
+        );
+
+        rewriter.write(svg_content.as_bytes())?;
+        rewriter.end()?;
+
+        Ok(hrefs)
+    }
+
     /// SVGから画像URLを抽出
     fn extract_image_urls(&self, svg_content: &str) -> Vec<String> {
         let image_regex = Regex::new(r#"<image\s+(?:[^>]*?\s+)?xlink:href="([^"]*)""#).unwrap();
@@ -195,20 +456,32 @@ impl PreProcessor {
             .collect()
     }
 
-    /// 画像を取得
+    /// 画像を取得し、取得できた画像と失敗した画像のエラーメッセージを返す
     async fn fetch_images(
         &self,
         client: &reqwest::Client,
         urls: &[String],
-    ) -> Result<HashMap<String, Bytes>, PdfConversionError> {
-        let futures = urls.iter().map(|url| async move {
-            let bytes = self.fetch_image(client, url).await?;
-            Ok((url.clone(), bytes))
+    ) -> (HashMap<String, Bytes>, HashMap<String, String>) {
+        // 同じSVG内で重複するURLは1回だけ取得する
+        let urls = urls.iter().collect::<HashSet<_>>();
+        let futures = urls.into_iter().map(|url| async move {
+            let result = self.fetch_image(client, url).await;
+            (url.clone(), result)
         });
 
-        futures::future::try_join_all(futures)
-            .await
-            .map(|results| results.into_iter().collect())
+        let mut images = HashMap::new();
+        let mut failed = HashMap::new();
+        for (url, result) in futures::future::join_all(futures).await {
+            match result {
+                Ok(bytes) => {
+                    images.insert(url, bytes);
+                }
+                Err(error) => {
+                    failed.insert(url, error.to_string());
+                }
+            }
+        }
+        (images, failed)
     }
 
     /// 単一画像を取得
@@ -217,8 +490,28 @@ impl PreProcessor {
         client: &reqwest::Client,
         url: &str,
     ) -> Result<Bytes, PdfConversionError> {
-        let response = client.get(url).send().await?;
-        Ok(response.bytes().await?)
+        self.cache.fetch(client, url).await
+    }
+
+    /// 描画できる形式への変換と最適化を行い、データURIとそのバイト数を返す
+    fn encode_image(
+        &self,
+        original: &[u8],
+        rendered: Option<(f64, f64)>,
+    ) -> Result<(String, usize), ImageConvertError> {
+        let jpeg_quality = self
+            .config
+            .optimize_images
+            .as_ref()
+            .map_or(ImageOptimizeConfig::default().jpeg_quality, |config| {
+                config.jpeg_quality
+            });
+        let transcoded = transcode::transcode(original, jpeg_quality)?;
+        let bytes = match &self.config.optimize_images {
+            Some(config) => optimize::optimize(&transcoded, rendered, config),
+            None => Cow::Borrowed(transcoded.as_ref()),
+        };
+        Ok((self.encode_base64(&bytes)?, bytes.len()))
     }
 
     /// Base64エンコード
@@ -230,63 +523,518 @@ impl PreProcessor {
     }
 }
 
-/// SlideContentをPDFに変換（メイン機能）
-pub fn convert(slide: &SlideContent) -> Result<Document, PdfConversionError> {
-    let options = Options::default();
-
-    let documents = slide
-        .svgs
-        .par_iter()
-        .map(|svg_content| {
-            let conversion_options = ConversionOptions::default();
-            let page_options = PageOptions::default();
-            let tree = Tree::from_str(&svg_content.content, &options)
-                .map_err(|e| PdfConversionError::SvgParsing(e.to_string()))?;
-            let pdf = to_pdf(&tree, conversion_options, page_options)
-                .map_err(|e| PdfConversionError::PdfGeneration(e.to_string()))?;
-            Document::load_mem(&pdf).map_err(PdfConversionError::PdfLoading)
+/// 代替画像を参照する`image`要素
+///
+/// lol_htmlは追加した属性の名前を小文字にするが、XMLの属性名は大文字・小文字を区別し、
+/// usvgは`preserveaspectratio`を無視する。そのため要素ごと書き出して置き換える。
+fn placeholder_image(el: &Element<'_, '_>) -> String {
+    static PLACEHOLDER: LazyLock<String> = LazyLock::new(|| {
+        let base64 = general_purpose::STANDARD.encode(PLACEHOLDER_SVG);
+        format!("data:image/svg+xml;base64,{base64}")
+    });
+
+    let attributes = el
+        .attributes()
+        .iter()
+        .filter(|attr| {
+            !matches!(
+                attr.name().as_str(),
+                "href" | "xlink:href" | "preserveaspectratio"
+            )
+        })
+        .map(|attr| {
+            format!(
+                r#" {}="{}""#,
+                attr.name_preserve_case(),
+                attr.value().replace('"', "&quot;")
+            )
         })
-        .collect::<Result<Vec<_>, _>>()?;
+        .collect::<String>();
+    format!(
+        r#"<{}{attributes} xlink:href="{}" preserveAspectRatio="none"/>"#,
+        el.tag_name_preserve_case(),
+        *PLACEHOLDER
+    )
+}
+
+/// Base64のデータURIを復号（それ以外の参照はNone）
+fn decode_data_uri(href: &str) -> Option<Bytes> {
+    let (header, data) = href.strip_prefix("data:")?.split_once(',')?;
+    if !header
+        .split(';')
+        .any(|parameter| parameter.trim().eq_ignore_ascii_case("base64"))
+    {
+        return None;
+    }
+    general_purpose::STANDARD
+        .decode(data.trim())
+        .ok()
+        .map(Bytes::from)
+}
+
+/// PDF出力設定
+#[derive(Debug, Clone, Default)]
+pub struct ConvertConfig {
+    /// PDF/A-2bに準拠した出力にする
+    ///
+    /// システムフォントで描画したテキストはサブセット化して埋め込み、
+    /// 埋め込めないフォントはアウトラインに変換する。
+    pub pdfa: bool,
+    pub fonts: FontConfig,
+    pub page: PageConfig,
+    /// ヘッダー・フッター・透かし
+    pub stamp: StampConfig,
+    /// 印刷向けの配色（配布資料など）
+    pub colors: ColorMode,
+    /// タグ付きPDFにする（Noneならタグを付けない）
+    ///
+    /// 前処理で残る`aria-label`から見出し・段落・図の構造を作る。
+    pub tags: Option<TagConfig>,
+    /// 変換したSVGを各ページに添付する（`extract_sources`で読み出せる）
+    ///
+    /// 前処理したSVGを渡せば画像も埋め込まれているため、オフラインで再変換や再索引ができる。
+    /// PDF/Aでは添付ファイルが許されないため、`pdfa`を指定すると添付しない。
+    pub attach_sources: bool,
+}
+
+/// SVGからPDFへのコンバーター
+///
+/// SVGのサニタイズは行わないため、取得したSVGは`PreProcessor`を通してから渡す。
+pub struct Converter {
+    config: ConvertConfig,
+    fontdb: Arc<Database>,
+    /// スタンプのテンプレートに埋め込む値
+    context: StampContext,
+}
 
-    merge_documents(documents)
+impl Default for Converter {
+    fn default() -> Self {
+        let config = ConvertConfig::default();
+        // 既定の設定はフォントファイルを指定しないため読み込みに失敗しない
+        let fontdb = config.fonts.database().unwrap_or_default();
+        Self {
+            config,
+            fontdb: Arc::new(fontdb),
+            context: StampContext::default(),
+        }
+    }
 }
 
-/// 複数PDFドキュメントをマージ
-fn merge_documents(documents: Vec<Document>) -> Result<Document, PdfConversionError> {
-    let mut merged = Document::with_version("1.5");
-    let mut document_pages = vec![];
+impl Converter {
+    /// 新しいコンバーターを作成
+    pub fn new(config: ConvertConfig) -> Result<Self, PdfConversionError> {
+        let fontdb = Arc::new(config.fonts.database()?);
+        Ok(Self {
+            config,
+            fontdb,
+            context: StampContext::default(),
+        })
+    }
 
-    for mut document in documents {
-        document.renumber_objects_with(merged.max_id + 1);
-        merged.max_id = document.max_id;
+    /// 設定の一部を変えたコンバーターを作成
+    ///
+    /// 読み込み済みのフォントを共有するため、`fonts`の変更は反映されない。
+    pub fn with_config(&self, f: impl FnOnce(&mut ConvertConfig)) -> Self {
+        let mut config = self.config.clone();
+        f(&mut config);
+        Self {
+            config,
+            fontdb: self.fontdb.clone(),
+            context: self.context.clone(),
+        }
+    }
 
-        for (_, object_id) in document.get_pages() {
-            let reference = Object::Reference(object_id);
-            document_pages.push(reference);
+    /// スタンプに埋め込む値を設定したコンバーターを作成（フォントは共有する）
+    pub fn with_context(&self, context: StampContext) -> Self {
+        Self {
+            config: self.config.clone(),
+            fontdb: self.fontdb.clone(),
+            context,
         }
+    }
 
-        merged.objects.extend(document.objects);
+    /// SlideContentをPDFに変換
+    ///
+    /// スライド内のリンク（`<a>`要素）は、クリックできるリンク注釈として残す。
+    /// 日時を含めないため、同じ入力からは常に同じ内容のドキュメントが得られる。
+    pub fn convert(&self, slide: &SlideContent) -> Result<Document, PdfConversionError> {
+        self.convert_with_report(slide)
+            .map(|(document, _)| document)
     }
 
-    let count = document_pages.len() as u32;
-    let pages = dictionary! {
-        "Type" => "Pages",
-        "Kids" => document_pages,
-        "Count" => count,
-    };
-    let pages_id = merged.new_object_id();
-    merged.objects.insert(pages_id, Object::Dictionary(pages));
+    /// SlideContentをPDFに変換し、描画できなかった文字を報告
+    pub fn convert_with_report(
+        &self,
+        slide: &SlideContent,
+    ) -> Result<(Document, FontReport), PdfConversionError> {
+        let options = self.options();
+        // collectは入力の順序を保つため、並列に変換してもページ順とオブジェクト番号は変わらない
+        let results = slide
+            .svgs
+            .par_iter()
+            .map(|svg| self.convert_svg(svg, slide.svgs.len(), &options))
+            .collect::<Result<Vec<_>, PdfConversionError>>()?;
+
+        let mut report = FontReport::default();
+        let documents = results
+            .into_iter()
+            .map(|(document, (index, missing))| {
+                report.insert(index, missing);
+                MergeInput::from(document)
+            })
+            .collect::<Vec<_>>();
+
+        // 同一画像の統合は結合時に行われる
+        let mut document = merge::merge(documents)?;
+        if self.config.pdfa {
+            to_pdfa(&mut document, None)?;
+        }
+        Ok((document, report))
+    }
 
-    let catalog_id = merged.new_object_id();
-    let catalog = dictionary! {
-        "Type" => "Catalog",
-        "Pages" => pages_id,
-    };
+    /// SlideContentをPDFに変換しながら書き出し、描画できなかった文字を報告
+    ///
+    /// ページは`memory_limit`に収まる単位で並列に変換し、変換したものから書き出す。
+    /// PDF/Aはドキュメント全体の処理が必要なため、メモリ上で変換してから書き出す。
+    /// `config.timestamp`で日時を指定しなければ、同じ入力からは同じバイト列が出力される。
+    pub fn write_to<W: Write>(
+        &self,
+        slide: &SlideContent,
+        mut output: W,
+        config: &StreamConfig,
+    ) -> Result<FontReport, PdfConversionError> {
+        if self.config.pdfa {
+            let (mut document, report) = self.convert_with_report(slide)?;
+            document.save_to(&mut output)?;
+            return Ok(report);
+        }
+
+        let options = self.options();
+        let mut writer = PdfWriter::new(output, config)?;
+        let mut report = FontReport::default();
+        let sizes = slide
+            .svgs
+            .iter()
+            .map(|svg| svg.content.len() * SVG_EXPANSION);
+        for range in writer::batches(sizes, config.memory_limit) {
+            let results = slide.svgs[range]
+                .par_iter()
+                .map(|svg| self.convert_svg(svg, slide.svgs.len(), &options))
+                .collect::<Result<Vec<_>, PdfConversionError>>()?;
+            for (document, (index, missing)) in results {
+                report.insert(index, missing);
+                writer.add_document(document)?;
+            }
+        }
+        writer.finish()?;
+        Ok(report)
+    }
 
-    merged
-        .objects
-        .insert(catalog_id, Object::Dictionary(catalog));
-    merged.trailer.set("Root", catalog_id);
+    fn options(&self) -> Options<'static> {
+        Options {
+            fontdb: self.fontdb.clone(),
+            // サニタイズを通り抜けた参照があってもローカルのファイルは読み込まない
+            image_href_resolver: ImageHrefResolver {
+                resolve_data: ImageHrefResolver::default_data_resolver(),
+                resolve_string: Box::new(|_, _| None),
+            },
+            ..Options::default()
+        }
+    }
+
+    /// 1枚のSVGを1ページのPDFに変換
+    fn convert_svg(
+        &self,
+        svg: &ProcessedSvg,
+        total: usize,
+        options: &Options,
+    ) -> Result<(Document, (usize, BTreeSet<char>)), PdfConversionError> {
+        let conversion_options = ConversionOptions {
+            pdfa: self.config.pdfa,
+            embed_text: true,
+            ..ConversionOptions::default()
+        };
+        let page_options = PageOptions::default();
+        let content = color::apply(&svg.content, self.config.colors, options)?;
+        let (content, anchors) = link::mark_anchors(&content)?;
+        let (content, labels) = match &self.config.tags {
+            Some(_) => tag::mark_labels(&content)?,
+            None => (content, vec![]),
+        };
+        let tree = Tree::from_str(&content, options)
+            .map_err(|e| PdfConversionError::SvgParsing(e.to_string()))?;
+        let missing = font::missing_glyphs(&content, &tree);
+        let pdf = match to_pdf(&tree, conversion_options, page_options) {
+            // サブセット化できないフォントはアウトラインにして、フォントを使わずに描画する
+            Err(ConversionError::SubsetError(_) | ConversionError::InvalidFont(_))
+                if self.config.pdfa =>
+            {
+                let conversion_options = ConversionOptions {
+                    embed_text: false,
+                    ..conversion_options
+                };
+                to_pdf(&tree, conversion_options, page_options)
+            }
+            result => result,
+        }
+        .map_err(|e| PdfConversionError::PdfGeneration(e.to_string()))?;
+        let mut document = Document::load_mem(&pdf).map_err(PdfConversionError::PdfLoading)?;
+        link::annotate(&mut document, &tree, &anchors)?;
+        if let Some(tags) = &self.config.tags {
+            tag::collect(&mut document, &tree, &labels, tags)?;
+        }
+        let document = page::apply(document, &self.config.page)?;
+        let document = stamp::apply(
+            document,
+            &self.config.stamp,
+            &self.context,
+            &self.fontdb,
+            svg.index + 1,
+            total,
+        )?;
+        let mut document = tag::apply(document, &self.fontdb)?;
+        if self.config.attach_sources && !self.config.pdfa {
+            source::attach(&mut document, svg)?;
+        }
+        Ok((document, (svg.index, missing)))
+    }
+}
+
+/// システムフォントの読み込みは重いため使い回す
+static CONVERTER: LazyLock<Converter> = LazyLock::new(Converter::default);
+
+/// SlideContentをPDFに変換（メイン機能）
+pub fn convert(slide: &SlideContent) -> Result<Document, PdfConversionError> {
+    CONVERTER.convert(slide)
+}
+
+/// SlideContentをPDFに変換しながら書き出し
+pub fn write_to<W: Write>(
+    slide: &SlideContent,
+    output: W,
+    config: &StreamConfig,
+) -> Result<FontReport, PdfConversionError> {
+    CONVERTER.write_to(slide, output, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    const SVG: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"><image width="10" height="10" xlink:href="http://127.0.0.1:9/missing.png"/></svg>"#;
+
+    #[tokio::test]
+    async fn test_failed_image_is_replaced_with_placeholder() {
+        let preprocessor = PreProcessor::default();
+
+        let (content, report) = preprocessor
+            .embed_images_in_svg(&reqwest::Client::new(), 3, SVG.to_string())
+            .await
+            .unwrap();
 
-    Ok(merged)
+        assert!(content.contains("data:image/svg+xml;base64,"));
+        assert!(content.contains(r#"<image width="10" height="10" xlink:href="data:"#));
+        assert!(content.contains(r#"preserveAspectRatio="none"/>"#));
+        assert!(!content.contains("preserveaspectratio"));
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(report.warnings[0].index(), 3);
+        assert_eq!(
+            report.warnings[0].url(),
+            Some("http://127.0.0.1:9/missing.png")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_placeholder_replaces_only_failed_image() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"><image x='1' preserveAspectRatio="xMidYMid slice" xlink:href="http://127.0.0.1:9/missing.png"/><text> preserveaspectratio="x"</text></svg>"#;
+
+        let (content, _) = PreProcessor::default()
+            .embed_images_in_svg(&reqwest::Client::new(), 0, svg.to_string())
+            .await
+            .unwrap();
+
+        assert!(content.contains(r#"<image x="1" xlink:href="data:"#));
+        assert!(content.contains(r#"preserveAspectRatio="none"/><text>"#));
+        assert!(!content.contains("xMidYMid"));
+        // 画像以外の内容は書き換えない
+        assert!(content.contains(r#"<text> preserveaspectratio="x"</text>"#));
+    }
+
+    #[tokio::test]
+    async fn test_failed_image_is_kept() {
+        let preprocessor = PreProcessor::new(PreProcessConfig {
+            image_fallback: ImageFallback::Keep,
+            ..PreProcessConfig::default()
+        });
+
+        let (content, report) = preprocessor
+            .embed_images_in_svg(&reqwest::Client::new(), 0, SVG.to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(content, SVG);
+        assert_eq!(report.missing_images().len(), 1);
+    }
+
+    #[test]
+    fn test_with_config_shares_fonts() {
+        let converter = Converter::default();
+
+        let pdfa = converter.with_config(|config| config.pdfa = true);
+
+        assert!(pdfa.config.pdfa);
+        assert!(!converter.config.pdfa);
+        assert!(Arc::ptr_eq(&pdfa.fontdb, &converter.fontdb));
+    }
+
+    #[tokio::test]
+    async fn test_collect_images() {
+        let preprocessor = PreProcessor::default();
+        let png = general_purpose::STANDARD.encode(b"\x89PNG\r\n\x1a\n");
+        let embedded = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"><image width="1" height="1" href="data:image/png;base64,{png}"/><image width="1" height="1" href="data:image/png;base64,{png}"/></svg>"#
+        );
+
+        let (images, report) = preprocessor
+            .collect_images(
+                &reqwest::Client::new(),
+                &slide(vec![
+                    ProcessedSvg::new(SVG, 0),
+                    ProcessedSvg::new(embedded, 1),
+                ]),
+            )
+            .await
+            .unwrap();
+
+        // 同じSVG内の同じ参照は1回だけ
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].index, 1);
+        assert_eq!(images[0].url, None);
+        assert_eq!(images[0].extension(), Some("png"));
+        assert_eq!(
+            report.missing_images(),
+            BTreeSet::from(["http://127.0.0.1:9/missing.png"])
+        );
+    }
+
+    fn slide(svgs: Vec<ProcessedSvg>) -> SlideContent {
+        SlideContent::new(testing::page_key(), svgs)
+    }
+
+    #[tokio::test]
+    async fn test_unsafe_content_is_removed_and_reported() {
+        let preprocessor = PreProcessor::new(PreProcessConfig {
+            embed_images: false,
+            embed_text: false,
+            ..PreProcessConfig::default()
+        });
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" onload="alert(1)"><script>alert(2)</script><rect width="1" height="1"/></svg>"#;
+
+        let (content, report) = preprocessor
+            .preprocess_with_report(
+                &reqwest::Client::new(),
+                &slide(vec![ProcessedSvg::new(svg.to_string(), 5)]),
+            )
+            .await
+            .unwrap();
+
+        assert!(!content.svgs[0].content.contains("alert"));
+        assert_eq!(
+            report.warnings,
+            vec![PreprocessWarning::UnsafeContent {
+                index: 5,
+                removed: vec!["<script>".to_string(), "onload".to_string()],
+            }]
+        );
+        assert!(report.missing_images().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unsafe_svg_is_rejected() {
+        let svg = r#"<!DOCTYPE svg [<!ENTITY a "a">]><svg xmlns="http://www.w3.org/2000/svg"><text>&a;</text></svg>"#;
+
+        let result = PreProcessor::default()
+            .preprocess(
+                &reqwest::Client::new(),
+                &slide(vec![ProcessedSvg::new(svg.to_string(), 0)]),
+            )
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(PdfConversionError::Sanitize(
+                SanitizeError::EntityDeclaration
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_sources_round_trip() {
+        let svgs = (0..2)
+            .map(|index| {
+                ProcessedSvg::new(
+                    format!(r#"<svg xmlns="http://www.w3.org/2000/svg" width="160" height="90"><text y="40">スライド{index}</text></svg>"#),
+                    index,
+                )
+            })
+            .collect::<Vec<_>>();
+        let slide = slide(svgs.clone());
+        let converter = Converter::new(ConvertConfig {
+            attach_sources: true,
+            ..ConvertConfig::default()
+        })
+        .unwrap();
+
+        let document = converter.convert(&slide).unwrap();
+        let sources = extract_sources(&document, slide.page_key.clone()).unwrap();
+
+        assert_eq!(sources.page_key, slide.page_key);
+        let contents = sources
+            .svgs
+            .iter()
+            .map(|svg| (svg.index, svg.content.as_str()))
+            .collect::<Vec<_>>();
+        let expected = svgs
+            .iter()
+            .map(|svg| (svg.index, svg.content.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(contents, expected);
+    }
+
+    #[test]
+    fn test_conversion_is_deterministic() {
+        let svgs = ["#ff0000", "#00ff00", "#0000ff"]
+            .iter()
+            .enumerate()
+            .map(|(index, color)| {
+                ProcessedSvg::new(
+                    format!(r#"<svg xmlns="http://www.w3.org/2000/svg" width="160" height="90"><rect width="80" height="45" fill="{color}"/></svg>"#),
+                    index,
+                )
+            })
+            .collect();
+        let slide = slide(svgs);
+        let converter = Converter::default();
+
+        let write = || {
+            let mut output = vec![];
+            converter
+                .write_to(&slide, &mut output, &StreamConfig::default())
+                .unwrap();
+            output
+        };
+        assert_eq!(write(), write());
+
+        let save = || {
+            let mut output = vec![];
+            converter
+                .convert(&slide)
+                .unwrap()
+                .save_to(&mut output)
+                .unwrap();
+            output
+        };
+        assert_eq!(save(), save());
+    }
 }
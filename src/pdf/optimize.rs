@@ -0,0 +1,285 @@
+use image::{
+    codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, GenericImageView, ImageFormat,
+};
+use std::{borrow::Cow, collections::HashSet, io::Cursor};
+
+/// 写真と判定する色数のしきい値（サンプリングした画素のうち）
+const PHOTO_COLOR_THRESHOLD: usize = 2048;
+/// 色数を数えるときのサンプル数
+const COLOR_SAMPLES: u32 = 16384;
+
+/// 画像最適化の設定
+#[derive(Debug, Clone)]
+pub struct ImageOptimizeConfig {
+    /// 描画サイズに対する最大倍率（2.0なら描画サイズの2倍の画素数まで縮小）
+    pub max_scale: f32,
+    /// JPEG出力の品質（1〜100）
+    pub jpeg_quality: u8,
+    /// 不透明な写真のPNGをJPEGに変換する
+    pub convert_opaque_png: bool,
+    /// EXIFなどのメタデータを削除する
+    pub strip_metadata: bool,
+}
+
+impl Default for ImageOptimizeConfig {
+    fn default() -> Self {
+        Self {
+            max_scale: 2.0,
+            jpeg_quality: 85,
+            convert_opaque_png: true,
+            strip_metadata: true,
+        }
+    }
+}
+
+/// 埋め込み画像のサイズの集計
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImageSizeReport {
+    pub images: usize,
+    pub original_bytes: usize,
+    pub output_bytes: usize,
+}
+
+impl ImageSizeReport {
+    pub(crate) fn add(&mut self, original_bytes: usize, output_bytes: usize) {
+        self.images += 1;
+        self.original_bytes += original_bytes;
+        self.output_bytes += output_bytes;
+    }
+
+    pub(crate) fn merge(&mut self, other: &ImageSizeReport) {
+        self.images += other.images;
+        self.original_bytes += other.original_bytes;
+        self.output_bytes += other.output_bytes;
+    }
+
+    /// 削減されたバイト数
+    pub fn saved_bytes(&self) -> usize {
+        self.original_bytes.saturating_sub(self.output_bytes)
+    }
+}
+
+/// 描画サイズ（SVGのユーザー単位）に合わせて画像を最適化
+///
+/// 最適化できない形式や失敗した場合は元のデータを返す。
+pub(crate) fn optimize<'a>(
+    bytes: &'a [u8],
+    rendered: Option<(f64, f64)>,
+    config: &ImageOptimizeConfig,
+) -> Cow<'a, [u8]> {
+    let format = match image::guess_format(bytes) {
+        Ok(format @ (ImageFormat::Png | ImageFormat::Jpeg)) => format,
+        _ => return Cow::Borrowed(bytes),
+    };
+
+    let stripped = if config.strip_metadata {
+        match format {
+            ImageFormat::Png => strip_png_metadata(bytes),
+            _ => strip_jpeg_metadata(bytes),
+        }
+    } else {
+        None
+    };
+    let original = stripped.map_or(Cow::Borrowed(bytes), Cow::Owned);
+
+    match reencode(&original, format, rendered, config) {
+        Some(reencoded) if reencoded.len() < original.len() => Cow::Owned(reencoded),
+        _ => original,
+    }
+}
+
+/// 縮小・形式変換が必要な場合に再エンコード
+fn reencode(
+    bytes: &[u8],
+    format: ImageFormat,
+    rendered: Option<(f64, f64)>,
+    config: &ImageOptimizeConfig,
+) -> Option<Vec<u8>> {
+    let image = image::load_from_memory_with_format(bytes, format).ok()?;
+    let (width, height) = image.dimensions();
+
+    let max_size = rendered.map(|(w, h)| {
+        let scale = f64::from(config.max_scale);
+        (
+            (w * scale).ceil().max(1.0) as u32,
+            (h * scale).ceil().max(1.0) as u32,
+        )
+    });
+    let needs_resize = max_size.is_some_and(|(w, h)| width > w || height > h);
+    let to_jpeg = format == ImageFormat::Png
+        && config.convert_opaque_png
+        && is_opaque(&image)
+        && is_photo(&image);
+
+    if !needs_resize && !to_jpeg {
+        return None;
+    }
+
+    let image = match max_size {
+        Some((w, h)) if needs_resize => image.resize(w, h, FilterType::Lanczos3),
+        _ => image,
+    };
+
+    let mut output = vec![];
+    if format == ImageFormat::Jpeg || to_jpeg {
+        let rgb = DynamicImage::ImageRgb8(image.to_rgb8());
+        JpegEncoder::new_with_quality(&mut output, config.jpeg_quality.clamp(1, 100))
+            .encode_image(&rgb)
+            .ok()?;
+    } else {
+        image
+            .write_to(&mut Cursor::new(&mut output), ImageFormat::Png)
+            .ok()?;
+    }
+    Some(output)
+}
+
+pub(super) fn is_opaque(image: &DynamicImage) -> bool {
+    !image.color().has_alpha() || image.to_rgba8().pixels().all(|pixel| pixel[3] == u8::MAX)
+}
+
+/// 色数の多い画像を写真とみなす
+pub(super) fn is_photo(image: &DynamicImage) -> bool {
+    let rgb = image.to_rgb8();
+    let pixels = rgb.as_raw().chunks_exact(3).collect::<Vec<_>>();
+    let step = (pixels.len() / COLOR_SAMPLES as usize).max(1);
+    let colors = pixels.iter().step_by(step).collect::<HashSet<_>>().len();
+    colors > PHOTO_COLOR_THRESHOLD
+}
+
+/// PNGから描画に影響しない補助チャンクを削除
+fn strip_png_metadata(bytes: &[u8]) -> Option<Vec<u8>> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    const METADATA_CHUNKS: [&[u8; 4]; 5] = [b"tEXt", b"zTXt", b"iTXt", b"eXIf", b"tIME"];
+
+    let mut output = SIGNATURE.to_vec();
+    let mut rest = bytes.strip_prefix(SIGNATURE)?;
+    while !rest.is_empty() {
+        let length = u32::from_be_bytes(rest.get(0..4)?.try_into().ok()?) as usize;
+        // 長さ・種類・データ・CRC
+        let chunk = rest.get(..12 + length)?;
+        if !METADATA_CHUNKS.iter().any(|kind| chunk[4..8] == kind[..]) {
+            output.extend_from_slice(chunk);
+        }
+        rest = &rest[chunk.len()..];
+    }
+    Some(output)
+}
+
+/// JPEGからEXIF・XMP・コメントを削除
+///
+/// JFIF（APP0）と色変換に関わるAdobe（APP14）、ICCプロファイル（APP2）は残す。
+fn strip_jpeg_metadata(bytes: &[u8]) -> Option<Vec<u8>> {
+    const SOI: [u8; 2] = [0xFF, 0xD8];
+    const SOS: u8 = 0xDA;
+    const COM: u8 = 0xFE;
+    const KEEP: [u8; 3] = [0xE0, 0xE2, 0xEE];
+
+    let mut output = SOI.to_vec();
+    let mut rest = bytes.strip_prefix(&SOI)?;
+    loop {
+        if rest.first() != Some(&0xFF) {
+            return None;
+        }
+        let marker = *rest.get(1)?;
+        if marker == SOS {
+            // スキャン以降は画像データ
+            output.extend_from_slice(rest);
+            return Some(output);
+        }
+        let length = u16::from_be_bytes([*rest.get(2)?, *rest.get(3)?]) as usize;
+        let segment = rest.get(..2 + length)?;
+        let is_metadata = (0xE1..=0xEF).contains(&marker) && !KEEP.contains(&marker);
+        if !is_metadata && marker != COM {
+            output.extend_from_slice(segment);
+        }
+        rest = &rest[segment.len()..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage, Rgba, RgbaImage};
+
+    fn encode(image: DynamicImage, format: ImageFormat) -> Vec<u8> {
+        let mut output = vec![];
+        image
+            .write_to(&mut Cursor::new(&mut output), format)
+            .unwrap();
+        output
+    }
+
+    /// 色数の多いグラデーション画像
+    fn photo(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            Rgb([(x % 256) as u8, (y % 256) as u8, ((x * y) % 256) as u8])
+        }))
+    }
+
+    #[test]
+    fn test_downscale_to_rendered_size() {
+        let png = encode(photo(400, 200), ImageFormat::Png);
+        let config = ImageOptimizeConfig {
+            convert_opaque_png: false,
+            ..ImageOptimizeConfig::default()
+        };
+
+        let output = optimize(&png, Some((50.0, 25.0)), &config);
+        let image = image::load_from_memory(&output).unwrap();
+
+        assert_eq!(image.dimensions(), (100, 50));
+        assert_eq!(image::guess_format(&output).unwrap(), ImageFormat::Png);
+    }
+
+    #[test]
+    fn test_opaque_photo_png_to_jpeg() {
+        let png = encode(photo(256, 256), ImageFormat::Png);
+
+        let output = optimize(&png, None, &ImageOptimizeConfig::default());
+
+        assert_eq!(image::guess_format(&output).unwrap(), ImageFormat::Jpeg);
+        assert!(output.len() < png.len());
+    }
+
+    #[test]
+    fn test_transparent_png_is_kept() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(64, 64, |x, y| {
+            Rgba([
+                (x * 4) as u8,
+                (y * 4) as u8,
+                0,
+                if x < 32 { 0 } else { 255 },
+            ])
+        }));
+        let png = encode(image, ImageFormat::Png);
+
+        let output = optimize(&png, None, &ImageOptimizeConfig::default());
+
+        assert_eq!(image::guess_format(&output).unwrap(), ImageFormat::Png);
+    }
+
+    #[test]
+    fn test_strip_png_metadata() {
+        let png = encode(photo(4, 4), ImageFormat::Png);
+        // IHDRの直後にtEXtチャンクを挿入（CRCは検証されない）
+        let text = [&12u32.to_be_bytes()[..], b"tEXt", b"Comment\0test", &[0; 4]].concat();
+        let ihdr_end = 8 + 12 + 13;
+        let with_text = [&png[..ihdr_end], &text, &png[ihdr_end..]].concat();
+
+        let stripped = strip_png_metadata(&with_text).unwrap();
+
+        assert_eq!(stripped, png);
+    }
+
+    #[test]
+    fn test_strip_jpeg_metadata() {
+        let jpeg = encode(photo(8, 8), ImageFormat::Jpeg);
+        let exif = [&[0xFF, 0xE1][..], &8u16.to_be_bytes(), b"Exif\0\0"].concat();
+        let with_exif = [&jpeg[..2], &exif, &jpeg[2..]].concat();
+
+        let stripped = strip_jpeg_metadata(&with_exif).unwrap();
+
+        assert_eq!(stripped, jpeg);
+    }
+}
@@ -0,0 +1,471 @@
+use super::{form::page_to_form, layout::PaperSize, tag::TAGS_KEY, PdfConversionError};
+use lopdf::{
+    content::{Content, Operation},
+    dictionary, Document, Object, ObjectId, Stream,
+};
+
+/// 出力するページのサイズ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PageSize {
+    /// SVGのサイズのまま
+    #[default]
+    Native,
+    /// 横向きの用紙
+    Landscape(PaperSize),
+    /// 16:9（960×540pt）
+    Widescreen,
+}
+
+impl PageSize {
+    /// (幅, 高さ)（pt）。`Native`はSVGのサイズに依存するためNone
+    pub fn dimensions(&self) -> Option<(f32, f32)> {
+        match self {
+            Self::Native => None,
+            Self::Landscape(paper) => {
+                let (width, height) = paper.dimensions();
+                Some((height, width))
+            }
+            Self::Widescreen => Some((960.0, 540.0)),
+        }
+    }
+}
+
+/// ページへのスライドの収め方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FitMode {
+    /// 全体が収まるように拡大・縮小（余白ができる）
+    #[default]
+    Fit,
+    /// 余白ができないように拡大・縮小（はみ出した部分は切り取る）
+    Fill,
+}
+
+/// ページの余白（pt）
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Margins {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+impl Margins {
+    pub fn uniform(value: f32) -> Self {
+        Self {
+            top: value,
+            right: value,
+            bottom: value,
+            left: value,
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// 変換後のページの設定
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PageConfig {
+    pub size: PageSize,
+    pub fit: FitMode,
+    pub margins: Margins,
+    /// 背景色（RGB）。Noneなら透明
+    pub background: Option<[u8; 3]>,
+}
+
+impl PageConfig {
+    /// SVGのページをそのまま使う設定かどうか
+    fn is_native(&self) -> bool {
+        self.size == PageSize::Native && self.margins.is_zero() && self.background.is_none()
+    }
+}
+
+/// `#rrggbb`形式の色を解析
+pub fn parse_color(hex: &str) -> Option<[u8; 3]> {
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok();
+    Some([channel(0)?, channel(1)?, channel(2)?])
+}
+
+/// 各ページを設定したサイズのページに配置し直す
+///
+/// 元のページはForm XObjectとして配置されるため、ベクターのまま拡大・縮小される。
+pub(crate) fn apply(
+    mut document: Document,
+    config: &PageConfig,
+) -> Result<Document, PdfConversionError> {
+    if config.is_native() {
+        return Ok(document);
+    }
+
+    for page_id in document.get_pages().into_values() {
+        let (form_id, bbox) = page_to_form(&mut document, page_id)?;
+        let (form_width, form_height) = (bbox[2] - bbox[0], bbox[3] - bbox[1]);
+        let margins = &config.margins;
+        let (page_width, page_height) = config.size.dimensions().unwrap_or((
+            form_width + margins.left + margins.right,
+            form_height + margins.top + margins.bottom,
+        ));
+        let area_width = page_width - margins.left - margins.right;
+        let area_height = page_height - margins.top - margins.bottom;
+        if area_width <= 0.0 || area_height <= 0.0 || form_width <= 0.0 || form_height <= 0.0 {
+            return Err(PdfConversionError::Layout(
+                "margins leave no space for the slide".to_string(),
+            ));
+        }
+
+        let scale = match config.fit {
+            FitMode::Fit => (area_width / form_width).min(area_height / form_height),
+            FitMode::Fill => (area_width / form_width).max(area_height / form_height),
+        };
+        let x = margins.left + (area_width - form_width * scale) / 2.0 - bbox[0] * scale;
+        let y = margins.bottom + (area_height - form_height * scale) / 2.0 - bbox[1] * scale;
+
+        let mut operations = vec![Operation::new("q", vec![])];
+        if let Some([r, g, b]) = config.background {
+            operations.extend([
+                Operation::new(
+                    "rg",
+                    [r, g, b]
+                        .map(|channel| Object::from(f32::from(channel) / 255.0))
+                        .to_vec(),
+                ),
+                Operation::new(
+                    "re",
+                    vec![0.into(), 0.into(), page_width.into(), page_height.into()],
+                ),
+                Operation::new("f", vec![]),
+            ]);
+        }
+        // はみ出した部分を余白に描画しない
+        operations.extend([
+            Operation::new(
+                "re",
+                vec![
+                    margins.left.into(),
+                    margins.bottom.into(),
+                    area_width.into(),
+                    area_height.into(),
+                ],
+            ),
+            Operation::new("W", vec![]),
+            Operation::new("n", vec![]),
+            Operation::new(
+                "cm",
+                vec![
+                    scale.into(),
+                    0.into(),
+                    0.into(),
+                    scale.into(),
+                    x.into(),
+                    y.into(),
+                ],
+            ),
+            Operation::new("Do", vec![Object::Name(b"Slide".to_vec())]),
+            Operation::new("Q", vec![]),
+        ]);
+
+        // 余白とスライドの外側には描画されないため、リンクもその内側に収める
+        let visible = [
+            margins.left.max(bbox[0] * scale + x),
+            margins.bottom.max(bbox[1] * scale + y),
+            (margins.left + area_width).min(bbox[2] * scale + x),
+            (margins.bottom + area_height).min(bbox[3] * scale + y),
+        ];
+        let links = move_links(&mut document, page_id, scale, (x, y), visible)?;
+        let tags = move_tags(&document, page_id, scale, (x, y), visible)?;
+
+        let content = Content { operations }.encode()?;
+        let mut stream = Stream::new(dictionary! {}, content);
+        let _ = stream.compress();
+        let content_id = document.add_object(stream);
+
+        let page = document.get_dictionary_mut(page_id)?;
+        let parent = page.get(b"Parent")?.clone();
+        // リンク以外の注釈は元の座標のままでは位置がずれるため、新しいページには引き継がない
+        *page = dictionary! {
+            "Type" => "Page",
+            "Parent" => parent,
+            "MediaBox" => vec![0.into(), 0.into(), page_width.into(), page_height.into()],
+            "Resources" => dictionary! {
+                "XObject" => dictionary! { "Slide" => form_id },
+            },
+            "Contents" => content_id,
+        };
+        if !links.is_empty() {
+            page.set("Annots", links);
+        }
+        if let Some(tags) = tags {
+            page.set(TAGS_KEY, tags);
+        }
+    }
+
+    Ok(document)
+}
+
+/// リンク注釈の範囲をスライドと同じように移動し、`visible`（`[x0, y0, x1, y1]`）に切り詰める
+///
+/// 範囲が`visible`の外に出たリンクは取り除く。
+fn move_links(
+    document: &mut Document,
+    page_id: ObjectId,
+    scale: f32,
+    offset: (f32, f32),
+    visible: [f32; 4],
+) -> Result<Vec<Object>, PdfConversionError> {
+    let annots = match document.get_dictionary(page_id)?.get(b"Annots") {
+        Ok(annots) => document.dereference(annots)?.1.as_array()?.clone(),
+        Err(_) => return Ok(vec![]),
+    };
+
+    let mut links = vec![];
+    for annot in annots {
+        let mut dict = document.dereference(&annot)?.1.as_dict()?.clone();
+        if !dict
+            .get(b"Subtype")
+            .and_then(Object::as_name)
+            .is_ok_and(|subtype| subtype == b"Link")
+        {
+            continue;
+        }
+        let Some(rect) = move_rect(dict.get(b"Rect"), scale, offset, visible) else {
+            continue;
+        };
+        dict.set("Rect", rect);
+        // 文字単位の範囲は移動後の座標と合わないため、Rectだけを使わせる
+        dict.remove(b"QuadPoints");
+
+        links.push(match annot {
+            Object::Reference(id) => {
+                document.objects.insert(id, Object::Dictionary(dict));
+                Object::Reference(id)
+            }
+            _ => Object::Dictionary(dict),
+        });
+    }
+    Ok(links)
+}
+
+/// タグ付けする要素の範囲をスライドと同じように移動する（`visible`の外に出た要素は取り除く）
+fn move_tags(
+    document: &Document,
+    page_id: ObjectId,
+    scale: f32,
+    offset: (f32, f32),
+    visible: [f32; 4],
+) -> Result<Option<Object>, PdfConversionError> {
+    let Ok(tags) = document.get_dictionary(page_id)?.get(TAGS_KEY.as_bytes()) else {
+        return Ok(None);
+    };
+    let mut tags = tags.as_dict()?.clone();
+    let items = tags
+        .get(b"K")?
+        .as_array()?
+        .iter()
+        .filter_map(|item| {
+            let mut item = item.as_dict().ok()?.clone();
+            let bbox = move_rect(item.get(b"BBox"), scale, offset, visible)?;
+            item.set("BBox", bbox);
+            Some(Object::Dictionary(item))
+        })
+        .collect::<Vec<_>>();
+    tags.set("K", items);
+    Ok(Some(Object::Dictionary(tags)))
+}
+
+/// `[x0, y0, x1, y1]`の範囲を拡大・移動して`visible`に切り詰める（空になればNone）
+fn move_rect(
+    rect: lopdf::Result<&Object>,
+    scale: f32,
+    (x, y): (f32, f32),
+    visible: [f32; 4],
+) -> Option<Object> {
+    let rect = rect
+        .and_then(Object::as_array)
+        .and_then(|rect| {
+            rect.iter()
+                .map(Object::as_float)
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .ok()?;
+    let [x0, y0, x1, y1] = rect[..] else {
+        return None;
+    };
+
+    let rect = [
+        (x0.min(x1) * scale + x).max(visible[0]),
+        (y0.min(y1) * scale + y).max(visible[1]),
+        (x0.max(x1) * scale + x).min(visible[2]),
+        (y0.max(y1) * scale + y).min(visible[3]),
+    ];
+    if rect[0] >= rect[2] || rect[1] >= rect[3] {
+        return None;
+    }
+    Some(
+        rect.iter()
+            .map(|&v| v.into())
+            .collect::<Vec<Object>>()
+            .into(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn transform(document: &Document) -> Vec<f32> {
+        let page_id = *document.get_pages().values().next().unwrap();
+        let content = Content::decode(&document.get_page_content(page_id).unwrap()).unwrap();
+        content
+            .operations
+            .iter()
+            .find(|operation| operation.operator == "cm")
+            .unwrap()
+            .operands
+            .iter()
+            .map(|operand| operand.as_float().unwrap())
+            .collect()
+    }
+
+    fn media_box(document: &Document) -> Vec<f32> {
+        let page_id = *document.get_pages().values().next().unwrap();
+        document
+            .get_dictionary(page_id)
+            .unwrap()
+            .get(b"MediaBox")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value.as_float().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_fit_to_widescreen() {
+        let config = PageConfig {
+            size: PageSize::Widescreen,
+            ..PageConfig::default()
+        };
+
+        let document = apply(testing::document(1, 400, 300, b"0 0 m"), &config).unwrap();
+
+        assert_eq!(media_box(&document), [0.0, 0.0, 960.0, 540.0]);
+        // 高さに合わせて1.8倍し、左右中央に配置
+        let transform = transform(&document);
+        assert!((transform[0] - 1.8).abs() < 1e-4);
+        assert!((transform[4] - 120.0).abs() < 1e-2);
+        assert!(transform[5].abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_fill_with_margins() {
+        let config = PageConfig {
+            size: PageSize::Widescreen,
+            fit: FitMode::Fill,
+            margins: Margins::uniform(20.0),
+            background: Some([255, 255, 255]),
+        };
+
+        let document = apply(testing::document(1, 400, 300, b"0 0 m"), &config).unwrap();
+
+        // 幅に合わせて2.3倍し、上下にはみ出す
+        let transform = transform(&document);
+        assert!((transform[0] - 2.3).abs() < 1e-4);
+        assert!((transform[4] - 20.0).abs() < 1e-2);
+        assert!((transform[5] + 75.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_native_with_margins() {
+        let config = PageConfig {
+            margins: Margins::uniform(10.0),
+            ..PageConfig::default()
+        };
+
+        let document = apply(testing::document(1, 400, 300, b"0 0 m"), &config).unwrap();
+
+        assert_eq!(media_box(&document), [0.0, 0.0, 420.0, 320.0]);
+    }
+
+    #[test]
+    fn test_links_follow_the_slide() {
+        let mut document = testing::document(1, 400, 300, b"0 0 m");
+        let page_id = *document.get_pages().values().next().unwrap();
+        let link = |rect: [i64; 4]| {
+            dictionary! {
+                "Type" => "Annot",
+                "Subtype" => "Link",
+                "Rect" => rect.map(Object::from).to_vec(),
+            }
+        };
+        let inside = document.add_object(link([100, 100, 200, 150]));
+        let outside = document.add_object(link([-50, 0, -10, 10]));
+        let note = document.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Text",
+            "Rect" => vec![0.into(), 0.into(), 10.into(), 10.into()],
+        });
+        document.get_dictionary_mut(page_id).unwrap().set(
+            "Annots",
+            vec![
+                inside.into(),
+                Object::Dictionary(link([350, 0, 450, 20])),
+                outside.into(),
+                note.into(),
+            ],
+        );
+        let config = PageConfig {
+            size: PageSize::Widescreen,
+            ..PageConfig::default()
+        };
+
+        let document = apply(document, &config).unwrap();
+
+        let annots = document
+            .get_dictionary(page_id)
+            .unwrap()
+            .get(b"Annots")
+            .unwrap()
+            .as_array()
+            .unwrap();
+        let rects = annots
+            .iter()
+            .map(|annot| {
+                document
+                    .dereference(annot)
+                    .unwrap()
+                    .1
+                    .as_dict()
+                    .unwrap()
+                    .get(b"Rect")
+                    .unwrap()
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|value| value.as_float().unwrap())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        // 1.8倍して右に120pt移動し、スライドの外に出た部分は切り詰める
+        assert_eq!(rects.len(), 2);
+        let expected = [[300.0, 180.0, 480.0, 270.0], [750.0, 0.0, 840.0, 36.0]];
+        for (rect, expected) in rects.iter().zip(expected) {
+            for (value, expected) in rect.iter().zip(expected) {
+                assert!((value - expected).abs() < 1e-2, "{rect:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#ff8000"), Some([255, 128, 0]));
+        assert_eq!(parse_color("000000"), Some([0, 0, 0]));
+        assert_eq!(parse_color("#fff"), None);
+    }
+}
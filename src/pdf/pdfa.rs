@@ -0,0 +1,784 @@
+use super::{form::inherited, text::text_string, PdfConversionError};
+use lopdf::{
+    content::Content, dictionary, Dictionary, Document, Object, ObjectId, Stream, StringFormat,
+};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+
+pub(super) const PRODUCER: &str = concat!("moocs-collect ", env!("CARGO_PKG_VERSION"));
+const SRGB_IDENTIFIER: &str = "sRGB IEC61966-2.1";
+
+/// PDF/Aで禁止されているアノテーション
+const FORBIDDEN_ANNOTATIONS: [&[u8]; 6] = [
+    b"Sound",
+    b"Movie",
+    b"FileAttachment",
+    b"3D",
+    b"RichMedia",
+    b"Screen",
+];
+
+/// PDF/Aで禁止されているアクション
+const FORBIDDEN_ACTIONS: [&[u8]; 11] = [
+    b"Launch",
+    b"Sound",
+    b"Movie",
+    b"ResetForm",
+    b"ImportData",
+    b"JavaScript",
+    b"Hide",
+    b"SetOCGState",
+    b"Rendition",
+    b"Trans",
+    b"GoTo3DView",
+];
+
+/// ドキュメントをPDF/A-2bに準拠させる
+///
+/// sRGBのOutputIntentとXMPメタデータを追加し、準拠しない構造を取り除く。
+/// フォントはここでは埋め込まないため、埋め込まれていないフォントがあればエラーを返す。
+/// `Converter`でPDF/Aを指定した場合は、変換時にフォントが埋め込まれる。
+pub fn to_pdfa(document: &mut Document, title: Option<&str>) -> Result<(), PdfConversionError> {
+    document.version = "1.7".to_string();
+    document.trailer.remove(b"Encrypt");
+
+    remove_forbidden_objects(document)?;
+    document.prune_objects();
+    check_fonts(document)?;
+
+    let metadata_id = document.add_object(
+        Stream::new(
+            dictionary! {
+                "Type" => "Metadata",
+                "Subtype" => "XML",
+            },
+            xmp(title).into_bytes(),
+        )
+        // PDF/AではメタデータストリームにFilterを付けられない
+        .with_compression(false),
+    );
+
+    let mut profile = Stream::new(dictionary! { "N" => 3 }, srgb_profile());
+    let _ = profile.compress();
+    let profile_id = document.add_object(profile);
+
+    let catalog = document.catalog_mut()?;
+    catalog.set("Metadata", metadata_id);
+    catalog.set(
+        "OutputIntents",
+        vec![Object::Dictionary(dictionary! {
+            "Type" => "OutputIntent",
+            "S" => "GTS_PDFA1",
+            "OutputConditionIdentifier" => Object::string_literal(SRGB_IDENTIFIER),
+            "Info" => Object::string_literal(SRGB_IDENTIFIER),
+            "DestOutputProfile" => profile_id,
+        })],
+    );
+
+    // InfoはXMPと一致している必要がある
+    let mut info = dictionary! {
+        "Producer" => Object::string_literal(PRODUCER),
+    };
+    if let Some(title) = title {
+        info.set("Title", text_string(title));
+    }
+    let info_id = document.add_object(info);
+    document.trailer.set("Info", info_id);
+
+    let id = document_id(document, title);
+    document.trailer.set(
+        "ID",
+        vec![
+            Object::String(id.clone(), StringFormat::Hexadecimal),
+            Object::String(id, StringFormat::Hexadecimal),
+        ],
+    );
+
+    document.prune_objects();
+    Ok(())
+}
+
+/// 禁止されているアクション・アノテーション・外部参照などを削除
+fn remove_forbidden_objects(document: &mut Document) -> Result<(), PdfConversionError> {
+    let removed = document
+        .objects
+        .iter()
+        .filter(|(_, object)| object.as_dict().is_ok_and(is_forbidden_annotation))
+        .map(|(&id, _)| id)
+        .collect::<BTreeSet<_>>();
+    for id in &removed {
+        document.objects.remove(id);
+    }
+
+    for object in document.objects.values_mut() {
+        sanitize(object, &removed);
+    }
+
+    // 名前ツリーのJavaScriptと埋め込みファイル
+    let names = match document.catalog()?.get(b"Names") {
+        Ok(Object::Reference(id)) => Some(*id),
+        _ => None,
+    };
+    let names = match names {
+        Some(id) => document.get_dictionary_mut(id).ok(),
+        None => document
+            .catalog_mut()?
+            .get_mut(b"Names")
+            .and_then(Object::as_dict_mut)
+            .ok(),
+    };
+    if let Some(names) = names {
+        names.remove(b"JavaScript");
+        names.remove(b"EmbeddedFiles");
+    }
+
+    Ok(())
+}
+
+fn sanitize(object: &mut Object, removed: &BTreeSet<ObjectId>) {
+    match object {
+        Object::Array(items) => items.iter_mut().for_each(|item| sanitize(item, removed)),
+        Object::Dictionary(dict) => sanitize_dictionary(dict, removed),
+        Object::Stream(stream) => {
+            // 外部ファイルのストリーム
+            stream.dict.remove(b"F");
+            stream.dict.remove(b"FFilter");
+            stream.dict.remove(b"FDecodeParms");
+            sanitize_dictionary(&mut stream.dict, removed);
+        }
+        _ => {}
+    }
+}
+
+fn sanitize_dictionary(dict: &mut Dictionary, removed: &BTreeSet<ObjectId>) {
+    for key in [
+        &b"AA"[..],
+        b"Alternates",
+        b"OPI",
+        b"TR",
+        b"HTP",
+        b"Interpolate",
+        b"PresSteps",
+        b"NeedsRendering",
+    ] {
+        dict.remove(key);
+    }
+    if dict.has(b"TR2") {
+        dict.set("TR2", "Default");
+    }
+
+    for key in [&b"A"[..], b"OpenAction"] {
+        if dict
+            .get(key)
+            .and_then(Object::as_dict)
+            .is_ok_and(is_forbidden_action)
+        {
+            dict.remove(key);
+        }
+    }
+
+    if let Ok(Object::Array(annotations)) = dict.get_mut(b"Annots") {
+        annotations.retain(|annotation| match annotation {
+            Object::Reference(id) => !removed.contains(id),
+            Object::Dictionary(annotation) => !is_forbidden_annotation(annotation),
+            _ => true,
+        });
+    }
+
+    if dict.has_type(b"Annot") {
+        // 印刷可能かつ非表示でないこと
+        const INVISIBLE: i64 = 1;
+        const HIDDEN: i64 = 2;
+        const PRINT: i64 = 4;
+        const NO_VIEW: i64 = 32;
+        let flags = dict.get(b"F").and_then(Object::as_i64).unwrap_or(0);
+        dict.set("F", (flags | PRINT) & !(INVISIBLE | HIDDEN | NO_VIEW));
+    }
+
+    for (_, value) in dict.iter_mut() {
+        sanitize(value, removed);
+    }
+}
+
+fn is_forbidden_annotation(dict: &Dictionary) -> bool {
+    dict.has_type(b"Annot")
+        && dict
+            .get(b"Subtype")
+            .and_then(Object::as_name)
+            .is_ok_and(|subtype| FORBIDDEN_ANNOTATIONS.contains(&subtype))
+}
+
+fn is_forbidden_action(dict: &Dictionary) -> bool {
+    dict.get(b"S")
+        .and_then(Object::as_name)
+        .is_ok_and(|action| FORBIDDEN_ACTIONS.contains(&action))
+}
+
+/// すべてのフォントが埋め込まれているか確認
+///
+/// 不可視（Tr 3）のテキストだけに使われるフォントは埋め込まなくてよい。
+fn check_fonts(document: &Document) -> Result<(), PdfConversionError> {
+    let invisible = invisible_fonts(document)?;
+    for (id, object) in &document.objects {
+        let Ok(dict) = object.as_dict() else {
+            continue;
+        };
+        if !dict.has_type(b"Font") || invisible.contains(id) {
+            continue;
+        }
+        let subtype = dict
+            .get(b"Subtype")
+            .and_then(Object::as_name)
+            .unwrap_or_default();
+        // Type0は子孫フォント、Type3はグリフ手続きで判定される
+        if matches!(subtype, b"Type0" | b"Type3") {
+            continue;
+        }
+
+        let embedded = dict
+            .get(b"FontDescriptor")
+            .and_then(|descriptor| document.dereference(descriptor))
+            .and_then(|(_, descriptor)| descriptor.as_dict())
+            .is_ok_and(|descriptor| {
+                [&b"FontFile"[..], b"FontFile2", b"FontFile3"]
+                    .iter()
+                    .any(|key| descriptor.has(key))
+            });
+        if !embedded {
+            let name = dict
+                .get(b"BaseFont")
+                .and_then(Object::as_name)
+                .map(|name| String::from_utf8_lossy(name).to_string())
+                .unwrap_or_default();
+            return Err(PdfConversionError::Conformance(format!(
+                "font is not embedded: {name}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// 不可視（Tr 3）のテキストだけに使われるフォント（Type0の子孫フォントを含む）
+///
+/// ページとフォームXObject・パターンの内容を解析する。解析できない内容から
+/// 参照されるフォントは描画に使われるものとみなす。
+fn invisible_fonts(document: &Document) -> Result<BTreeSet<ObjectId>, PdfConversionError> {
+    let mut usage = FontUsage::default();
+    for page_id in document.get_pages().into_values() {
+        let resources = inherited(document, page_id, b"Resources")?;
+        let content = document.get_page_content(page_id).unwrap_or_default();
+        usage.scan(document, resources.as_ref(), &content);
+    }
+    for object in document.objects.values() {
+        match object {
+            Object::Stream(stream) => {
+                let Ok(resources) = stream.dict.get(b"Resources") else {
+                    continue;
+                };
+                let content = stream
+                    .decompressed_content()
+                    .unwrap_or_else(|_| stream.content.clone());
+                usage.scan(document, Some(resources), &content);
+            }
+            // Type3フォントのグリフ手続きは解析しない
+            Object::Dictionary(dict) if dict.has_type(b"Font") => {
+                if let Ok(resources) = dict.get(b"Resources") {
+                    let fonts = resource_fonts(document, Some(resources));
+                    usage.visible.extend(fonts.into_values());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let visible = with_descendants(document, &usage.visible);
+    Ok(with_descendants(document, &usage.used)
+        .difference(&visible)
+        .copied()
+        .collect())
+}
+
+#[derive(Default)]
+struct FontUsage {
+    used: BTreeSet<ObjectId>,
+    visible: BTreeSet<ObjectId>,
+}
+
+impl FontUsage {
+    /// 内容の文字描画を調べ、使われたフォントを記録する
+    fn scan(&mut self, document: &Document, resources: Option<&Object>, content: &[u8]) {
+        let fonts = resource_fonts(document, resources);
+        let Ok(content) = Content::decode(content) else {
+            self.visible.extend(fonts.values());
+            return;
+        };
+
+        // テキストの描画モードとフォントはグラフィックス状態に含まれる
+        let (mut mode, mut font) = (0, None);
+        let mut stack = vec![];
+        for operation in content.operations {
+            match operation.operator.as_str() {
+                "q" => stack.push((mode, font)),
+                "Q" => (mode, font) = stack.pop().unwrap_or((mode, font)),
+                "Tr" => {
+                    mode = operation
+                        .operands
+                        .first()
+                        .and_then(|mode| mode.as_i64().ok())
+                        .unwrap_or(0);
+                }
+                "Tf" => {
+                    font = operation
+                        .operands
+                        .first()
+                        .and_then(|name| name.as_name().ok())
+                        .and_then(|name| fonts.get(name).copied());
+                }
+                "Tj" | "TJ" | "'" | "\"" => {
+                    if let Some(id) = font {
+                        self.used.insert(id);
+                        if mode != 3 {
+                            self.visible.insert(id);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// リソースのフォント名と参照先
+fn resource_fonts(document: &Document, resources: Option<&Object>) -> BTreeMap<Vec<u8>, ObjectId> {
+    resources
+        .and_then(|resources| document.dereference(resources).ok())
+        .and_then(|(_, resources)| resources.as_dict().ok())
+        .and_then(|resources| resources.get(b"Font").ok())
+        .and_then(|fonts| document.dereference(fonts).ok())
+        .and_then(|(_, fonts)| fonts.as_dict().ok())
+        .map(|fonts| {
+            fonts
+                .iter()
+                .filter_map(|(name, font)| Some((name.clone(), font.as_reference().ok()?)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// フォントとType0フォントの子孫フォント
+fn with_descendants(document: &Document, fonts: &BTreeSet<ObjectId>) -> BTreeSet<ObjectId> {
+    let mut result = fonts.clone();
+    for &id in fonts {
+        let descendants = document
+            .get_dictionary(id)
+            .and_then(|font| font.get(b"DescendantFonts"))
+            .and_then(|descendants| document.dereference(descendants))
+            .and_then(|(_, descendants)| descendants.as_array());
+        if let Ok(descendants) = descendants {
+            result.extend(
+                descendants
+                    .iter()
+                    .filter_map(|font| font.as_reference().ok()),
+            );
+        }
+    }
+    result
+}
+
+/// PDF/A-2bの識別情報を含むXMPメタデータ
+fn xmp(title: Option<&str>) -> String {
+    let title = title
+        .map(|title| {
+            format!(
+                r#"<dc:title><rdf:Alt><rdf:li xml:lang="x-default">{}</rdf:li></rdf:Alt></dc:title>"#,
+                html_escape::encode_text(title)
+            )
+        })
+        .unwrap_or_default();
+
+    format!(
+        r#"<?xpacket begin="{bom}" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about="" xmlns:pdfaid="http://www.aiim.org/pdfa/ns/id/" xmlns:pdf="http://ns.adobe.com/pdf/1.3/" xmlns:dc="http://purl.org/dc/elements/1.1/">
+<pdfaid:part>2</pdfaid:part>
+<pdfaid:conformance>B</pdfaid:conformance>
+<pdf:Producer>{PRODUCER}</pdf:Producer>
+{title}
+</rdf:Description>
+</rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#,
+        bom = '\u{FEFF}',
+    )
+}
+
+/// ページ内容から決定的なドキュメントIDを生成
+fn document_id(document: &Document, title: Option<&str>) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(title.unwrap_or_default().as_bytes());
+    for page_id in document.get_pages().into_values() {
+        if let Ok(content) = document.get_page_content(page_id) {
+            hasher.update(&content);
+        }
+    }
+    hasher.finalize()[..16].to_vec()
+}
+
+/// sRGB（IEC 61966-2.1）のICCプロファイル（v2、ディスプレイクラス）
+fn srgb_profile() -> Vec<u8> {
+    fn s15_fixed16(value: f64) -> [u8; 4] {
+        ((value * 65536.0).round() as i32).to_be_bytes()
+    }
+
+    fn xyz(x: f64, y: f64, z: f64) -> Vec<u8> {
+        let mut data = b"XYZ \0\0\0\0".to_vec();
+        for value in [x, y, z] {
+            data.extend(s15_fixed16(value));
+        }
+        data
+    }
+
+    let description = {
+        let text = b"sRGB IEC61966-2.1\0";
+        let mut data = b"desc\0\0\0\0".to_vec();
+        data.extend((text.len() as u32).to_be_bytes());
+        data.extend(text);
+        // Unicode・ScriptCodeの説明は空
+        data.extend([0; 4 + 4 + 2 + 1 + 67]);
+        data
+    };
+    let copyright = [&b"text\0\0\0\0"[..], b"No copyright, use freely\0"].concat();
+    let curve = {
+        const ENTRIES: u32 = 1024;
+        let mut data = b"curv\0\0\0\0".to_vec();
+        data.extend(ENTRIES.to_be_bytes());
+        for i in 0..ENTRIES {
+            let v = f64::from(i) / f64::from(ENTRIES - 1);
+            let linear = if v <= 0.04045 {
+                v / 12.92
+            } else {
+                ((v + 0.055) / 1.055).powf(2.4)
+            };
+            data.extend(((linear * 65535.0).round() as u16).to_be_bytes());
+        }
+        data
+    };
+
+    // D50に順応させたsRGBの原色
+    let tags: [(&[u8; 4], Vec<u8>); 7] = [
+        (b"desc", description),
+        (b"cprt", copyright),
+        (b"wtpt", xyz(0.9642, 1.0, 0.8249)),
+        (b"rXYZ", xyz(0.4361, 0.2225, 0.0139)),
+        (b"gXYZ", xyz(0.3851, 0.7169, 0.0971)),
+        (b"bXYZ", xyz(0.1431, 0.0606, 0.7141)),
+        (b"rTRC", curve),
+    ];
+    // 緑と青のトーンカーブは赤と同じデータを参照する
+    let tag_count = tags.len() + 2;
+
+    let mut table = vec![];
+    let mut data = vec![];
+    let data_offset = 128 + 4 + 12 * tag_count;
+    let mut curve_entry = (0, 0);
+    for (signature, tag) in &tags {
+        let offset = (data_offset + data.len()) as u32;
+        let size = tag.len() as u32;
+        table.push((**signature, offset, size));
+        if *signature == b"rTRC" {
+            curve_entry = (offset, size);
+        }
+        data.extend(tag);
+        data.resize(data.len().next_multiple_of(4), 0);
+    }
+    table.push((*b"gTRC", curve_entry.0, curve_entry.1));
+    table.push((*b"bTRC", curve_entry.0, curve_entry.1));
+
+    let size = (data_offset + data.len()) as u32;
+    let mut profile = Vec::with_capacity(size as usize);
+    profile.extend(size.to_be_bytes());
+    profile.extend([0; 4]);
+    profile.extend([0x02, 0x10, 0x00, 0x00]);
+    profile.extend(b"mntrRGB XYZ ");
+    for value in [2000u16, 1, 1, 0, 0, 0] {
+        profile.extend(value.to_be_bytes());
+    }
+    profile.extend(b"acsp");
+    profile.extend([0; 4 * 4 + 8 + 4]);
+    profile.extend(s15_fixed16(0.9642));
+    profile.extend(s15_fixed16(1.0));
+    profile.extend(s15_fixed16(0.8249));
+    profile.resize(128, 0);
+
+    profile.extend((tag_count as u32).to_be_bytes());
+    for (signature, offset, size) in table {
+        profile.extend(signature);
+        profile.extend(offset.to_be_bytes());
+        profile.extend(size.to_be_bytes());
+    }
+    profile.extend(data);
+    profile
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        domain::models::{ProcessedSvg, SlideContent},
+        pdf::{
+            stamp, ConvertConfig, Converter, FontConfig, StampConfig, StampContext, StreamConfig,
+            TagConfig,
+        },
+        testing,
+    };
+
+    fn document() -> Document {
+        let mut document = Document::with_version("1.5");
+        let image_id = document.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => 1,
+                "Height" => 1,
+                "ColorSpace" => "DeviceRGB",
+                "BitsPerComponent" => 8,
+                "Interpolate" => true,
+            },
+            vec![0, 0, 0],
+        ));
+        let sound_id = document.add_object(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Sound",
+        });
+        let mut page = testing::page(&mut document, 100, 100, b"/Im0 Do");
+        page.set(
+            "Resources",
+            dictionary! { "XObject" => dictionary! { "Im0" => image_id } },
+        );
+        page.set("AA", dictionary! {});
+        page.set(
+            "Annots",
+            vec![
+                sound_id.into(),
+                Object::Dictionary(dictionary! {
+                    "Type" => "Annot",
+                    "Subtype" => "Link",
+                    "Rect" => vec![0.into(), 0.into(), 10.into(), 10.into()],
+                    "F" => 2,
+                    "A" => dictionary! {
+                        "S" => "JavaScript",
+                        "JS" => Object::string_literal("app.alert(1)"),
+                    },
+                }),
+            ],
+        );
+        let mut document = testing::with_pages(document, vec![page]);
+        document.catalog_mut().unwrap().set(
+            "OpenAction",
+            dictionary! {
+                "S" => "JavaScript",
+                "JS" => Object::string_literal("app.alert(1)"),
+            },
+        );
+        document
+    }
+
+    #[test]
+    fn test_pdfa_structures() {
+        let mut document = document();
+        to_pdfa(&mut document, Some("講義 & スライド")).unwrap();
+
+        assert_eq!(document.version, "1.7");
+        assert!(document.trailer.get(b"Encrypt").is_err());
+        let id = document.trailer.get(b"ID").unwrap().as_array().unwrap();
+        assert_eq!(id.len(), 2);
+
+        let catalog = document.catalog().unwrap();
+        assert!(catalog.get(b"OpenAction").is_err());
+
+        // XMPメタデータ
+        let metadata_id = catalog.get(b"Metadata").unwrap().as_reference().unwrap();
+        let metadata = document
+            .get_object(metadata_id)
+            .unwrap()
+            .as_stream()
+            .unwrap();
+        assert!(metadata.dict.get(b"Filter").is_err());
+        let xmp = String::from_utf8(metadata.content.clone()).unwrap();
+        assert!(xmp.contains("<pdfaid:part>2</pdfaid:part>"));
+        assert!(xmp.contains("<pdfaid:conformance>B</pdfaid:conformance>"));
+        assert!(xmp.contains("講義 &amp; スライド"));
+
+        // sRGBのOutputIntent
+        let intents = catalog.get(b"OutputIntents").unwrap().as_array().unwrap();
+        let intent = intents[0].as_dict().unwrap();
+        assert_eq!(intent.get(b"S").unwrap().as_name().unwrap(), b"GTS_PDFA1");
+        let profile_id = intent
+            .get(b"DestOutputProfile")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        let profile = document
+            .get_object(profile_id)
+            .unwrap()
+            .as_stream()
+            .unwrap();
+        assert_eq!(profile.dict.get(b"N").unwrap().as_i64().unwrap(), 3);
+        let data = profile.decompressed_content().unwrap();
+        assert_eq!(&data[36..40], b"acsp");
+        assert_eq!(
+            u32::from_be_bytes(data[0..4].try_into().unwrap()) as usize,
+            data.len()
+        );
+
+        // Infoとの整合性
+        let info_id = document
+            .trailer
+            .get(b"Info")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        let info = document.get_dictionary(info_id).unwrap();
+        assert_eq!(
+            info.get(b"Producer").unwrap().as_str().unwrap(),
+            PRODUCER.as_bytes()
+        );
+        assert!(xmp.contains(&format!("<pdf:Producer>{PRODUCER}</pdf:Producer>")));
+
+        // 準拠しない構造の除去
+        let page_id = *document.get_pages().get(&1).unwrap();
+        let page = document.get_dictionary(page_id).unwrap();
+        assert!(page.get(b"AA").is_err());
+        let annotations = page.get(b"Annots").unwrap().as_array().unwrap();
+        assert_eq!(annotations.len(), 1);
+        let link = annotations[0].as_dict().unwrap();
+        assert!(link.get(b"A").is_err());
+        assert_eq!(link.get(b"F").unwrap().as_i64().unwrap(), 4);
+        assert!(document
+            .objects
+            .values()
+            .filter_map(|object| object.as_stream().ok())
+            .all(|stream| stream.dict.get(b"Interpolate").is_err()));
+    }
+
+    #[test]
+    fn test_converter_embeds_fonts() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="160" height="90"><text x="10" y="50" font-family="DejaVu Sans, Liberation Sans, Arial, Helvetica">PDF/A</text></svg>"#;
+        let slide = SlideContent::new(testing::page_key(), vec![ProcessedSvg::new(svg, 0)]);
+        let converter = Converter::new(ConvertConfig {
+            pdfa: true,
+            ..ConvertConfig::default()
+        })
+        .unwrap();
+
+        let document = converter.convert(&slide).unwrap();
+
+        let descriptors = document
+            .objects
+            .values()
+            .filter_map(|o| o.as_dict().ok())
+            .filter(|dict| dict.has_type(b"FontDescriptor"))
+            .collect::<Vec<_>>();
+        // いずれのフォントもなくテキストが描画されなければ、検証できないため失敗させる
+        assert!(!descriptors.is_empty(), "no font was found to embed");
+        for dict in descriptors {
+            assert!([&b"FontFile"[..], b"FontFile2", b"FontFile3"]
+                .iter()
+                .any(|key| dict.has(key)));
+        }
+    }
+
+    /// 埋め込まれていないフォントをF1として追加し、ページの内容を置き換える
+    fn with_unembedded_font(content: &[u8]) -> Document {
+        let mut document = document();
+        let font_id = document.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+        });
+        let page_id = *document.get_pages().get(&1).unwrap();
+        document
+            .get_dictionary_mut(page_id)
+            .unwrap()
+            .get_mut(b"Resources")
+            .unwrap()
+            .as_dict_mut()
+            .unwrap()
+            .set("Font", dictionary! { "F1" => font_id });
+        document
+            .change_page_content(page_id, content.to_vec())
+            .unwrap();
+        document
+    }
+
+    #[test]
+    fn test_converter_tagged_pdfa() {
+        // 読み上げ用のテキストは描画しないため、グリフがなければフォントを埋め込まない
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="160" height="90"><g role="img" aria-label="見出し"><rect x="10" y="10" width="100" height="20"/></g></svg>"#;
+        let slide = SlideContent::new(testing::page_key(), vec![ProcessedSvg::new(svg, 0)]);
+        let converter = Converter::new(ConvertConfig {
+            pdfa: true,
+            tags: Some(TagConfig::default()),
+            fonts: FontConfig {
+                system_fonts: false,
+                ..FontConfig::default()
+            },
+            ..ConvertConfig::default()
+        })
+        .unwrap();
+
+        let mut output = vec![];
+        converter
+            .write_to(&slide, &mut output, &StreamConfig::default())
+            .unwrap();
+
+        let document = Document::load_mem(&output).unwrap();
+        assert!(document.catalog().unwrap().has(b"StructTreeRoot"));
+    }
+
+    #[test]
+    fn test_pdfa_rejects_unembedded_font() {
+        for content in [
+            &b"/Im0 Do"[..],
+            b"BT /F1 12 Tf (a) Tj ET",
+            // Qで描画モードが戻る
+            b"q 3 Tr Q BT /F1 12 Tf (a) Tj ET",
+        ] {
+            let mut document = with_unembedded_font(content);
+
+            assert!(matches!(
+                to_pdfa(&mut document, None),
+                Err(PdfConversionError::Conformance(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_pdfa_accepts_invisible_only_font() {
+        let mut document = with_unembedded_font(b"BT /F1 12 Tf 3 Tr (a) Tj ET");
+
+        to_pdfa(&mut document, None).unwrap();
+    }
+
+    #[test]
+    fn test_stamp_font_is_embedded() {
+        let config = StampConfig {
+            header: Some("Page {slide}/{total}".to_string()),
+            watermark: Some("Draft".to_string()),
+            ..StampConfig::default()
+        };
+        let fontdb = testing::font_database();
+        let mut document = stamp::apply(
+            testing::document(2, 400, 300, b"0 0 m"),
+            &config,
+            &StampContext::default(),
+            &fontdb,
+            1,
+            2,
+        )
+        .unwrap();
+
+        to_pdfa(&mut document, None).unwrap();
+    }
+}
@@ -0,0 +1,139 @@
+use super::ImageSizeReport;
+use std::{collections::BTreeSet, fmt};
+
+/// 前処理で発生した警告（PDFの生成は続行できる）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreprocessWarning {
+    /// 画像を取得できなかった
+    ImageFetch {
+        index: usize,
+        url: String,
+        message: String,
+    },
+    /// 画像の形式に対応していない
+    UnsupportedImage { index: usize, url: String },
+    /// 安全でない要素や属性を取り除いた
+    UnsafeContent { index: usize, removed: Vec<String> },
+}
+
+impl PreprocessWarning {
+    /// 警告が発生したSVGのインデックス
+    pub fn index(&self) -> usize {
+        match self {
+            Self::ImageFetch { index, .. }
+            | Self::UnsupportedImage { index, .. }
+            | Self::UnsafeContent { index, .. } => *index,
+        }
+    }
+
+    /// 埋め込めなかった画像のURL（画像の警告でなければNone）
+    pub fn url(&self) -> Option<&str> {
+        match self {
+            Self::ImageFetch { url, .. } | Self::UnsupportedImage { url, .. } => Some(url),
+            Self::UnsafeContent { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for PreprocessWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ImageFetch {
+                index,
+                url,
+                message,
+            } => write!(f, "SVG {index}: failed to fetch image {url}: {message}"),
+            Self::UnsupportedImage { index, url } => {
+                write!(f, "SVG {index}: unsupported image format {url}")
+            }
+            Self::UnsafeContent { index, removed } => {
+                write!(
+                    f,
+                    "SVG {index}: removed unsafe content: {}",
+                    removed.join(", ")
+                )
+            }
+        }
+    }
+}
+
+/// 前処理の結果の報告
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PreprocessReport {
+    pub images: ImageSizeReport,
+    pub warnings: Vec<PreprocessWarning>,
+}
+
+impl PreprocessReport {
+    /// 警告がなければtrue
+    pub fn is_clean(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    /// 埋め込めなかった画像のURL
+    pub fn missing_images(&self) -> BTreeSet<&str> {
+        self.warnings
+            .iter()
+            .filter_map(PreprocessWarning::url)
+            .collect()
+    }
+
+    pub(crate) fn merge(&mut self, other: Self) {
+        self.images.merge(&other.images);
+        self.warnings.extend(other.warnings);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_and_missing_images() {
+        let mut report = PreprocessReport::default();
+        report.merge(PreprocessReport {
+            images: ImageSizeReport {
+                images: 1,
+                original_bytes: 10,
+                output_bytes: 5,
+            },
+            warnings: vec![PreprocessWarning::UnsupportedImage {
+                index: 0,
+                url: "https://example.com/a.tiff".to_string(),
+            }],
+        });
+        report.merge(PreprocessReport {
+            warnings: vec![
+                PreprocessWarning::ImageFetch {
+                    index: 1,
+                    url: "https://example.com/b.png".to_string(),
+                    message: "404 Not Found".to_string(),
+                },
+                PreprocessWarning::UnsupportedImage {
+                    index: 2,
+                    url: "https://example.com/a.tiff".to_string(),
+                },
+                PreprocessWarning::UnsafeContent {
+                    index: 2,
+                    removed: vec!["<script>".to_string(), "onload".to_string()],
+                },
+            ],
+            ..PreprocessReport::default()
+        });
+
+        assert!(!report.is_clean());
+        assert_eq!(report.images.images, 1);
+        assert_eq!(
+            report.missing_images(),
+            BTreeSet::from(["https://example.com/a.tiff", "https://example.com/b.png"])
+        );
+        assert_eq!(
+            report.warnings[1].to_string(),
+            "SVG 1: failed to fetch image https://example.com/b.png: 404 Not Found"
+        );
+        assert_eq!(
+            report.warnings[3].to_string(),
+            "SVG 2: removed unsafe content: <script>, onload"
+        );
+    }
+}
@@ -0,0 +1,528 @@
+use super::error::SanitizeError;
+use base64::{engine::general_purpose, Engine};
+use lol_html::{element, errors::RewritingError, HtmlRewriter, Settings};
+use regex::Regex;
+use std::{collections::BTreeSet, io::Cursor, sync::LazyLock};
+
+/// 描画に使う要素（小文字）。これ以外の要素は中身ごと取り除く
+const ALLOWED_ELEMENTS: [&str; 56] = [
+    "svg",
+    "g",
+    "defs",
+    "symbol",
+    "use",
+    "switch",
+    "a",
+    "title",
+    "desc",
+    "metadata",
+    "style",
+    "path",
+    "rect",
+    "circle",
+    "ellipse",
+    "line",
+    "polyline",
+    "polygon",
+    "text",
+    "tspan",
+    "textpath",
+    "image",
+    "clippath",
+    "mask",
+    "pattern",
+    "marker",
+    "lineargradient",
+    "radialgradient",
+    "stop",
+    "filter",
+    "feblend",
+    "fecolormatrix",
+    "fecomponenttransfer",
+    "fecomposite",
+    "feconvolvematrix",
+    "fediffuselighting",
+    "fedisplacementmap",
+    "fedistantlight",
+    "fedropshadow",
+    "feflood",
+    "fefunca",
+    "fefuncb",
+    "fefuncg",
+    "fefuncr",
+    "fegaussianblur",
+    "feimage",
+    "femerge",
+    "femergenode",
+    "femorphology",
+    "feoffset",
+    "fepointlight",
+    "fespecularlighting",
+    "fespotlight",
+    "fetile",
+    "feturbulence",
+    "view",
+];
+
+/// データURIとして埋め込める形式
+const ALLOWED_DATA_TYPES: [&str; 4] = ["image/png", "image/jpeg", "image/gif", "image/svg+xml"];
+
+/// 画像の寸法を調べるために復号するBase64の長さ
+const HEADER_PREFIX: usize = 64 * 1024;
+
+/// データURIとして埋め込まれたSVGの入れ子の上限
+const MAX_NESTING: usize = 2;
+
+/// 開始タグ・終了タグ（コメント・CDATA・宣言は名前なしで読み飛ばす）
+static TAG: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#"(?s)<!--.*?-->|<!\[CDATA\[.*?\]\]>|<[!?][^>]*>|<(/?)([A-Za-z][^\s/>]*)(?:"[^"]*"|'[^']*'|[^'">])*?(/?)>"#,
+    )
+    .unwrap()
+});
+
+/// 属性値の`url(...)`の参照先
+static URL_REFERENCE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"url\(\s*['"]?\s*([^)'"\s]*)"#).unwrap());
+
+/// 信頼できないSVGの制限
+#[derive(Debug, Clone)]
+pub struct SanitizeConfig {
+    /// SVG全体の最大バイト数（埋め込んだ画像を含む）
+    pub max_bytes: usize,
+    pub max_elements: usize,
+    pub max_paths: usize,
+    /// 要素の入れ子の最大の深さ
+    pub max_depth: usize,
+    /// データURIの最大バイト数（復号後）
+    pub max_data_uri_bytes: usize,
+    /// 埋め込まれた画像の最大画素数
+    pub max_image_pixels: u64,
+}
+
+impl Default for SanitizeConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes: 128 * 1024 * 1024,
+            max_elements: 200_000,
+            max_paths: 100_000,
+            max_depth: 256,
+            max_data_uri_bytes: 16 * 1024 * 1024,
+            max_image_pixels: 8192 * 8192,
+        }
+    }
+}
+
+/// サニタイズしたSVG
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sanitized {
+    pub content: String,
+    /// 取り除いた要素（`<script>`）と属性（`onload`）
+    pub removed: BTreeSet<String>,
+}
+
+enum Verdict {
+    Keep,
+    Replace(String),
+    Remove,
+}
+
+/// 信頼できないSVGを描画できる内容に制限する
+///
+/// スクリプトや`foreignObject`などの許可していない要素、イベント属性、
+/// 外部ファイルへの参照は取り除く。大きさや複雑さが制限を超える場合はエラーを返す。
+pub fn sanitize(svg: &str, config: &SanitizeConfig) -> Result<Sanitized, SanitizeError> {
+    sanitize_nested(svg, config, 0)
+}
+
+fn sanitize_nested(
+    svg: &str,
+    config: &SanitizeConfig,
+    nesting: usize,
+) -> Result<Sanitized, SanitizeError> {
+    check_structure(svg, config)?;
+
+    let mut removed = BTreeSet::new();
+    let mut error = None;
+    let mut output = vec![];
+    let mut rewriter = HtmlRewriter::new(
+        Settings {
+            element_content_handlers: vec![element!("*", |el| {
+                let tag = el.tag_name().to_ascii_lowercase();
+                if !ALLOWED_ELEMENTS.contains(&tag.as_str()) {
+                    removed.insert(format!("<{tag}>"));
+                    el.remove();
+                    return Ok(());
+                }
+
+                let attributes = el
+                    .attributes()
+                    .iter()
+                    .map(|attribute| (attribute.name(), attribute.value()))
+                    .collect::<Vec<_>>();
+                for (name, value) in attributes {
+                    match check_attribute(&tag, &name, &value, config, nesting) {
+                        Ok(Verdict::Keep) => {}
+                        Ok(Verdict::Replace(value)) => el.set_attribute(&name, &value)?,
+                        Ok(Verdict::Remove) => {
+                            el.remove_attribute(&name);
+                            removed.insert(name);
+                        }
+                        Err(e) => {
+                            error.get_or_insert(e);
+                        }
+                    }
+                }
+                Ok(())
+            })],
+            ..Settings::default()
+        },
+        |c: &[u8]| output.extend_from_slice(c),
+    );
+
+    rewriter.write(svg.as_bytes()).map_err(malformed)?;
+    rewriter.end().map_err(malformed)?;
+    if let Some(error) = error {
+        return Err(error);
+    }
+
+    let content = String::from_utf8(output).map_err(|e| SanitizeError::Malformed(e.to_string()))?;
+    Ok(Sanitized { content, removed })
+}
+
+fn malformed(error: RewritingError) -> SanitizeError {
+    SanitizeError::Malformed(error.to_string())
+}
+
+/// 大きさ・要素数・入れ子の深さを確認
+fn check_structure(svg: &str, config: &SanitizeConfig) -> Result<(), SanitizeError> {
+    if svg.len() > config.max_bytes {
+        return Err(SanitizeError::TooLarge {
+            size: svg.len(),
+            limit: config.max_bytes,
+        });
+    }
+    // 実体参照の展開による肥大化（billion laughs）を防ぐ
+    if svg.contains("<!ENTITY") {
+        return Err(SanitizeError::EntityDeclaration);
+    }
+
+    let (mut elements, mut paths, mut depth) = (0, 0, 0_usize);
+    for captures in TAG.captures_iter(svg) {
+        let Some(name) = captures.get(2) else {
+            continue;
+        };
+        if captures.get(1).is_some_and(|slash| !slash.is_empty()) {
+            depth = depth.saturating_sub(1);
+            continue;
+        }
+
+        elements += 1;
+        if elements > config.max_elements {
+            return Err(SanitizeError::TooManyElements {
+                limit: config.max_elements,
+            });
+        }
+        if name.as_str().eq_ignore_ascii_case("path") {
+            paths += 1;
+            if paths > config.max_paths {
+                return Err(SanitizeError::TooManyPaths {
+                    limit: config.max_paths,
+                });
+            }
+        }
+        if captures.get(3).is_some_and(|slash| slash.is_empty()) {
+            depth += 1;
+            if depth > config.max_depth {
+                return Err(SanitizeError::TooDeep {
+                    limit: config.max_depth,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_attribute(
+    tag: &str,
+    name: &str,
+    value: &str,
+    config: &SanitizeConfig,
+    nesting: usize,
+) -> Result<Verdict, SanitizeError> {
+    let name = name.to_ascii_lowercase();
+    if name.starts_with("on") {
+        return Ok(Verdict::Remove);
+    }
+    if name == "href" || name.ends_with(":href") {
+        return check_href(tag, value.trim(), config, nesting);
+    }
+    // 塗りやフィルターなどは文書内の要素だけを参照できる
+    let external = URL_REFERENCE
+        .captures_iter(value)
+        .filter_map(|captures| captures.get(1))
+        .any(|target| !target.as_str().starts_with('#'));
+    Ok(if external {
+        Verdict::Remove
+    } else {
+        Verdict::Keep
+    })
+}
+
+fn check_href(
+    tag: &str,
+    href: &str,
+    config: &SanitizeConfig,
+    nesting: usize,
+) -> Result<Verdict, SanitizeError> {
+    if href.starts_with('#') {
+        return Ok(Verdict::Keep);
+    }
+
+    // スキームのない相対パスや`file:`はローカルのファイルを読み込むため許可しない
+    let scheme = href
+        .split_once(':')
+        .map(|(scheme, _)| scheme.to_ascii_lowercase());
+    match (scheme.as_deref(), tag) {
+        (Some("http" | "https"), "image" | "feimage" | "a") => Ok(Verdict::Keep),
+        (Some("mailto"), "a") => Ok(Verdict::Keep),
+        (Some("data"), "image" | "feimage") => check_data_uri(href, config, nesting),
+        _ => Ok(Verdict::Remove),
+    }
+}
+
+fn check_data_uri(
+    href: &str,
+    config: &SanitizeConfig,
+    nesting: usize,
+) -> Result<Verdict, SanitizeError> {
+    let Some((header, data)) = href
+        .get("data:".len()..)
+        .and_then(|uri| uri.split_once(','))
+    else {
+        return Ok(Verdict::Remove);
+    };
+    let mut parameters = header.split(';');
+    let mime = parameters
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let is_base64 = parameters.any(|parameter| parameter.trim().eq_ignore_ascii_case("base64"));
+    if !is_base64 || !data.is_ascii() || !ALLOWED_DATA_TYPES.contains(&mime.as_str()) {
+        return Ok(Verdict::Remove);
+    }
+
+    let size = data.len() / 4 * 3;
+    if size > config.max_data_uri_bytes {
+        return Err(SanitizeError::DataUriTooLarge {
+            size,
+            limit: config.max_data_uri_bytes,
+        });
+    }
+
+    if mime == "image/svg+xml" {
+        if nesting >= MAX_NESTING {
+            return Err(SanitizeError::NestingTooDeep { limit: MAX_NESTING });
+        }
+        let Some(nested) = general_purpose::STANDARD
+            .decode(data)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+        else {
+            return Ok(Verdict::Remove);
+        };
+        let sanitized = sanitize_nested(&nested, config, nesting + 1)?;
+        if sanitized.content == nested {
+            return Ok(Verdict::Keep);
+        }
+        let base64 = general_purpose::STANDARD.encode(sanitized.content);
+        return Ok(Verdict::Replace(format!(
+            "data:image/svg+xml;base64,{base64}"
+        )));
+    }
+
+    // 展開後のサイズが極端に大きい画像（解凍爆弾）を防ぐ。寸法はヘッダーだけから読む
+    let prefix = &data[..data.len().min(HEADER_PREFIX) / 4 * 4];
+    let Ok(header) = general_purpose::STANDARD.decode(prefix) else {
+        return Ok(Verdict::Remove);
+    };
+    let dimensions = image::ImageReader::new(Cursor::new(header))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_dimensions().ok());
+    if let Some((width, height)) = dimensions {
+        if u64::from(width) * u64::from(height) > config.max_image_pixels {
+            return Err(SanitizeError::ImageTooLarge { width, height });
+        }
+    }
+    Ok(Verdict::Keep)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clean(svg: &str) -> Sanitized {
+        sanitize(svg, &SanitizeConfig::default()).unwrap()
+    }
+
+    fn png(width: u32, height: u32) -> String {
+        let mut bytes = vec![];
+        image::RgbImage::new(width, height)
+            .write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png)
+            .unwrap();
+        general_purpose::STANDARD.encode(bytes)
+    }
+
+    #[test]
+    fn test_safe_svg_is_unchanged() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"><defs><linearGradient id="g"><stop offset="0"/></linearGradient></defs><g role="img" aria-label="a &gt; b"><rect fill="url(#g)" width="10" height="10"/><image xlink:href="https://example.com/a.png"/><use xlink:href="#g"/></g></svg>"##;
+
+        let sanitized = clean(svg);
+
+        assert_eq!(sanitized.content, svg);
+        assert!(sanitized.removed.is_empty());
+    }
+
+    #[test]
+    fn test_scripts_and_foreign_content_are_removed() {
+        let sanitized = clean(
+            r#"<svg onload="alert(1)"><script>alert(2)</script><foreignObject><iframe src="https://example.com"></iframe></foreignObject><rect onclick="alert(3)" width="1"/></svg>"#,
+        );
+
+        assert!(!sanitized.content.contains("alert"));
+        assert!(!sanitized.content.contains("iframe"));
+        assert!(sanitized.content.contains(r#"<rect width="1" />"#));
+        assert!(sanitized.removed.contains("<script>"));
+        assert!(sanitized.removed.contains("<foreignobject>"));
+        assert!(sanitized.removed.contains("onload"));
+        assert!(sanitized.removed.contains("onclick"));
+    }
+
+    #[test]
+    fn test_local_and_script_references_are_removed() {
+        let sanitized = clean(
+            r#"<svg xmlns:xlink="http://www.w3.org/1999/xlink"><image xlink:href="file:///etc/passwd"/><image href="../secret.png"/><a href="javascript:alert(1)"><text>x</text></a><rect fill="url(file:///etc/passwd)" style="fill: url('https://example.com/x.svg#p')"/><a href="https://example.com">y</a></svg>"#,
+        );
+
+        assert!(!sanitized.content.contains("passwd"));
+        assert!(!sanitized.content.contains("secret"));
+        assert!(!sanitized.content.contains("javascript"));
+        assert!(!sanitized.content.contains("x.svg"));
+        assert!(sanitized
+            .content
+            .contains(r#"<a href="https://example.com">"#));
+        assert_eq!(
+            sanitized.removed,
+            BTreeSet::from(["xlink:href", "href", "fill", "style"].map(ToString::to_string))
+        );
+    }
+
+    #[test]
+    fn test_data_uris() {
+        let html = general_purpose::STANDARD.encode("<script>alert(1)</script>");
+        let sanitized = clean(&format!(
+            r#"<svg><image href="data:text/html;base64,{html}"/><image href="data:image/png;base64,{}"/></svg>"#,
+            png(4, 4)
+        ));
+        assert!(!sanitized.content.contains("text/html"));
+        assert!(sanitized.content.contains("data:image/png"));
+
+        // 埋め込まれたSVGもサニタイズする
+        let nested = general_purpose::STANDARD.encode(r#"<svg><script>alert(1)</script></svg>"#);
+        let sanitized = clean(&format!(
+            r#"<svg><image href="data:image/svg+xml;base64,{nested}"/></svg>"#
+        ));
+        assert!(!sanitized.content.contains(&nested));
+        assert!(sanitized.content.contains("data:image/svg+xml;base64,"));
+    }
+
+    #[test]
+    fn test_data_uri_bombs_are_rejected() {
+        let config = SanitizeConfig {
+            max_image_pixels: 8,
+            ..SanitizeConfig::default()
+        };
+        let svg = format!(
+            r#"<svg><image href="data:image/png;base64,{}"/></svg>"#,
+            png(4, 4)
+        );
+        assert!(matches!(
+            sanitize(&svg, &config),
+            Err(SanitizeError::ImageTooLarge {
+                width: 4,
+                height: 4
+            })
+        ));
+
+        let config = SanitizeConfig {
+            max_data_uri_bytes: 16,
+            ..SanitizeConfig::default()
+        };
+        assert!(matches!(
+            sanitize(&svg, &config),
+            Err(SanitizeError::DataUriTooLarge { limit: 16, .. })
+        ));
+
+        // SVGの中にSVGを際限なく入れ子にできない
+        let mut svg = "<svg/>".to_string();
+        for _ in 0..=MAX_NESTING {
+            let base64 = general_purpose::STANDARD.encode(&svg);
+            svg = format!(r#"<svg><image href="data:image/svg+xml;base64,{base64}"/></svg>"#);
+        }
+        assert!(matches!(
+            sanitize(&svg, &SanitizeConfig::default()),
+            Err(SanitizeError::NestingTooDeep { .. })
+        ));
+    }
+
+    #[test]
+    fn test_entity_expansion_is_rejected() {
+        let svg = r#"<?xml version="1.0"?><!DOCTYPE svg [<!ENTITY a "aaaaaaaaaa"><!ENTITY b "&a;&a;&a;&a;&a;&a;&a;&a;&a;&a;">]><svg><text>&b;</text></svg>"#;
+
+        assert!(matches!(
+            sanitize(svg, &SanitizeConfig::default()),
+            Err(SanitizeError::EntityDeclaration)
+        ));
+    }
+
+    #[test]
+    fn test_complexity_limits() {
+        let config = SanitizeConfig {
+            max_bytes: 1024,
+            max_elements: 50,
+            max_paths: 10,
+            max_depth: 20,
+            ..SanitizeConfig::default()
+        };
+
+        let deep = format!("<svg>{}{}</svg>", "<g>".repeat(30), "</g>".repeat(30));
+        assert!(matches!(
+            sanitize(&deep, &config),
+            Err(SanitizeError::TooDeep { limit: 20 })
+        ));
+
+        // 兄弟要素は深さに数えない
+        let wide = format!("<svg>{}</svg>", r#"<g><rect width="1"/></g>"#.repeat(30));
+        assert!(matches!(
+            sanitize(&wide, &config),
+            Err(SanitizeError::TooManyElements { limit: 50 })
+        ));
+
+        let paths = format!("<svg>{}</svg>", r#"<path d="M0 0"/>"#.repeat(11));
+        assert!(matches!(
+            sanitize(&paths, &config),
+            Err(SanitizeError::TooManyPaths { limit: 10 })
+        ));
+
+        let large = format!("<svg><!--{}--></svg>", "x".repeat(2048));
+        assert!(matches!(
+            sanitize(&large, &config),
+            Err(SanitizeError::TooLarge { limit: 1024, .. })
+        ));
+
+        let comments = format!("<svg>{}</svg>", "<!-- <g> -->".repeat(30));
+        assert!(sanitize(&comments, &config).is_ok());
+    }
+}
@@ -0,0 +1,151 @@
+use super::{text::text_string, PdfConversionError};
+use crate::domain::models::{PageKey, ProcessedSvg, SlideContent};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+
+/// 添付したSVGのファイル仕様を指すページの属性
+///
+/// 結合してもページから辿れるように、書き出した後もページに残す。
+pub(crate) const SOURCE_KEY: &str = "CollectSource";
+
+/// 添付するSVGのMIMEタイプ
+const MIME_TYPE: &str = "image/svg+xml";
+
+/// 1ページのドキュメントに変換元のSVGを添付
+///
+/// 添付ファイルはページの`CollectSource`から参照し、PdfWriterがカタログの
+/// 添付ファイルの一覧（`EmbeddedFiles`）にも加える。
+pub(crate) fn attach(
+    document: &mut Document,
+    svg: &ProcessedSvg,
+) -> Result<(), PdfConversionError> {
+    let Some(&page_id) = document.get_pages().values().next() else {
+        return Ok(());
+    };
+
+    let content = svg.content.as_bytes().to_vec();
+    let size = content.len() as i64;
+    let mut stream = Stream::new(
+        dictionary! {
+            "Type" => "EmbeddedFile",
+            "Subtype" => Object::Name(MIME_TYPE.as_bytes().to_vec()),
+            "Params" => dictionary! { "Size" => size },
+        },
+        content,
+    );
+    let _ = stream.compress();
+    let file_id = document.add_object(stream);
+
+    let name = file_name(svg.index);
+    let filespec_id = document.add_object(dictionary! {
+        "Type" => "Filespec",
+        "F" => Object::string_literal(name.as_str()),
+        "UF" => text_string(&name),
+        "Desc" => text_string(&format!("スライド{}の変換元", svg.index + 1)),
+        "AFRelationship" => "Source",
+        "EF" => dictionary! { "F" => file_id },
+    });
+    document
+        .get_dictionary_mut(page_id)?
+        .set(SOURCE_KEY, filespec_id);
+    Ok(())
+}
+
+/// ページに添付したSVGのファイル仕様
+pub(crate) fn source(page: &Dictionary) -> Option<ObjectId> {
+    page.get(SOURCE_KEY.as_bytes())
+        .and_then(Object::as_reference)
+        .ok()
+}
+
+/// 添付ファイルの一覧に表示するファイル名
+pub(crate) fn name(filespec: &Dictionary) -> Option<String> {
+    let name = filespec.get(b"F").and_then(Object::as_str).ok()?;
+    String::from_utf8(name.to_vec()).ok()
+}
+
+/// `Converter`で添付した変換元のSVGを読み出す
+///
+/// SVGはページの順に並べる。スライドの番号は添付したときのファイル名から復元し、
+/// 添付されていないページは飛ばす。結合したPDFでは、複数のページのスライドが
+/// `page_key`の1つのSlideContentにまとめられる。
+pub fn extract_sources(
+    document: &Document,
+    page_key: PageKey,
+) -> Result<SlideContent, PdfConversionError> {
+    let mut svgs = vec![];
+    for page_id in document.get_pages().into_values() {
+        let Some(filespec_id) = source(document.get_dictionary(page_id)?) else {
+            continue;
+        };
+        let filespec = document.get_dictionary(filespec_id)?;
+        let file_id = filespec
+            .get(b"EF")
+            .and_then(Object::as_dict)
+            .and_then(|files| files.get(b"F"))
+            .and_then(Object::as_reference)?;
+        let stream = document.get_object(file_id)?.as_stream()?;
+        let content = stream
+            .decompressed_content()
+            .unwrap_or_else(|_| stream.content.clone());
+
+        let index = name(filespec)
+            .as_deref()
+            .and_then(parse_index)
+            .unwrap_or(svgs.len());
+        svgs.push(ProcessedSvg::new(String::from_utf8(content)?, index));
+    }
+    Ok(SlideContent::new(page_key, svgs))
+}
+
+fn file_name(index: usize) -> String {
+    format!("slide-{:03}.svg", index + 1)
+}
+
+fn parse_index(name: &str) -> Option<usize> {
+    let number = name.strip_prefix("slide-")?.strip_suffix(".svg")?;
+    number.parse::<usize>().ok()?.checked_sub(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn test_attach_and_extract() {
+        let svg = ProcessedSvg::new(
+            r#"<svg xmlns="http://www.w3.org/2000/svg"><text>日本語</text></svg>"#,
+            4,
+        );
+        let mut document = testing::document(1, 160, 90, b"");
+
+        attach(&mut document, &svg).unwrap();
+
+        let page_id = *document.get_pages().values().next().unwrap();
+        let filespec_id = source(document.get_dictionary(page_id).unwrap()).unwrap();
+        let filespec = document.get_dictionary(filespec_id).unwrap();
+        assert_eq!(name(filespec).as_deref(), Some("slide-005.svg"));
+
+        let content = extract_sources(&document, testing::page_key()).unwrap();
+        assert_eq!(content.page_key, testing::page_key());
+        assert_eq!(content.svgs.len(), 1);
+        assert_eq!(content.svgs[0].index, 4);
+        assert_eq!(content.svgs[0].content, svg.content);
+    }
+
+    #[test]
+    fn test_extract_without_sources() {
+        let content =
+            extract_sources(&testing::document(1, 160, 90, b""), testing::page_key()).unwrap();
+
+        assert!(content.is_empty());
+    }
+
+    #[test]
+    fn test_parse_index() {
+        assert_eq!(parse_index(&file_name(0)), Some(0));
+        assert_eq!(parse_index("slide-120.svg"), Some(119));
+        assert_eq!(parse_index("slide-000.svg"), None);
+        assert_eq!(parse_index("notes.svg"), None);
+    }
+}
@@ -0,0 +1,452 @@
+use super::{
+    font::FontConfig,
+    form::{inherited, page_box, sub_dictionary},
+    text::TextFont,
+    PdfConversionError,
+};
+use crate::domain::models::{Course, Lecture, LecturePage, MoocsUrl, UrlBuilder};
+use lopdf::{
+    content::{Content, Operation},
+    dictionary, Dictionary, Document, Object, ObjectId, Stream,
+};
+use std::sync::LazyLock;
+use svg2pdf::usvg::fontdb::Database;
+
+const FONT_NAME: &str = "FStamp";
+const GSTATE_NAME: &str = "GSStamp";
+/// ページ端からテキストまでの距離（pt）
+const MARGIN: f32 = 6.0;
+const TEXT_GRAY: f32 = 0.4;
+const WATERMARK_GRAY: f32 = 0.5;
+const WATERMARK_OPACITY: f32 = 0.2;
+/// 透かしの幅のページの対角線に対する割合
+const WATERMARK_RATIO: f32 = 0.6;
+
+/// 学内限定の透かし
+pub const INTERNAL_USE_WATERMARK: &str = "学内限定";
+
+/// ページに入れるヘッダー・フッター・透かしの設定
+///
+/// ヘッダーとフッターはテンプレートで、`{course}`、`{lecture}`、`{page}`、`{slide}`、
+/// `{total}`、`{url}`が置き換えられる。`{url}`を含む行は元のページへのリンクになる。
+#[derive(Debug, Clone, PartialEq)]
+pub struct StampConfig {
+    pub header: Option<String>,
+    pub footer: Option<String>,
+    /// 対角線上に入れる透かし
+    pub watermark: Option<String>,
+    /// ヘッダー・フッターの文字サイズ（pt）
+    pub font_size: f32,
+}
+
+impl Default for StampConfig {
+    fn default() -> Self {
+        Self {
+            header: None,
+            footer: None,
+            watermark: None,
+            font_size: 8.0,
+        }
+    }
+}
+
+impl StampConfig {
+    fn is_empty(&self) -> bool {
+        self.header.is_none() && self.footer.is_none() && self.watermark.is_none()
+    }
+}
+
+/// テンプレートに埋め込む値
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StampContext {
+    pub course: String,
+    pub lecture: String,
+    pub page: String,
+    /// 元のページのURL
+    pub url: Option<String>,
+}
+
+impl StampContext {
+    /// 科目・授業・ページの情報から作成
+    pub fn new(course: &Course, lecture: &Lecture, page: &LecturePage) -> Self {
+        let url = MoocsUrl::Page {
+            page_key: page.key.clone(),
+        };
+        Self {
+            course: course.display_name().to_string(),
+            lecture: lecture.display_name().to_string(),
+            page: page.display_name().to_string(),
+            url: Some(url.to_url_string(UrlBuilder::default().base_url())),
+        }
+    }
+}
+
+/// テンプレートのプレースホルダーを置き換える（未知のものはそのまま残す）
+pub fn render_template(
+    template: &str,
+    context: &StampContext,
+    slide: usize,
+    total: usize,
+) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+        match &rest[1..end] {
+            "course" => output.push_str(&context.course),
+            "lecture" => output.push_str(&context.lecture),
+            "page" => output.push_str(&context.page),
+            "slide" => output.push_str(&slide.to_string()),
+            "total" => output.push_str(&total.to_string()),
+            "url" => output.push_str(context.url.as_deref().unwrap_or_default()),
+            _ => output.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// ドキュメントの各ページにヘッダー・フッター・透かしを入れる
+///
+/// `{slide}`はページ順の番号、`{total}`はページ数になる。
+pub fn stamp(
+    document: Document,
+    config: &StampConfig,
+    context: &StampContext,
+) -> Result<Document, PdfConversionError> {
+    /// システムフォントの読み込みは重いため使い回す
+    static FONTDB: LazyLock<Database> =
+        LazyLock::new(|| FontConfig::default().database().unwrap_or_default());
+
+    let total = document.get_pages().len();
+    apply(document, config, context, &FONTDB, 1, total)
+}
+
+/// 最初のページのスライド番号を`first_slide`として各ページにスタンプを入れる
+///
+/// テキストのグリフがすべて`fontdb`の書体にあれば、そのサブセットを埋め込む。
+pub(crate) fn apply(
+    mut document: Document,
+    config: &StampConfig,
+    context: &StampContext,
+    fontdb: &Database,
+    first_slide: usize,
+    total: usize,
+) -> Result<Document, PdfConversionError> {
+    if config.is_empty() {
+        return Ok(document);
+    }
+
+    let page_ids = document.get_pages().into_values().collect::<Vec<_>>();
+    let slides = first_slide..first_slide + page_ids.len();
+    let texts = slides
+        .flat_map(|slide| {
+            [&config.header, &config.footer]
+                .into_iter()
+                .flatten()
+                .map(move |template| render_template(template, context, slide, total))
+        })
+        .chain(config.watermark.clone())
+        .collect::<Vec<_>>();
+
+    let mut stamper = Stamper {
+        config,
+        context,
+        total,
+        font: TextFont::new(
+            &mut document,
+            Some(fontdb),
+            texts.iter().map(String::as_str),
+        ),
+        gstate_id: document.add_object(dictionary! {
+            "Type" => "ExtGState",
+            "ca" => WATERMARK_OPACITY,
+        }),
+    };
+    for (i, page_id) in page_ids.into_iter().enumerate() {
+        stamper.stamp_page(&mut document, page_id, first_slide + i)?;
+    }
+    stamper.font.finish(&mut document)?;
+    Ok(document)
+}
+
+struct Stamper<'a> {
+    config: &'a StampConfig,
+    context: &'a StampContext,
+    total: usize,
+    font: TextFont<'a>,
+    gstate_id: ObjectId,
+}
+
+impl Stamper<'_> {
+    fn stamp_page(
+        &mut self,
+        document: &mut Document,
+        page_id: ObjectId,
+        slide: usize,
+    ) -> Result<(), PdfConversionError> {
+        let bbox = page_box(document, page_id)?;
+        let size = self.config.font_size;
+        let mut operations = vec![];
+        let mut links = vec![];
+
+        if let Some(text) = &self.config.watermark {
+            operations.extend(self.watermark_operations(text, &bbox));
+        }
+        let lines = [
+            (&self.config.header, bbox[3] - MARGIN - size),
+            // ディセンダーが切れないように少し上げる
+            (&self.config.footer, bbox[1] + MARGIN + size * 0.2),
+        ];
+        for (template, baseline) in lines {
+            let Some(template) = template else {
+                continue;
+            };
+            let (line, rect) = self.line_operations(template, slide, &bbox, baseline);
+            operations.extend(line);
+            if let (true, Some(url)) = (template.contains("{url}"), &self.context.url) {
+                links.push(document.add_object(dictionary! {
+                    "Type" => "Annot",
+                    "Subtype" => "Link",
+                    "Rect" => rect.iter().map(|&v| v.into()).collect::<Vec<Object>>(),
+                    "Border" => vec![0.into(), 0.into(), 0.into()],
+                    "A" => dictionary! {
+                        "S" => "URI",
+                        "URI" => Object::string_literal(url.as_str()),
+                    },
+                }));
+            }
+        }
+
+        // 元の内容の描画状態がスタンプに影響しないように囲む
+        let mut content = b"q\n".to_vec();
+        content.extend(document.get_page_content(page_id)?);
+        content.extend(b"\nQ\n");
+        content.extend(Content { operations }.encode()?);
+        let mut stream = Stream::new(dictionary! {}, content);
+        let _ = stream.compress();
+        let content_id = document.add_object(stream);
+
+        let mut resources = match inherited(document, page_id, b"Resources")? {
+            Some(Object::Dictionary(resources)) => resources,
+            _ => Dictionary::new(),
+        };
+        let mut fonts = sub_dictionary(document, &resources, b"Font")?;
+        fonts.set(FONT_NAME, self.font.id());
+        resources.set("Font", fonts);
+        let mut gstates = sub_dictionary(document, &resources, b"ExtGState")?;
+        gstates.set(GSTATE_NAME, self.gstate_id);
+        resources.set("ExtGState", gstates);
+
+        let mut annots = match document.get_dictionary(page_id)?.get(b"Annots") {
+            Ok(annots) => document.dereference(annots)?.1.as_array()?.clone(),
+            Err(_) => vec![],
+        };
+        annots.extend(links.into_iter().map(Object::Reference));
+
+        let page = document.get_dictionary_mut(page_id)?;
+        page.set("Contents", content_id);
+        page.set("Resources", resources);
+        if !annots.is_empty() {
+            page.set("Annots", annots);
+        }
+        Ok(())
+    }
+
+    /// 中央揃えの1行のテキストと、その範囲（`[x0, y0, x1, y1]`）
+    fn line_operations(
+        &mut self,
+        template: &str,
+        slide: usize,
+        bbox: &[f32; 4],
+        baseline: f32,
+    ) -> (Vec<Operation>, [f32; 4]) {
+        let size = self.config.font_size;
+        let page_width = bbox[2] - bbox[0];
+        let text = self.font.fit(
+            &render_template(template, self.context, slide, self.total),
+            size,
+            page_width - MARGIN * 2.0,
+        );
+        let text_width = self.font.width(&text, size);
+        let x = bbox[0] + (page_width - text_width) / 2.0;
+
+        let mut operations = vec![
+            Operation::new("q", vec![]),
+            Operation::new("g", vec![TEXT_GRAY.into()]),
+        ];
+        operations.extend(self.font.show(FONT_NAME, size, x, baseline, &text));
+        operations.push(Operation::new("Q", vec![]));
+        let rect = [
+            x,
+            baseline - size * 0.2,
+            x + text_width,
+            baseline + size * 0.9,
+        ];
+        (operations, rect)
+    }
+
+    /// ページの中央に対角線に沿って半透明のテキストを描画
+    fn watermark_operations(&mut self, text: &str, bbox: &[f32; 4]) -> Vec<Operation> {
+        let (page_width, page_height) = (bbox[2] - bbox[0], bbox[3] - bbox[1]);
+        let unit_width = self.font.width(text, 1.0);
+        if unit_width <= 0.0 {
+            return vec![];
+        }
+        let size = page_width.hypot(page_height) * WATERMARK_RATIO / unit_width;
+        let angle = page_height.atan2(page_width);
+        let (sin, cos) = angle.sin_cos();
+        let center = (bbox[0] + page_width / 2.0, bbox[1] + page_height / 2.0);
+
+        let mut operations = vec![
+            Operation::new("q", vec![]),
+            Operation::new("gs", vec![Object::Name(GSTATE_NAME.as_bytes().to_vec())]),
+            Operation::new("g", vec![WATERMARK_GRAY.into()]),
+            Operation::new(
+                "cm",
+                vec![
+                    cos.into(),
+                    sin.into(),
+                    (-sin).into(),
+                    cos.into(),
+                    center.0.into(),
+                    center.1.into(),
+                ],
+            ),
+        ];
+        // 文字の高さの中心を対角線に合わせる
+        operations.extend(self.font.show(
+            FONT_NAME,
+            size,
+            -unit_width * size / 2.0,
+            -size * 0.35,
+            text,
+        ));
+        operations.push(Operation::new("Q", vec![]));
+        operations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn context() -> StampContext {
+        StampContext {
+            course: "情報連携学".to_string(),
+            lecture: "第1回".to_string(),
+            page: "導入".to_string(),
+            url: Some("https://moocs.iniad.org/courses/2024/CS101/01/01-1".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_render_template() {
+        assert_eq!(
+            render_template(
+                "{course} / {lecture} / {page} — {slide}/{total}",
+                &context(),
+                3,
+                12
+            ),
+            "情報連携学 / 第1回 / 導入 — 3/12"
+        );
+        assert_eq!(
+            render_template("{unknown} {", &context(), 1, 1),
+            "{unknown} {"
+        );
+        assert_eq!(render_template("{url}", &StampContext::default(), 1, 1), "");
+    }
+
+    #[test]
+    fn test_stamp_header_and_link() {
+        let config = StampConfig {
+            header: Some("{course} {slide}/{total}".to_string()),
+            footer: Some("{url}".to_string()),
+            ..StampConfig::default()
+        };
+
+        let document = stamp(
+            testing::document(2, 400, 300, b"0 0 m"),
+            &config,
+            &context(),
+        )
+        .unwrap();
+
+        for page_id in document.get_pages().into_values() {
+            let page = document.get_dictionary(page_id).unwrap();
+            let annots = page.get(b"Annots").unwrap().as_array().unwrap();
+            assert_eq!(annots.len(), 1);
+            let link = document
+                .get_dictionary(annots[0].as_reference().unwrap())
+                .unwrap();
+            let action = link.get(b"A").unwrap().as_dict().unwrap();
+            assert_eq!(
+                action.get(b"URI").unwrap().as_str().unwrap(),
+                b"https://moocs.iniad.org/courses/2024/CS101/01/01-1"
+            );
+
+            let fonts = page
+                .get(b"Resources")
+                .unwrap()
+                .as_dict()
+                .unwrap()
+                .get(b"Font")
+                .unwrap()
+                .as_dict()
+                .unwrap();
+            assert!(fonts.has(FONT_NAME.as_bytes()));
+
+            // 元の内容はそのまま残る
+            let content = Content::decode(&document.get_page_content(page_id).unwrap()).unwrap();
+            assert!(content.operations.iter().any(|op| op.operator == "m"));
+            assert_eq!(
+                content
+                    .operations
+                    .iter()
+                    .filter(|op| op.operator == "Tj")
+                    .count(),
+                2
+            );
+        }
+    }
+
+    #[test]
+    fn test_watermark_without_link() {
+        let config = StampConfig {
+            watermark: Some(INTERNAL_USE_WATERMARK.to_string()),
+            ..StampConfig::default()
+        };
+
+        let document = stamp(
+            testing::document(1, 400, 300, b"0 0 m"),
+            &config,
+            &context(),
+        )
+        .unwrap();
+
+        let page_id = *document.get_pages().values().next().unwrap();
+        assert!(!document.get_dictionary(page_id).unwrap().has(b"Annots"));
+        let content = Content::decode(&document.get_page_content(page_id).unwrap()).unwrap();
+        assert!(content.operations.iter().any(|op| op.operator == "gs"));
+    }
+
+    #[test]
+    fn test_empty_config_keeps_document() {
+        let document = stamp(
+            testing::document(1, 400, 300, b"0 0 m"),
+            &StampConfig::default(),
+            &context(),
+        )
+        .unwrap();
+
+        let page_id = *document.get_pages().values().next().unwrap();
+        assert_eq!(document.get_page_content(page_id).unwrap(), b"0 0 m");
+    }
+}
@@ -0,0 +1,463 @@
+use super::{
+    form::{inherited, sub_dictionary},
+    text::{text_string, TextFont},
+    PdfConversionError,
+};
+use lol_html::{element, html_content::ContentType, HtmlRewriter, Settings};
+use lopdf::{
+    content::{Content, Operation},
+    dictionary, Dictionary, Document, Object, Stream,
+};
+use svg2pdf::usvg::{fontdb::Database, Group, Image, Node, Rect, Tree};
+
+/// 構造要素の候補を書き出しまでページに持たせておくキー（書き出し時に取り除く）
+pub(crate) const TAGS_KEY: &str = "CollectTags";
+
+/// `aria-label`を持つ`<g>`の中身を囲む`<g>`のIDの接頭辞
+const ID_PREFIX: &str = "collect-tag-";
+const FONT_NAME: &str = "FTag";
+/// 同じ行とみなす上端の差（ページの高さに対する割合）
+const ROW_RATIO: f32 = 0.05;
+const MAX_FONT_SIZE: f32 = 72.0;
+
+/// タグ付きPDFの設定
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagConfig {
+    /// 文書の言語（BCP 47）
+    pub lang: String,
+}
+
+impl Default for TagConfig {
+    fn default() -> Self {
+        Self {
+            lang: "ja".to_string(),
+        }
+    }
+}
+
+/// `aria-label`を持つ要素
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Label {
+    /// 中身を囲んだ`<g>`のID
+    id: String,
+    text: String,
+}
+
+/// `aria-label`を持つ`<g>`の中身をIDを付けた`<g>`で囲み、ラベルを抽出
+pub(crate) fn mark_labels(svg: &str) -> Result<(String, Vec<Label>), PdfConversionError> {
+    let mut labels = vec![];
+    let mut output = vec![];
+    let mut rewriter = HtmlRewriter::new(
+        Settings {
+            element_content_handlers: vec![element!("g[aria-label]", |el| {
+                let Some(text) = el
+                    .get_attribute("aria-label")
+                    .map(|text| text.trim().to_string())
+                    .filter(|text| !text.is_empty())
+                else {
+                    return Ok(());
+                };
+                if el.is_self_closing() {
+                    return Ok(());
+                }
+
+                let id = format!("{ID_PREFIX}{}", labels.len());
+                el.prepend(&format!(r#"<g id="{id}">"#), ContentType::Html);
+                el.append("</g>", ContentType::Html);
+                labels.push(Label { id, text });
+                Ok(())
+            })],
+            ..Settings::default()
+        },
+        |c: &[u8]| output.extend_from_slice(c),
+    );
+
+    rewriter.write(svg.as_bytes())?;
+    rewriter.end()?;
+
+    Ok((String::from_utf8(output)?, labels))
+}
+
+/// 描画されたラベルを読む順序に並べ、構造要素の候補としてページに記録
+///
+/// 画像を含むものは代替テキスト付きの図、それ以外は最初のものを見出し、残りを段落とする。
+/// `document`は`tree`を変換した1ページのドキュメント。
+pub(crate) fn collect(
+    document: &mut Document,
+    tree: &Tree,
+    labels: &[Label],
+    config: &TagConfig,
+) -> Result<(), PdfConversionError> {
+    let Some(&page_id) = document.get_pages().values().next() else {
+        return Ok(());
+    };
+    let height = tree.size().height();
+    let row = height * ROW_RATIO;
+
+    let mut regions = labels
+        .iter()
+        .filter_map(|label| {
+            let Some(Node::Group(group)) = tree.node_by_id(&label.id) else {
+                return None;
+            };
+            let (rect, figure) = measure(group);
+            let [left, top, right, bottom] = rect.or_else(|| {
+                let rect = group.abs_bounding_box();
+                Some([rect.left(), rect.top(), rect.right(), rect.bottom()])
+            })?;
+            Some((label, [left, height - bottom, right, height - top], figure))
+        })
+        .collect::<Vec<_>>();
+    // 上から下、同じ行なら左から右に読む
+    regions.sort_by(|(_, a, _), (_, b, _)| {
+        let row_a = ((height - a[3]) / row).floor() as i64;
+        let row_b = ((height - b[3]) / row).floor() as i64;
+        row_a.cmp(&row_b).then(a[0].total_cmp(&b[0]))
+    });
+
+    let mut heading = true;
+    let items = regions
+        .into_iter()
+        .map(|(label, rect, figure)| {
+            let kind = match (figure, heading) {
+                (true, _) => "Figure",
+                (false, true) => "H1",
+                (false, false) => "P",
+            };
+            heading &= figure;
+            Object::Dictionary(dictionary! {
+                "S" => kind,
+                "Text" => text_string(&label.text),
+                "BBox" => rect.iter().map(|&v| v.into()).collect::<Vec<Object>>(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    document.get_dictionary_mut(page_id)?.set(
+        TAGS_KEY,
+        dictionary! {
+            "Lang" => Object::string_literal(config.lang.as_str()),
+            "K" => items,
+        },
+    );
+    Ok(())
+}
+
+/// 図形と画像の範囲（`[left, top, right, bottom]`）と、画像を含むかどうか
+///
+/// 前処理で埋め込んだ`aria-label`のテキストは縮小して原点付近に置かれるため、範囲に含めない。
+fn measure(group: &Group) -> (Option<[f32; 4]>, bool) {
+    let mut rect: Option<[f32; 4]> = None;
+    let mut figure = false;
+    let mut extend = |other: [f32; 4]| {
+        rect = Some(match rect {
+            Some(r) => [
+                r[0].min(other[0]),
+                r[1].min(other[1]),
+                r[2].max(other[2]),
+                r[3].max(other[3]),
+            ],
+            None => other,
+        });
+    };
+    for node in group.children() {
+        let bbox = match node {
+            Node::Group(group) => {
+                let (child, has_image) = measure(group);
+                figure |= has_image;
+                if let Some(child) = child {
+                    extend(child);
+                }
+                continue;
+            }
+            Node::Path(path) => path.abs_bounding_box(),
+            Node::Image(image) => {
+                figure = true;
+                match viewport(group, image) {
+                    Some(bbox) => bbox,
+                    None => continue,
+                }
+            }
+            Node::Text(_) => continue,
+        };
+        extend([bbox.left(), bbox.top(), bbox.right(), bbox.bottom()]);
+    }
+    (rect, figure)
+}
+
+/// 画像の表示範囲（`x` `y` `width` `height`）のキャンバス上の範囲
+///
+/// usvgは画像を配置の変換を持つグループ（`group`）に入れ、`abs_bounding_box`には
+/// 表示範囲にその変換まで掛けた範囲を入れるため、配置の変換を外して求め直す。
+/// 逆行列による誤差は0.01pt単位に丸める。
+fn viewport(group: &Group, image: &Image) -> Option<Rect> {
+    let rect = image
+        .abs_bounding_box()
+        .transform(image.abs_transform().invert()?)?;
+    let parent = group
+        .abs_transform()
+        .pre_concat(group.transform().invert()?);
+    let rect = rect.transform(parent)?;
+    let round = |v: f32| (v * 100.0).round() / 100.0;
+    Rect::from_ltrb(
+        round(rect.left()),
+        round(rect.top()),
+        round(rect.right()),
+        round(rect.bottom()),
+    )
+}
+
+/// 記録した構造要素の候補をマークされたコンテンツとしてページに描画
+///
+/// 元の内容はアーティファクトとし、見出しと段落は透明なテキストとして範囲内に重ねる。
+/// マークされたコンテンツのMCIDは候補の順番と一致する。
+/// テキストのグリフがすべて`fontdb`の書体にあれば、そのサブセットを埋め込む。
+pub(crate) fn apply(
+    mut document: Document,
+    fontdb: &Database,
+) -> Result<Document, PdfConversionError> {
+    let pages = document
+        .get_pages()
+        .into_values()
+        .filter(|&page_id| {
+            document
+                .get_dictionary(page_id)
+                .is_ok_and(|page| page.has(TAGS_KEY.as_bytes()))
+        })
+        .collect::<Vec<_>>();
+    if pages.is_empty() {
+        return Ok(document);
+    }
+
+    let pages = pages
+        .into_iter()
+        .map(|page_id| {
+            let items = document
+                .get_dictionary(page_id)?
+                .get(TAGS_KEY.as_bytes())?
+                .as_dict()?
+                .get(b"K")?
+                .as_array()?
+                .clone();
+            Ok((page_id, items))
+        })
+        .collect::<Result<Vec<_>, PdfConversionError>>()?;
+    let texts = pages
+        .iter()
+        .flat_map(|(_, items)| items)
+        .filter_map(|item| item_text(item.as_dict().ok()?).ok())
+        .collect::<Vec<_>>();
+    let mut font = TextFont::new(
+        &mut document,
+        Some(fontdb),
+        texts.iter().map(String::as_str),
+    );
+
+    for (page_id, items) in pages {
+        let mut operations = vec![];
+        for (mcid, item) in items.iter().enumerate() {
+            let item = item.as_dict()?;
+            let kind = item.get(b"S")?.as_name()?;
+            operations.push(Operation::new(
+                "BDC",
+                vec![
+                    Object::Name(kind.to_vec()),
+                    dictionary! { "MCID" => mcid as i64 }.into(),
+                ],
+            ));
+            if kind != b"Figure" {
+                operations.extend(text_operations(&mut font, item)?);
+            }
+            operations.push(Operation::new("EMC", vec![]));
+        }
+
+        // 元の内容（スタンプを含む）は読み上げの対象外にする
+        let mut content = b"/Artifact BMC\nq\n".to_vec();
+        content.extend(document.get_page_content(page_id)?);
+        content.extend(b"\nQ\nEMC\n");
+        content.extend(Content { operations }.encode()?);
+        let mut stream = Stream::new(dictionary! {}, content);
+        let _ = stream.compress();
+        let content_id = document.add_object(stream);
+
+        let mut resources = match inherited(&document, page_id, b"Resources")? {
+            Some(Object::Dictionary(resources)) => resources,
+            _ => Dictionary::new(),
+        };
+        let mut fonts = sub_dictionary(&document, &resources, b"Font")?;
+        fonts.set(FONT_NAME, font.id());
+        resources.set("Font", fonts);
+
+        let page = document.get_dictionary_mut(page_id)?;
+        page.set("Contents", content_id);
+        page.set("Resources", resources);
+    }
+    font.finish(&mut document)?;
+    Ok(document)
+}
+
+/// 構造要素の候補のテキスト（BOM付きUTF-16BE）
+fn item_text(item: &Dictionary) -> Result<String, PdfConversionError> {
+    let text = item.get(b"Text")?.as_str()?;
+    Ok(String::from_utf16_lossy(
+        &text
+            .get(2..)
+            .unwrap_or_default()
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect::<Vec<_>>(),
+    ))
+}
+
+/// 範囲の左上から1行ずつ透明なテキストを置く命令列
+fn text_operations(
+    font: &mut TextFont,
+    item: &Dictionary,
+) -> Result<Vec<Operation>, PdfConversionError> {
+    let text = item_text(item)?;
+    let rect = item
+        .get(b"BBox")?
+        .as_array()?
+        .iter()
+        .map(Object::as_float)
+        .collect::<Result<Vec<_>, _>>()?;
+    let [x0, y0, _, y1] = rect[..] else {
+        return Ok(vec![]);
+    };
+
+    let lines = text.lines().collect::<Vec<_>>();
+    let size = ((y1 - y0) / lines.len().max(1) as f32).clamp(1.0, MAX_FONT_SIZE);
+    let mut operations = vec![
+        Operation::new("BT", vec![]),
+        // 描画しないがテキストとして抽出・選択できる
+        Operation::new("Tr", vec![3.into()]),
+        Operation::new("Tf", vec![FONT_NAME.into(), size.into()]),
+        Operation::new("Td", vec![x0.into(), (y1 - size).into()]),
+    ];
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            operations.push(Operation::new("Td", vec![0.into(), (-size).into()]));
+        }
+        operations.push(Operation::new("Tj", vec![font.encode(line)]));
+    }
+    operations.push(Operation::new("ET", vec![]));
+    Ok(operations)
+}
+
+/// 書き出すページから構造要素の候補を取り除いて返す
+pub(crate) fn take(page: &mut Dictionary) -> Option<Dictionary> {
+    match page.remove(TAGS_KEY.as_bytes()) {
+        Some(Object::Dictionary(tags)) => Some(tags),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use svg2pdf::usvg::Options;
+
+    const SVG: &str = concat!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="200" height="100"><g role="img" aria-label="本文"##,
+        "\n",
+        r##"2行目"><rect x="10" y="50" width="100" height="40"/></g><g role="img" aria-label="図の説明"><image x="150" y="10" width="40" height="30" xlink:href="data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR4nGNgYGD4DwABBAEAwS2OUAAAAABJRU5ErkJggg=="/></g><g role="img" aria-label="タイトル"><rect x="10" y="10" width="100" height="20"/><text transform="scale(0.01)">タイトル</text></g><g aria-label=" "><rect width="5" height="5"/></g></svg>"##
+    );
+
+    fn tagged() -> Document {
+        let (svg, labels) = mark_labels(SVG).unwrap();
+        let tree = Tree::from_str(&svg, &Options::default()).unwrap();
+        let mut document = testing::document(1, 200, 100, b"0 0 m");
+        collect(&mut document, &tree, &labels, &TagConfig::default()).unwrap();
+        document
+    }
+
+    #[test]
+    fn test_mark_labels() {
+        let (svg, labels) = mark_labels(SVG).unwrap();
+
+        assert_eq!(labels.len(), 3);
+        assert_eq!(labels[0].text, "本文\n2行目");
+        assert!(svg.contains("2行目\"><g id=\"collect-tag-0\"><rect"));
+    }
+
+    #[test]
+    fn test_collect_in_reading_order() {
+        let document = tagged();
+
+        let page_id = *document.get_pages().values().next().unwrap();
+        let tags = document
+            .get_dictionary(page_id)
+            .unwrap()
+            .get(TAGS_KEY.as_bytes())
+            .unwrap()
+            .as_dict()
+            .unwrap();
+        assert_eq!(tags.get(b"Lang").unwrap().as_str().unwrap(), b"ja");
+        let items = tags
+            .get(b"K")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| {
+                let item = item.as_dict().unwrap();
+                let bbox = item
+                    .get(b"BBox")
+                    .unwrap()
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|v| v.as_float().unwrap())
+                    .collect::<Vec<_>>();
+                (item.get(b"S").unwrap().as_name().unwrap().to_vec(), bbox)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            items,
+            vec![
+                // 埋め込まれたテキストは範囲に含めない
+                (b"H1".to_vec(), vec![10.0, 70.0, 110.0, 90.0]),
+                (b"Figure".to_vec(), vec![150.0, 60.0, 190.0, 90.0]),
+                (b"P".to_vec(), vec![10.0, 10.0, 110.0, 50.0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_marks_content() {
+        let document = apply(tagged(), &testing::font_database()).unwrap();
+
+        let page_id = *document.get_pages().values().next().unwrap();
+        let content = Content::decode(&document.get_page_content(page_id).unwrap()).unwrap();
+        let operators = content
+            .operations
+            .iter()
+            .map(|operation| operation.operator.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(&operators[..5], ["BMC", "q", "m", "Q", "EMC"]);
+        let mcids = content
+            .operations
+            .iter()
+            .filter(|operation| operation.operator == "BDC")
+            .map(|operation| {
+                operation.operands[1]
+                    .as_dict()
+                    .unwrap()
+                    .get(b"MCID")
+                    .unwrap()
+                    .as_i64()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(mcids, [0, 1, 2]);
+        // 段落は2行
+        let lines = content
+            .operations
+            .iter()
+            .filter(|operation| operation.operator == "Tj")
+            .count();
+        assert_eq!(lines, 3);
+    }
+}
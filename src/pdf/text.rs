@@ -0,0 +1,535 @@
+use super::PdfConversionError;
+use lopdf::{content::Operation, dictionary, Document, Object, ObjectId, Stream, StringFormat};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+use subsetter::GlyphRemapper;
+use svg2pdf::usvg::fontdb::{Database, Style, ID};
+use ttf_parser::{name_id, Face, RawFace, Tag};
+
+/// 埋め込みなしで利用できる日本語フォント（Adobe-Japan1）
+const BASE_FONT: &str = "KozGoPr6N-Medium";
+const ENCODING: &str = "UniJIS-UTF16-H";
+const ELLIPSIS: char = '…';
+const CFF: Tag = Tag::from_bytes(b"CFF ");
+const CFF2: Tag = Tag::from_bytes(b"CFF2");
+
+/// テキストの描画に使うフォント
+///
+/// 描画する文字をすべて含む書体がフォントデータベースにあれば、使ったグリフだけを
+/// サブセットとして埋め込む。なければ埋め込みなしの日本語フォントを使う。
+pub(crate) struct TextFont<'a> {
+    id: ObjectId,
+    embedded: Option<SubsetFont<'a>>,
+}
+
+impl<'a> TextFont<'a> {
+    /// `texts`を描画するフォントを追加（`fontdb`がNoneなら埋め込まない）
+    pub(crate) fn new<'t>(
+        document: &mut Document,
+        fontdb: Option<&'a Database>,
+        texts: impl IntoIterator<Item = &'t str>,
+    ) -> Self {
+        let chars = texts
+            .into_iter()
+            .flat_map(str::chars)
+            .chain([ELLIPSIS])
+            .filter(|c| !c.is_control())
+            .collect::<BTreeSet<_>>();
+        match fontdb.and_then(|fontdb| SubsetFont::find(fontdb, &chars)) {
+            Some(font) => Self {
+                id: document.new_object_id(),
+                embedded: Some(font),
+            },
+            None => Self {
+                id: add_cjk_font(document),
+                embedded: None,
+            },
+        }
+    }
+
+    pub(crate) fn id(&self) -> ObjectId {
+        self.id
+    }
+
+    /// テキストの描画幅（pt）
+    pub(crate) fn width(&self, text: &str, size: f32) -> f32 {
+        text.chars().map(|c| self.advance(c)).sum::<f32>() * size
+    }
+
+    /// 幅に収まるようにテキストを切り詰める
+    pub(crate) fn fit(&self, text: &str, size: f32, max_width: f32) -> String {
+        if self.width(text, size) <= max_width {
+            return text.to_string();
+        }
+
+        let mut fitted = String::new();
+        let mut current = self.advance(ELLIPSIS) * size;
+        for c in text.chars() {
+            let char_width = self.advance(c) * size;
+            if current + char_width > max_width {
+                break;
+            }
+            current += char_width;
+            fitted.push(c);
+        }
+        fitted.push(ELLIPSIS);
+        fitted
+    }
+
+    /// 指定位置にテキストを描画する命令列
+    pub(crate) fn show(
+        &mut self,
+        name: &str,
+        size: f32,
+        x: f32,
+        y: f32,
+        text: &str,
+    ) -> Vec<Operation> {
+        vec![
+            Operation::new("BT", vec![]),
+            Operation::new("Tf", vec![name.into(), size.into()]),
+            Operation::new("Td", vec![x.into(), y.into()]),
+            Operation::new("Tj", vec![self.encode(text)]),
+            Operation::new("ET", vec![]),
+        ]
+    }
+
+    /// テキストをフォントの文字コードの文字列オブジェクトに変換
+    pub(crate) fn encode(&mut self, text: &str) -> Object {
+        match &mut self.embedded {
+            Some(font) => font.encode(text),
+            None => encode(text),
+        }
+    }
+
+    /// 埋め込むフォントを書き出す
+    ///
+    /// サブセットには`encode`したグリフだけが含まれるため、すべてのテキストを変換した後に呼ぶ。
+    pub(crate) fn finish(self, document: &mut Document) -> Result<(), PdfConversionError> {
+        match self.embedded {
+            Some(font) => font.write(document, self.id),
+            None => Ok(()),
+        }
+    }
+
+    /// 文字の送り幅（文字サイズに対する比）
+    fn advance(&self, c: char) -> f32 {
+        match &self.embedded {
+            Some(font) => font.advance(c),
+            // 埋め込まないフォントは半角・全角の幅で近似する
+            None if is_half_width(c) => 0.5,
+            None => 1.0,
+        }
+    }
+}
+
+/// フォントデータベースの書体から作るサブセット
+struct SubsetFont<'a> {
+    fontdb: &'a Database,
+    face: ID,
+    /// 文字ごとのグリフIDと送り幅（文字サイズに対する比）
+    glyphs: HashMap<char, (u16, f32)>,
+    remapper: GlyphRemapper,
+    /// 使ったグリフ（元のID）が表す文字
+    used: BTreeMap<u16, char>,
+}
+
+impl<'a> SubsetFont<'a> {
+    /// `chars`をすべて含む書体を探す（標準の太さの直立体を優先する）
+    fn find(fontdb: &'a Database, chars: &BTreeSet<char>) -> Option<Self> {
+        let mut faces = fontdb.faces().collect::<Vec<_>>();
+        faces.sort_by_key(|face| (face.style != Style::Normal, face.weight.0.abs_diff(400)));
+        faces.into_iter().find_map(|info| {
+            let glyphs = fontdb
+                .with_face_data(info.id, |data, index| glyphs(data, index, chars))
+                .flatten()?;
+            Some(Self {
+                fontdb,
+                face: info.id,
+                glyphs,
+                remapper: GlyphRemapper::new(),
+                used: BTreeMap::new(),
+            })
+        })
+    }
+
+    fn advance(&self, c: char) -> f32 {
+        self.glyphs.get(&c).map_or(0.0, |&(_, advance)| advance)
+    }
+
+    /// サブセットのグリフIDを2バイトずつ並べる（Identity-H）
+    fn encode(&mut self, text: &str) -> Object {
+        let mut bytes = Vec::with_capacity(text.len() * 2);
+        for c in text.chars() {
+            let gid = match self.glyphs.get(&c) {
+                Some(&(gid, _)) => {
+                    self.used.entry(gid).or_insert(c);
+                    self.remapper.remap(gid)
+                }
+                None => 0,
+            };
+            bytes.extend(gid.to_be_bytes());
+        }
+        Object::String(bytes, StringFormat::Hexadecimal)
+    }
+
+    fn write(self, document: &mut Document, id: ObjectId) -> Result<(), PdfConversionError> {
+        self.fontdb
+            .with_face_data(self.face, |data, index| {
+                self.write_face(document, id, data, index)
+            })
+            .unwrap_or(Err(PdfConversionError::FontLoading))
+    }
+
+    /// Type0フォント（Identity-H）と子孫のCIDフォント、ToUnicode CMapを書き出す
+    fn write_face(
+        &self,
+        document: &mut Document,
+        id: ObjectId,
+        data: &[u8],
+        index: u32,
+    ) -> Result<(), PdfConversionError> {
+        let face = Face::parse(data, index).map_err(|_| PdfConversionError::FontLoading)?;
+        let is_cff = face.raw_face().table(CFF).is_some();
+        let units_per_em = f32::from(face.units_per_em());
+        let scale = |value: i16| f32::from(value) / units_per_em * 1000.0;
+
+        let subset = subsetter::subset(data, index, &self.remapper)
+            .map_err(|_| PdfConversionError::FontLoading)?;
+        // CFFはOpenTypeのテーブルからフォントプログラムだけを取り出して埋め込む
+        let program = if is_cff {
+            RawFace::parse(&subset, 0)
+                .ok()
+                .and_then(|face| face.table(CFF))
+                .ok_or(PdfConversionError::FontLoading)?
+                .to_vec()
+        } else {
+            subset
+        };
+        let mut file = Stream::new(dictionary! {}, program);
+        if is_cff {
+            file.dict.set("Subtype", "CIDFontType0C");
+        }
+        let _ = file.compress();
+        let file_id = document.add_object(file);
+
+        let base_font = format!("{}+{}", self.subset_tag(&face), postscript_name(&face));
+        let bbox = face.global_bounding_box();
+        let ascent = scale(face.typographic_ascender().unwrap_or(face.ascender()));
+        let descent = scale(face.typographic_descender().unwrap_or(face.descender()));
+        let cap_height = face
+            .capital_height()
+            .filter(|&height| height > 0)
+            .map_or(ascent, scale);
+        let mut descriptor = dictionary! {
+            "Type" => "FontDescriptor",
+            "FontName" => Object::Name(base_font.clone().into_bytes()),
+            // Symbolic（標準の文字集合にない文字を含む）
+            "Flags" => 4,
+            "FontBBox" => vec![
+                scale(bbox.x_min).into(),
+                scale(bbox.y_min).into(),
+                scale(bbox.x_max).into(),
+                scale(bbox.y_max).into(),
+            ],
+            "ItalicAngle" => face.italic_angle(),
+            "Ascent" => ascent,
+            "Descent" => descent,
+            "CapHeight" => cap_height,
+            "StemV" => 10.0 + 0.244 * (f32::from(face.weight().to_number()) - 50.0),
+        };
+        descriptor.set(if is_cff { "FontFile3" } else { "FontFile2" }, file_id);
+        let descriptor_id = document.add_object(descriptor);
+
+        let widths = self
+            .remapper
+            .remapped_gids()
+            .map(|gid| {
+                let advance = face
+                    .glyph_hor_advance(ttf_parser::GlyphId(gid))
+                    .unwrap_or(0);
+                (f32::from(advance) / units_per_em * 1000.0).into()
+            })
+            .collect::<Vec<Object>>();
+        let mut descendant = dictionary! {
+            "Type" => "Font",
+            "Subtype" => if is_cff { "CIDFontType0" } else { "CIDFontType2" },
+            "BaseFont" => Object::Name(base_font.clone().into_bytes()),
+            "CIDSystemInfo" => dictionary! {
+                "Registry" => Object::string_literal("Adobe"),
+                "Ordering" => Object::string_literal("Identity"),
+                "Supplement" => 0,
+            },
+            "FontDescriptor" => descriptor_id,
+            "DW" => 0,
+            "W" => vec![0.into(), widths.into()],
+        };
+        if !is_cff {
+            descendant.set("CIDToGIDMap", "Identity");
+        }
+        let descendant_id = document.add_object(descendant);
+
+        let mut cmap = Stream::new(dictionary! {}, self.to_unicode().into_bytes());
+        let _ = cmap.compress();
+        let cmap_id = document.add_object(cmap);
+
+        let base_font = if is_cff {
+            format!("{base_font}-Identity-H")
+        } else {
+            base_font
+        };
+        document.objects.insert(
+            id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Font",
+                "Subtype" => "Type0",
+                "BaseFont" => Object::Name(base_font.into_bytes()),
+                "Encoding" => "Identity-H",
+                "DescendantFonts" => vec![descendant_id.into()],
+                "ToUnicode" => cmap_id,
+            }),
+        );
+        Ok(())
+    }
+
+    /// サブセットを区別する6文字の大文字のタグ
+    fn subset_tag(&self, face: &Face) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(postscript_name(face));
+        for gid in self.remapper.remapped_gids() {
+            hasher.update(gid.to_be_bytes());
+        }
+        hasher.finalize()[..6]
+            .iter()
+            .map(|byte| char::from(b'A' + byte % 26))
+            .collect()
+    }
+
+    /// サブセットのグリフIDから文字を引くCMap
+    fn to_unicode(&self) -> String {
+        let pairs = self
+            .used
+            .iter()
+            .filter_map(|(&gid, &c)| Some((self.remapper.get(gid)?, c)))
+            .collect::<Vec<_>>();
+
+        let mut cmap = String::from(
+            "/CIDInit /ProcSet findresource begin\n\
+             12 dict begin\n\
+             begincmap\n\
+             /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+             /CMapName /Adobe-Identity-UCS def\n\
+             /CMapType 2 def\n\
+             1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+        );
+        // 1つのbfcharブロックには100組まで
+        for chunk in pairs.chunks(100) {
+            let _ = writeln!(cmap, "{} beginbfchar", chunk.len());
+            for (gid, c) in chunk {
+                let unicode = c
+                    .encode_utf16(&mut [0; 2])
+                    .iter()
+                    .map(|unit| format!("{unit:04X}"))
+                    .collect::<String>();
+                let _ = writeln!(cmap, "<{gid:04X}> <{unicode}>");
+            }
+            cmap.push_str("endbfchar\n");
+        }
+        cmap.push_str(
+            "endcmap\n\
+             CMapName currentdict /CMap defineresource pop\n\
+             end\n\
+             end\n",
+        );
+        cmap
+    }
+}
+
+/// 書体が`chars`をすべて含めば、文字ごとのグリフIDと送り幅を返す
+fn glyphs(data: &[u8], index: u32, chars: &BTreeSet<char>) -> Option<HashMap<char, (u16, f32)>> {
+    let face = Face::parse(data, index).ok()?;
+    // CFF2の埋め込みにはPDF 2.0が必要なため使わない
+    if face.raw_face().table(CFF2).is_some() {
+        return None;
+    }
+    let units_per_em = f32::from(face.units_per_em());
+    chars
+        .iter()
+        .map(|&c| {
+            let gid = face.glyph_index(c)?;
+            let advance = f32::from(face.glyph_hor_advance(gid).unwrap_or(0)) / units_per_em;
+            Some((c, (gid.0, advance)))
+        })
+        .collect()
+}
+
+/// PostScript名（PDFの名前に使えない文字は除く）
+fn postscript_name(face: &Face) -> String {
+    let name = face
+        .names()
+        .into_iter()
+        .filter(|name| name.name_id == name_id::POST_SCRIPT_NAME)
+        .find_map(|name| name.to_string())
+        .unwrap_or_default();
+    let name = name
+        .chars()
+        .filter(|c| c.is_ascii_graphic() && !"()<>[]{}/%#".contains(*c))
+        .take(100)
+        .collect::<String>();
+    if name.is_empty() {
+        "Unknown".to_string()
+    } else {
+        name
+    }
+}
+
+/// CJKテキスト描画用のType0フォントを追加
+fn add_cjk_font(document: &mut Document) -> ObjectId {
+    let descriptor_id = document.add_object(dictionary! {
+        "Type" => "FontDescriptor",
+        "FontName" => BASE_FONT,
+        "Flags" => 4,
+        "FontBBox" => vec![(-149).into(), (-374).into(), 1254.into(), 1008.into()],
+        "ItalicAngle" => 0,
+        "Ascent" => 880,
+        "Descent" => -120,
+        "CapHeight" => 763,
+        "StemV" => 116,
+    });
+    let descendant_id = document.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "CIDFontType0",
+        "BaseFont" => BASE_FONT,
+        "CIDSystemInfo" => dictionary! {
+            "Registry" => Object::string_literal("Adobe"),
+            "Ordering" => Object::string_literal("Japan1"),
+            "Supplement" => 6,
+        },
+        "FontDescriptor" => descriptor_id,
+        "DW" => 1000,
+        // 半角英数字（CID 1〜95）はプロポーショナルではなく半角幅
+        "W" => vec![1.into(), 95.into(), 500.into()],
+    });
+    document.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type0",
+        "BaseFont" => format!("{BASE_FONT}-{ENCODING}"),
+        "Encoding" => ENCODING,
+        "DescendantFonts" => vec![descendant_id.into()],
+    })
+}
+
+/// テキストをUTF-16BEの文字列オブジェクトに変換
+fn encode(text: &str) -> Object {
+    let bytes = text
+        .encode_utf16()
+        .flat_map(|unit| unit.to_be_bytes())
+        .collect::<Vec<_>>();
+    Object::String(bytes, StringFormat::Hexadecimal)
+}
+
+fn is_half_width(c: char) -> bool {
+    c.is_ascii() || ('\u{FF61}'..='\u{FF9F}').contains(&c)
+}
+
+/// BOM付きUTF-16BEのテキスト文字列
+pub(crate) fn text_string(text: &str) -> Object {
+    let bytes = [0xFE, 0xFF]
+        .into_iter()
+        .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+        .collect();
+    Object::String(bytes, StringFormat::Hexadecimal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn test_encode_utf16be() {
+        let mut document = Document::with_version("1.7");
+        let mut font = TextFont::new(&mut document, None, ["Aあ"]);
+
+        assert_eq!(
+            font.encode("Aあ"),
+            Object::String(vec![0x00, 0x41, 0x30, 0x42], StringFormat::Hexadecimal)
+        );
+    }
+
+    #[test]
+    fn test_fit() {
+        let mut document = Document::with_version("1.7");
+        let font = TextFont::new(&mut document, None, ["abcd", "あいうえお"]);
+
+        assert_eq!(font.fit("abcd", 10.0, 100.0), "abcd");
+        assert_eq!(font.fit("あいうえお", 10.0, 30.0), "あい…");
+    }
+
+    #[test]
+    fn test_embed_subset() {
+        let fontdb = testing::font_database();
+        let mut document = Document::with_version("1.7");
+        let mut font = TextFont::new(&mut document, Some(&fontdb), ["Page 1/2"]);
+        let id = font.id();
+
+        // .notdefの次から使った順に番号が振られる
+        assert_eq!(
+            font.encode("Pa"),
+            Object::String(vec![0, 1, 0, 2], StringFormat::Hexadecimal)
+        );
+        assert!(font.width("Page", 10.0) > 0.0);
+        font.finish(&mut document).unwrap();
+
+        let type0 = document.get_dictionary(id).unwrap();
+        assert_eq!(
+            type0.get(b"Encoding").unwrap().as_name().unwrap(),
+            b"Identity-H"
+        );
+        let base_font = type0.get(b"BaseFont").unwrap().as_name().unwrap();
+        assert_eq!(base_font[6], b'+');
+        assert!(base_font.ends_with(b"Tuffy"));
+        let descendant_id = type0.get(b"DescendantFonts").unwrap().as_array().unwrap()[0]
+            .as_reference()
+            .unwrap();
+        let descriptor_id = document
+            .get_dictionary(descendant_id)
+            .unwrap()
+            .get(b"FontDescriptor")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        assert!(document
+            .get_dictionary(descriptor_id)
+            .unwrap()
+            .has(b"FontFile2"));
+
+        let cmap_id = type0.get(b"ToUnicode").unwrap().as_reference().unwrap();
+        let mut cmap = document
+            .get_object(cmap_id)
+            .unwrap()
+            .as_stream()
+            .unwrap()
+            .clone();
+        cmap.decompress().unwrap();
+        let cmap = String::from_utf8(cmap.content).unwrap();
+        assert!(cmap.contains("<0001> <0050>"));
+        assert!(cmap.contains("<0002> <0061>"));
+    }
+
+    #[test]
+    fn test_fallback_without_glyphs() {
+        let fontdb = testing::font_database();
+        let mut document = Document::with_version("1.7");
+        let font = TextFont::new(&mut document, Some(&fontdb), ["学内限定"]);
+        let id = font.id();
+        font.finish(&mut document).unwrap();
+
+        let type0 = document.get_dictionary(id).unwrap();
+        assert_eq!(
+            type0.get(b"BaseFont").unwrap().as_name().unwrap(),
+            format!("{BASE_FONT}-{ENCODING}").as_bytes()
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use super::{
+    error::ImageConvertError,
+    mime::Mime,
+    optimize::{is_opaque, is_photo},
+};
+use image::{codecs::jpeg::JpegEncoder, DynamicImage, ImageFormat};
+use std::{borrow::Cow, io::Cursor};
+
+/// svg2pdfが描画できない形式（WebP・BMP・TIFF・ICOなど）の画像をPNGまたはJPEGに変換
+///
+/// 描画できる形式はそのまま返す。不透明な写真はJPEGに、それ以外はPNGにする。
+/// `image`クレートでデコードできない形式はエラーになる。AVIFは`avif`フィーチャー
+/// （システムのdav1dが必要）を有効にしたときだけデコードする。
+pub(crate) fn transcode(
+    bytes: &[u8],
+    jpeg_quality: u8,
+) -> Result<Cow<'_, [u8]>, ImageConvertError> {
+    if Mime::try_from(bytes)?.is_renderable() {
+        return Ok(Cow::Borrowed(bytes));
+    }
+
+    let image = image::load_from_memory(bytes).map_err(|_| ImageConvertError::UnsupportedFormat)?;
+    let mut output = vec![];
+    if is_opaque(&image) && is_photo(&image) {
+        let rgb = DynamicImage::ImageRgb8(image.to_rgb8());
+        JpegEncoder::new_with_quality(&mut output, jpeg_quality.clamp(1, 100))
+            .encode_image(&rgb)
+            .map_err(|_| ImageConvertError::UnsupportedFormat)?;
+    } else {
+        image
+            .write_to(&mut Cursor::new(&mut output), ImageFormat::Png)
+            .map_err(|_| ImageConvertError::UnsupportedFormat)?;
+    }
+    Ok(Cow::Owned(output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage, Rgba, RgbaImage};
+
+    fn encode(image: DynamicImage, format: ImageFormat) -> Vec<u8> {
+        let mut output = vec![];
+        image
+            .write_to(&mut Cursor::new(&mut output), format)
+            .unwrap();
+        output
+    }
+
+    #[test]
+    fn test_renderable_image_is_borrowed() {
+        let png = encode(
+            DynamicImage::ImageRgb8(RgbImage::new(4, 4)),
+            ImageFormat::Png,
+        );
+
+        assert!(matches!(transcode(&png, 85).unwrap(), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_bmp_to_png() {
+        let bmp = encode(
+            DynamicImage::ImageRgb8(RgbImage::from_pixel(4, 4, Rgb([255, 0, 0]))),
+            ImageFormat::Bmp,
+        );
+
+        let output = transcode(&bmp, 85).unwrap();
+
+        assert_eq!(image::guess_format(&output).unwrap(), ImageFormat::Png);
+    }
+
+    #[test]
+    fn test_tiff_and_ico_are_detected() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(16, 16, Rgba([0, 0, 255, 128])));
+        let tiff = encode(image.clone(), ImageFormat::Tiff);
+        let ico = encode(image, ImageFormat::Ico);
+
+        assert!(matches!(Mime::try_from(&tiff[..]), Ok(Mime::Tiff)));
+        assert!(matches!(Mime::try_from(&ico[..]), Ok(Mime::Ico)));
+        for bytes in [tiff, ico] {
+            let output = transcode(&bytes, 85).unwrap();
+            assert_eq!(image::guess_format(&output).unwrap(), ImageFormat::Png);
+        }
+    }
+
+    #[test]
+    fn test_unknown_format() {
+        assert!(transcode(b"not an image", 85).is_err());
+    }
+}
@@ -0,0 +1,961 @@
+use super::{
+    dedup::ImageDeduplicator, form::inherited, pdfa::PRODUCER, source, tag, text::text_string,
+    PdfConversionError,
+};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, StringFormat};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, Write},
+    ops::Range,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// ページツリーのルート（ページの一覧が揃う最後に書き出す）
+const PAGES_ID: ObjectId = (1, 0);
+/// ページへ移す継承可能な属性
+const INHERITABLE: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// ストリーミング書き出しの設定
+#[derive(Debug, Clone)]
+pub struct StreamConfig {
+    /// 同時にメモリ上へ展開するページの推定サイズの上限（バイト）
+    pub memory_limit: usize,
+    /// 同一内容の画像を1つにまとめる
+    pub deduplicate_images: bool,
+    /// 文書情報に入れる作成日時
+    pub timestamp: Timestamp,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            memory_limit: 256 * 1024 * 1024,
+            deduplicate_images: true,
+            timestamp: Timestamp::default(),
+        }
+    }
+}
+
+/// 出力に含める作成日時
+///
+/// 既定では日時を含めないため、同じ入力からは常に同じバイト列が出力される。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timestamp {
+    #[default]
+    Omit,
+    /// 書き出しを始めた時刻
+    Now,
+    /// 指定した時刻（`SOURCE_DATE_EPOCH`などで固定する場合）
+    At(SystemTime),
+}
+
+/// しおりのID
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookmarkId(usize);
+
+#[derive(Debug)]
+struct Bookmark {
+    title: String,
+    page: ObjectId,
+    parent: Option<BookmarkId>,
+}
+
+/// ページ単位でPDFを書き出すライター
+///
+/// 追加したドキュメントのオブジェクトはすぐに書き出され、メモリにはページの一覧と
+/// 相互参照表の位置だけが残る。出力には`BufWriter`などのバッファ付きWriterを渡す。
+///
+/// オブジェクト番号は追加した順に振られ、ドキュメントIDは書き出した内容のハッシュになる。
+/// 同じドキュメントを同じ順に追加すれば、出力は実行ごとに変わらない。
+pub struct PdfWriter<W: Write> {
+    output: CountingWriter<W>,
+    offsets: BTreeMap<u32, u64>,
+    next_id: u32,
+    kids: Vec<ObjectId>,
+    bookmarks: Vec<Bookmark>,
+    /// タグ付けするページと構造要素の候補（インデックスがページのStructParents）
+    tagged: Vec<(ObjectId, Dictionary)>,
+    /// ページに添付された変換元のファイル名とファイル仕様
+    sources: Vec<(String, ObjectId)>,
+    deduplicator: Option<ImageDeduplicator>,
+    created: Option<SystemTime>,
+}
+
+impl<W: Write> PdfWriter<W> {
+    /// ヘッダーを書き出してライターを作成
+    pub fn new(output: W, config: &StreamConfig) -> Result<Self, PdfConversionError> {
+        let mut output = CountingWriter {
+            inner: output,
+            position: 0,
+            hasher: Sha256::new(),
+        };
+        output.write_all(b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n")?;
+
+        Ok(Self {
+            output,
+            offsets: BTreeMap::new(),
+            next_id: PAGES_ID.0 + 1,
+            kids: vec![],
+            bookmarks: vec![],
+            tagged: vec![],
+            sources: vec![],
+            deduplicator: config.deduplicate_images.then(ImageDeduplicator::default),
+            created: match config.timestamp {
+                Timestamp::Omit => None,
+                Timestamp::Now => Some(SystemTime::now()),
+                Timestamp::At(time) => Some(time),
+            },
+        })
+    }
+
+    /// 書き出したページ数
+    pub fn page_count(&self) -> usize {
+        self.kids.len()
+    }
+
+    /// ドキュメントのページを末尾に追加し、書き出したページのIDを返す
+    ///
+    /// カタログやしおりなどページから参照されないオブジェクトは書き出さない。
+    pub fn add_document(
+        &mut self,
+        mut document: Document,
+    ) -> Result<Vec<ObjectId>, PdfConversionError> {
+        let pages = document.get_pages().into_values().collect::<Vec<_>>();
+        for &page_id in &pages {
+            for key in INHERITABLE {
+                if document.get_dictionary(page_id)?.has(key) {
+                    continue;
+                }
+                if let Some(value) = inherited(&document, page_id, key)? {
+                    document.get_dictionary_mut(page_id)?.set(key, value);
+                }
+            }
+        }
+
+        let mapping = reachable(&document, &pages)
+            .into_iter()
+            .zip(self.next_id..)
+            .map(|(old, new)| (old, (new, 0)))
+            .collect::<BTreeMap<_, _>>();
+        self.next_id += mapping.len() as u32;
+
+        let mut objects = BTreeMap::new();
+        for (old, &new) in &mapping {
+            if let Some(mut object) = document.objects.remove(old) {
+                renumber(&mut object, &mapping);
+                objects.insert(new, object);
+            }
+        }
+        drop(document);
+
+        let pages = pages.iter().map(|id| mapping[id]).collect::<Vec<_>>();
+        for page_id in &pages {
+            if let Some(Object::Dictionary(page)) = objects.get_mut(page_id) {
+                page.set("Parent", PAGES_ID);
+                if let Some(tags) = tag::take(page) {
+                    page.set("StructParents", self.tagged.len() as i64);
+                    // 注釈もタブキーで構造の順に移動する
+                    page.set("Tabs", "S");
+                    self.tagged.push((*page_id, tags));
+                }
+            }
+        }
+        for filespec_id in pages.iter().filter_map(|id| {
+            let page = objects.get(id)?.as_dict().ok()?;
+            source::source(page)
+        }) {
+            let name = objects
+                .get(&filespec_id)
+                .and_then(|filespec| filespec.as_dict().ok())
+                .and_then(source::name)
+                .unwrap_or_default();
+            self.sources.push((name, filespec_id));
+        }
+        if let Some(deduplicator) = &mut self.deduplicator {
+            deduplicator.apply(&mut objects);
+        }
+
+        for (id, object) in objects {
+            self.write_indirect(id, &object)?;
+        }
+        self.kids.extend(&pages);
+        Ok(pages)
+    }
+
+    /// しおりを追加（`parent`を指定すると子のしおりになる）
+    pub fn add_bookmark(
+        &mut self,
+        title: impl Into<String>,
+        page: ObjectId,
+        parent: Option<BookmarkId>,
+    ) -> BookmarkId {
+        self.bookmarks.push(Bookmark {
+            title: title.into(),
+            page,
+            parent,
+        });
+        BookmarkId(self.bookmarks.len() - 1)
+    }
+
+    /// ページツリー・しおり・構造ツリー・添付ファイル・カタログ・相互参照表を書き出して完了
+    pub fn finish(mut self) -> Result<W, PdfConversionError> {
+        let outlines_id = self.write_outlines()?;
+        let structure = self.write_structure()?;
+
+        let kids = self
+            .kids
+            .iter()
+            .map(|&id| id.into())
+            .collect::<Vec<Object>>();
+        let pages = dictionary! {
+            "Type" => "Pages",
+            "Count" => kids.len() as i64,
+            "Kids" => kids,
+        };
+        self.write_indirect(PAGES_ID, &Object::Dictionary(pages))?;
+
+        let catalog_id = self.new_id();
+        let mut catalog = dictionary! {
+            "Type" => "Catalog",
+            "Pages" => PAGES_ID,
+        };
+        if let Some(outlines_id) = outlines_id {
+            catalog.set("Outlines", outlines_id);
+            catalog.set("PageMode", "UseOutlines");
+        }
+        if let Some((struct_tree_id, lang)) = structure {
+            catalog.set("StructTreeRoot", struct_tree_id);
+            catalog.set("MarkInfo", dictionary! { "Marked" => true });
+            if let Some(lang) = lang {
+                catalog.set("Lang", lang);
+            }
+        }
+        if !self.sources.is_empty() {
+            // 名前ツリーのキーは一意で昇順に並ぶ必要があるため、同じファイル名でも番号で区別する
+            let names = self
+                .sources
+                .iter()
+                .enumerate()
+                .flat_map(|(i, (name, filespec_id))| {
+                    [
+                        Object::string_literal(format!("{i:05} {name}")),
+                        Object::Reference(*filespec_id),
+                    ]
+                })
+                .collect::<Vec<_>>();
+            catalog.set(
+                "Names",
+                dictionary! { "EmbeddedFiles" => dictionary! { "Names" => names } },
+            );
+        }
+        self.write_indirect(catalog_id, &Object::Dictionary(catalog))?;
+
+        let info_id = self.new_id();
+        let mut info = dictionary! {
+            "Producer" => Object::string_literal(PRODUCER),
+        };
+        if let Some(created) = self.created {
+            let date = Object::string_literal(pdf_date(created));
+            info.set("CreationDate", date.clone());
+            info.set("ModDate", date);
+        }
+        self.write_indirect(info_id, &Object::Dictionary(info))?;
+
+        // 乱数や時刻ではなく内容から決めることで、同じ内容なら同じIDになる
+        let id = self.output.hasher.clone().finalize()[..16].to_vec();
+        let xref_offset = self.output.position;
+        let size = self.next_id;
+        writeln!(self.output, "xref\n0 {size}")?;
+        self.output.write_all(b"0000000000 65535 f \n")?;
+        for id in 1..size {
+            match self.offsets.get(&id) {
+                Some(offset) => writeln!(self.output, "{offset:010} 00000 n ")?,
+                None => self.output.write_all(b"0000000000 65535 f \n")?,
+            }
+        }
+        let trailer = dictionary! {
+            "Size" => i64::from(size),
+            "Root" => catalog_id,
+            "Info" => info_id,
+            "ID" => vec![
+                Object::String(id.clone(), StringFormat::Hexadecimal),
+                Object::String(id, StringFormat::Hexadecimal),
+            ],
+        };
+        self.output.write_all(b"trailer\n")?;
+        write_dictionary(&mut self.output, &trailer, None)?;
+        writeln!(self.output, "\nstartxref\n{xref_offset}\n%%EOF")?;
+        self.output.flush()?;
+
+        Ok(self.output.inner)
+    }
+
+    fn new_id(&mut self) -> ObjectId {
+        let id = (self.next_id, 0);
+        self.next_id += 1;
+        id
+    }
+
+    fn write_indirect(&mut self, id: ObjectId, object: &Object) -> io::Result<()> {
+        self.offsets.insert(id.0, self.output.position);
+        writeln!(self.output, "{} {} obj", id.0, id.1)?;
+        write_object(&mut self.output, object)?;
+        self.output.write_all(b"\nendobj\n")
+    }
+
+    fn write_outlines(&mut self) -> io::Result<Option<ObjectId>> {
+        if self.bookmarks.is_empty() {
+            return Ok(None);
+        }
+
+        let count = self.bookmarks.len();
+        let root_id = self.new_id();
+        let ids = (0..count).map(|_| self.new_id()).collect::<Vec<_>>();
+
+        let mut top = vec![];
+        let mut children = vec![vec![]; count];
+        for (i, bookmark) in self.bookmarks.iter().enumerate() {
+            match bookmark.parent {
+                Some(BookmarkId(parent)) => children[parent].push(i),
+                None => top.push(i),
+            }
+        }
+        let mut prev = vec![None; count];
+        let mut next = vec![None; count];
+        for siblings in children.iter().chain([&top]) {
+            for pair in siblings.windows(2) {
+                next[pair[0]] = Some(pair[1]);
+                prev[pair[1]] = Some(pair[0]);
+            }
+        }
+        // 親は子より先に追加されるため、逆順に子孫の数を集計できる
+        let mut descendants = vec![0_i64; count];
+        for (i, bookmark) in self.bookmarks.iter().enumerate().rev() {
+            if let Some(BookmarkId(parent)) = bookmark.parent {
+                descendants[parent] += descendants[i] + 1;
+            }
+        }
+
+        let mut items = vec![];
+        for (i, bookmark) in self.bookmarks.iter().enumerate() {
+            let mut item = dictionary! {
+                "Title" => text_string(&bookmark.title),
+                "Parent" => bookmark.parent.map_or(root_id, |BookmarkId(parent)| ids[parent]),
+                "Dest" => vec![bookmark.page.into(), "Fit".into()],
+            };
+            if let Some(prev) = prev[i] {
+                item.set("Prev", ids[prev]);
+            }
+            if let Some(next) = next[i] {
+                item.set("Next", ids[next]);
+            }
+            if let (Some(&first), Some(&last)) = (children[i].first(), children[i].last()) {
+                item.set("First", ids[first]);
+                item.set("Last", ids[last]);
+                item.set("Count", descendants[i]);
+            }
+            items.push((ids[i], item));
+        }
+
+        let mut root = dictionary! {
+            "Type" => "Outlines",
+            "Count" => count as i64,
+        };
+        if let (Some(&first), Some(&last)) = (top.first(), top.last()) {
+            root.set("First", ids[first]);
+            root.set("Last", ids[last]);
+        }
+        self.write_indirect(root_id, &Object::Dictionary(root))?;
+        for (id, item) in items {
+            self.write_indirect(id, &Object::Dictionary(item))?;
+        }
+        Ok(Some(root_id))
+    }
+
+    /// タグ付けしたページの構造ツリーを書き出し、ルートのIDと文書の言語を返す
+    ///
+    /// 構造要素はページの順、ページ内ではMCIDの順（読む順序）に文書要素の子として並べる。
+    fn write_structure(&mut self) -> io::Result<Option<(ObjectId, Option<Object>)>> {
+        if self.tagged.is_empty() {
+            return Ok(None);
+        }
+
+        let root_id = self.new_id();
+        let document_id = self.new_id();
+        let tagged = std::mem::take(&mut self.tagged);
+        let lang = tagged
+            .iter()
+            .find_map(|(_, tags)| tags.get(b"Lang").ok().cloned());
+
+        let mut kids = vec![];
+        let mut nums = vec![];
+        let mut elements = vec![];
+        for (index, (page_id, tags)) in tagged.iter().enumerate() {
+            let items = tags
+                .get(b"K")
+                .and_then(Object::as_array)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let mut parents = vec![];
+            for (mcid, item) in items.iter().enumerate() {
+                let Ok(item) = item.as_dict() else {
+                    continue;
+                };
+                let id = self.new_id();
+                let kind = item.get(b"S").cloned().unwrap_or_else(|_| "P".into());
+                let mut element = dictionary! {
+                    "Type" => "StructElem",
+                    "S" => kind.clone(),
+                    "P" => document_id,
+                    "Pg" => *page_id,
+                    "K" => mcid as i64,
+                };
+                if kind.as_name().is_ok_and(|kind| kind == b"Figure") {
+                    if let Ok(text) = item.get(b"Text") {
+                        element.set("Alt", text.clone());
+                    }
+                    if let Ok(bbox) = item.get(b"BBox") {
+                        element.set("A", dictionary! { "O" => "Layout", "BBox" => bbox.clone() });
+                    }
+                }
+                kids.push(Object::Reference(id));
+                parents.push(Object::Reference(id));
+                elements.push((id, element));
+            }
+            nums.extend([Object::Integer(index as i64), Object::Array(parents)]);
+        }
+
+        let root = dictionary! {
+            "Type" => "StructTreeRoot",
+            "K" => document_id,
+            "ParentTree" => dictionary! { "Nums" => nums },
+            "ParentTreeNextKey" => tagged.len() as i64,
+        };
+        self.write_indirect(root_id, &Object::Dictionary(root))?;
+        let document = dictionary! {
+            "Type" => "StructElem",
+            "S" => "Document",
+            "P" => root_id,
+            "K" => kids,
+        };
+        self.write_indirect(document_id, &Object::Dictionary(document))?;
+        for (id, element) in elements {
+            self.write_indirect(id, &Object::Dictionary(element))?;
+        }
+        Ok(Some((root_id, lang)))
+    }
+}
+
+/// PDFの日付文字列（UTC）
+fn pdf_date(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (days, rest) = (seconds / 86400, seconds % 86400);
+    let (hour, minute, second) = (rest / 3600, rest % 3600 / 60, rest % 60);
+
+    // 1970-01-01からの日数をグレゴリオ暦の日付に変換
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!("D:{year:04}{month:02}{day:02}{hour:02}{minute:02}{second:02}Z")
+}
+
+/// 推定サイズの合計が上限を超えないように区切る（1件で上限を超える場合はその1件のみ）
+pub(crate) fn batches(sizes: impl IntoIterator<Item = usize>, limit: usize) -> Vec<Range<usize>> {
+    let mut ranges = vec![];
+    let mut start = 0;
+    let mut total = 0;
+    let mut end = 0;
+    for size in sizes {
+        if end > start && total + size > limit {
+            ranges.push(start..end);
+            start = end;
+            total = 0;
+        }
+        total += size;
+        end += 1;
+    }
+    if end > start {
+        ranges.push(start..end);
+    }
+    ranges
+}
+
+/// ページから辿れるオブジェクト（ページツリーとカタログは除く）
+fn reachable(document: &Document, pages: &[ObjectId]) -> BTreeSet<ObjectId> {
+    let mut visited = BTreeSet::new();
+    let mut stack = pages.to_vec();
+    while let Some(id) = stack.pop() {
+        let Some(object) = document.objects.get(&id) else {
+            continue;
+        };
+        if is_structural(object) || !visited.insert(id) {
+            continue;
+        }
+        collect_references(object, &mut stack);
+    }
+    visited
+}
+
+fn is_structural(object: &Object) -> bool {
+    object.as_dict().is_ok_and(|dict| {
+        dict.has_type(b"Catalog") || dict.has_type(b"Pages") || dict.has_type(b"Outlines")
+    })
+}
+
+fn collect_references(object: &Object, found: &mut Vec<ObjectId>) {
+    match object {
+        Object::Reference(id) => found.push(*id),
+        Object::Array(items) => items
+            .iter()
+            .for_each(|item| collect_references(item, found)),
+        Object::Dictionary(dict) => dict
+            .iter()
+            .for_each(|(_, value)| collect_references(value, found)),
+        Object::Stream(stream) => stream
+            .dict
+            .iter()
+            .for_each(|(_, value)| collect_references(value, found)),
+        _ => {}
+    }
+}
+
+/// 参照を書き出し先のIDに置き換える（書き出さないオブジェクトへの参照はnullにする）
+fn renumber(object: &mut Object, mapping: &BTreeMap<ObjectId, ObjectId>) {
+    match object {
+        Object::Reference(id) => {
+            let new = mapping.get(id).copied();
+            *object = new.map_or(Object::Null, Object::Reference);
+        }
+        Object::Array(items) => items.iter_mut().for_each(|item| renumber(item, mapping)),
+        Object::Dictionary(dict) => dict
+            .iter_mut()
+            .for_each(|(_, value)| renumber(value, mapping)),
+        Object::Stream(stream) => stream
+            .dict
+            .iter_mut()
+            .for_each(|(_, value)| renumber(value, mapping)),
+        _ => {}
+    }
+}
+
+fn write_object(output: &mut impl Write, object: &Object) -> io::Result<()> {
+    match object {
+        Object::Null => output.write_all(b"null"),
+        Object::Boolean(value) => write!(output, "{value}"),
+        Object::Integer(value) => write!(output, "{value}"),
+        Object::Real(value) => write_real(output, f64::from(*value)),
+        Object::Name(name) => write_name(output, name),
+        Object::String(bytes, StringFormat::Literal) => write_literal(output, bytes),
+        Object::String(bytes, StringFormat::Hexadecimal) => {
+            output.write_all(b"<")?;
+            for byte in bytes {
+                write!(output, "{byte:02X}")?;
+            }
+            output.write_all(b">")
+        }
+        Object::Array(items) => {
+            output.write_all(b"[")?;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    output.write_all(b" ")?;
+                }
+                write_object(output, item)?;
+            }
+            output.write_all(b"]")
+        }
+        Object::Dictionary(dict) => write_dictionary(output, dict, None),
+        Object::Stream(stream) => {
+            write_dictionary(output, &stream.dict, Some(stream.content.len()))?;
+            output.write_all(b"\nstream\n")?;
+            output.write_all(&stream.content)?;
+            output.write_all(b"\nendstream")
+        }
+        Object::Reference((id, generation)) => write!(output, "{id} {generation} R"),
+    }
+}
+
+/// 辞書を書き出す（ストリームの場合は実際の長さでLengthを上書きする）
+fn write_dictionary(
+    output: &mut impl Write,
+    dict: &Dictionary,
+    length: Option<usize>,
+) -> io::Result<()> {
+    output.write_all(b"<<")?;
+    for (key, value) in dict.iter() {
+        if length.is_some() && key.as_slice() == b"Length" {
+            continue;
+        }
+        write_name(output, key)?;
+        output.write_all(b" ")?;
+        write_object(output, value)?;
+        output.write_all(b"\n")?;
+    }
+    if let Some(length) = length {
+        write!(output, "/Length {length}")?;
+    }
+    output.write_all(b">>")
+}
+
+fn write_real(output: &mut impl Write, value: f64) -> io::Result<()> {
+    if !value.is_finite() {
+        return output.write_all(b"0");
+    }
+    let formatted = format!("{value:.6}");
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "-0" | "" => output.write_all(b"0"),
+        _ => output.write_all(trimmed.as_bytes()),
+    }
+}
+
+fn write_name(output: &mut impl Write, name: &[u8]) -> io::Result<()> {
+    output.write_all(b"/")?;
+    for &byte in name {
+        if (b'!'..=b'~').contains(&byte) && !b"()<>[]{}/%#".contains(&byte) {
+            output.write_all(&[byte])?;
+        } else {
+            write!(output, "#{byte:02X}")?;
+        }
+    }
+    Ok(())
+}
+
+fn write_literal(output: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    output.write_all(b"(")?;
+    for &byte in bytes {
+        match byte {
+            b'(' | b')' | b'\\' => output.write_all(&[b'\\', byte])?,
+            b'\r' => output.write_all(b"\\r")?,
+            _ => output.write_all(&[byte])?,
+        }
+    }
+    output.write_all(b")")
+}
+
+/// 書き出したバイト数を数え、内容のハッシュを計算するWriter
+struct CountingWriter<W> {
+    inner: W,
+    position: u64,
+    hasher: Sha256,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.position += written as u64;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::models::ProcessedSvg;
+    use crate::testing;
+    use lopdf::Stream;
+
+    /// 1ページのドキュメント（ページツリーにMediaBoxとResourcesを持たせる）
+    fn document(text: &str, logo: &[u8]) -> Document {
+        let mut document = Document::with_version("1.7");
+        let image_id = document.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => 1,
+                "Height" => 1,
+                "ColorSpace" => "DeviceGray",
+                "BitsPerComponent" => 8,
+            },
+            logo.to_vec(),
+        ));
+        let content_id = document.add_object(Stream::new(dictionary! {}, text.as_bytes().to_vec()));
+        let page = dictionary! {
+            "Type" => "Page",
+            "Contents" => content_id,
+        };
+        let mut document = testing::with_pages(document, vec![page]);
+        let pages_id = document
+            .catalog()
+            .unwrap()
+            .get(b"Pages")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        let pages = document.get_dictionary_mut(pages_id).unwrap();
+        pages.set("MediaBox", vec![0.into(), 0.into(), 100.into(), 50.into()]);
+        pages.set(
+            "Resources",
+            dictionary! {
+                "XObject" => dictionary! { "Im0" => image_id },
+            },
+        );
+        document
+    }
+
+    #[test]
+    fn test_stream_documents_with_bookmarks() {
+        let mut writer = PdfWriter::new(vec![], &StreamConfig::default()).unwrap();
+        let first = writer.add_document(document("% a", &[0])).unwrap();
+        let second = writer.add_document(document("% b", &[0])).unwrap();
+        let chapter = writer.add_bookmark("第1章", first[0], None);
+        writer.add_bookmark("(2) 続き", second[0], Some(chapter));
+        let bytes = writer.finish().unwrap();
+
+        let output = Document::load_mem(&bytes).unwrap();
+        let pages = output.get_pages();
+        assert_eq!(pages.len(), 2);
+
+        let images = pages
+            .values()
+            .map(|&page_id| {
+                let page = output.get_dictionary(page_id).unwrap();
+                assert!(page.has(b"MediaBox"));
+                let resources = page.get(b"Resources").unwrap().as_dict().unwrap();
+                let xobjects = resources.get(b"XObject").unwrap().as_dict().unwrap();
+                xobjects.get(b"Im0").unwrap().as_reference().unwrap()
+            })
+            .collect::<BTreeSet<_>>();
+        // 同じ画像は1つにまとめられる
+        assert_eq!(images.len(), 1);
+
+        let catalog = output.catalog().unwrap();
+        let outlines_id = catalog.get(b"Outlines").unwrap().as_reference().unwrap();
+        let outlines = output.get_dictionary(outlines_id).unwrap();
+        assert_eq!(outlines.get(b"Count").unwrap().as_i64().unwrap(), 2);
+        let chapter_id = outlines.get(b"First").unwrap().as_reference().unwrap();
+        let chapter = output.get_dictionary(chapter_id).unwrap();
+        assert!(chapter.has(b"First"));
+    }
+
+    #[test]
+    fn test_structure_tree_from_tagged_pages() {
+        let tagged = |kinds: &[&str]| {
+            let mut document = document("% tagged", &[0]);
+            let page_id = *document.get_pages().values().next().unwrap();
+            let items = kinds
+                .iter()
+                .map(|&kind| {
+                    Object::Dictionary(dictionary! {
+                        "S" => kind,
+                        "Text" => text_string("説明"),
+                        "BBox" => vec![0.into(), 0.into(), 10.into(), 10.into()],
+                    })
+                })
+                .collect::<Vec<_>>();
+            document.get_dictionary_mut(page_id).unwrap().set(
+                tag::TAGS_KEY,
+                dictionary! {
+                    "Lang" => Object::string_literal("ja"),
+                    "K" => items,
+                },
+            );
+            document
+        };
+        let mut writer = PdfWriter::new(vec![], &StreamConfig::default()).unwrap();
+        writer.add_document(document("% plain", &[0])).unwrap();
+        writer.add_document(tagged(&["H1", "Figure"])).unwrap();
+        writer.add_document(tagged(&["P"])).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let output = Document::load_mem(&bytes).unwrap();
+        let catalog = output.catalog().unwrap();
+        assert_eq!(catalog.get(b"Lang").unwrap().as_str().unwrap(), b"ja");
+        let mark_info = catalog.get(b"MarkInfo").unwrap().as_dict().unwrap();
+        assert!(mark_info.get(b"Marked").unwrap().as_bool().unwrap());
+
+        let pages = output.get_pages().into_values().collect::<Vec<_>>();
+        let struct_parents = pages
+            .iter()
+            .map(|&page_id| {
+                let page = output.get_dictionary(page_id).unwrap();
+                assert!(!page.has(tag::TAGS_KEY.as_bytes()));
+                page.get(b"StructParents").and_then(Object::as_i64).ok()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(struct_parents, [None, Some(0), Some(1)]);
+
+        let root_id = catalog
+            .get(b"StructTreeRoot")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        let root = output.get_dictionary(root_id).unwrap();
+        let document_id = root.get(b"K").unwrap().as_reference().unwrap();
+        let elements = output
+            .get_dictionary(document_id)
+            .unwrap()
+            .get(b"K")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|kid| {
+                let element = output.get_dictionary(kid.as_reference().unwrap()).unwrap();
+                (
+                    element.get(b"S").unwrap().as_name().unwrap().to_vec(),
+                    element.get(b"Pg").unwrap().as_reference().unwrap(),
+                    element.get(b"K").unwrap().as_i64().unwrap(),
+                    element.has(b"Alt"),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            elements,
+            vec![
+                (b"H1".to_vec(), pages[1], 0, false),
+                (b"Figure".to_vec(), pages[1], 1, true),
+                (b"P".to_vec(), pages[2], 0, false),
+            ]
+        );
+        let nums = root
+            .get(b"ParentTree")
+            .unwrap()
+            .as_dict()
+            .unwrap()
+            .get(b"Nums")
+            .unwrap()
+            .as_array()
+            .unwrap();
+        assert_eq!(nums.len(), 4);
+    }
+
+    #[test]
+    fn test_embedded_files_from_sources() {
+        let mut writer = PdfWriter::new(vec![], &StreamConfig::default()).unwrap();
+        for text in ["% a", "% b"] {
+            let mut document = document(text, &[0]);
+            let svg = ProcessedSvg::new(format!("<svg>{text}</svg>"), 0);
+            source::attach(&mut document, &svg).unwrap();
+            writer.add_document(document).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+
+        let output = Document::load_mem(&bytes).unwrap();
+        let names = output
+            .catalog()
+            .unwrap()
+            .get(b"Names")
+            .and_then(Object::as_dict)
+            .and_then(|names| names.get(b"EmbeddedFiles"))
+            .and_then(Object::as_dict)
+            .and_then(|files| files.get(b"Names"))
+            .and_then(Object::as_array)
+            .unwrap();
+        // 同じファイル名でもキーは重複しない
+        let keys = names
+            .iter()
+            .step_by(2)
+            .map(|key| key.as_str().unwrap().to_vec())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            vec![
+                b"00000 slide-001.svg".to_vec(),
+                b"00001 slide-001.svg".to_vec()
+            ]
+        );
+
+        // 結合後もページから変換元を辿れる
+        let sources = source::extract_sources(&output, testing::page_key()).unwrap();
+        let contents = sources
+            .svgs
+            .iter()
+            .map(|svg| svg.content.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(contents, vec!["<svg>% a</svg>", "<svg>% b</svg>"]);
+    }
+
+    fn write(documents: &[&str], timestamp: Timestamp) -> Vec<u8> {
+        let config = StreamConfig {
+            timestamp,
+            ..StreamConfig::default()
+        };
+        let mut writer = PdfWriter::new(vec![], &config).unwrap();
+        for (i, text) in documents.iter().enumerate() {
+            let pages = writer.add_document(document(text, &[i as u8])).unwrap();
+            writer.add_bookmark(*text, pages[0], None);
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_output_is_deterministic() {
+        let first = write(&["% a", "% b"], Timestamp::Omit);
+        let second = write(&["% a", "% b"], Timestamp::Omit);
+        assert_eq!(first, second);
+
+        let output = Document::load_mem(&first).unwrap();
+        let info_id = output.trailer.get(b"Info").unwrap().as_reference().unwrap();
+        assert!(!output.get_dictionary(info_id).unwrap().has(b"CreationDate"));
+
+        // 内容が変わればIDも変わる
+        let changed = Document::load_mem(&write(&["% a", "% c"], Timestamp::Omit)).unwrap();
+        let id = |document: &Document| {
+            document.trailer.get(b"ID").unwrap().as_array().unwrap()[0]
+                .as_str()
+                .unwrap()
+                .to_vec()
+        };
+        assert_eq!(id(&output).len(), 16);
+        assert_ne!(id(&output), id(&changed));
+    }
+
+    #[test]
+    fn test_fixed_timestamp() {
+        let time = UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        let bytes = write(&["% a"], Timestamp::At(time));
+        assert_eq!(bytes, write(&["% a"], Timestamp::At(time)));
+
+        let output = Document::load_mem(&bytes).unwrap();
+        let info_id = output.trailer.get(b"Info").unwrap().as_reference().unwrap();
+        let info = output.get_dictionary(info_id).unwrap();
+        assert_eq!(
+            info.get(b"CreationDate").unwrap().as_str().unwrap(),
+            b"D:20231114221320Z"
+        );
+    }
+
+    #[test]
+    fn test_pdf_date() {
+        assert_eq!(pdf_date(UNIX_EPOCH), "D:19700101000000Z");
+        // うるう日
+        let leap_day = UNIX_EPOCH + std::time::Duration::from_secs(951_782_400);
+        assert_eq!(pdf_date(leap_day), "D:20000229000000Z");
+    }
+
+    #[test]
+    fn test_batches() {
+        assert_eq!(batches([3, 3, 3, 10, 1], 6), vec![0..2, 2..3, 3..4, 4..5]);
+        assert_eq!(batches([], 6), Vec::<Range<usize>>::new());
+    }
+
+    #[test]
+    fn test_write_name_and_literal() {
+        let mut output = vec![];
+        write_name(&mut output, b"A B#").unwrap();
+        write_literal(&mut output, b"(x)\\").unwrap();
+        write_real(&mut output, 0.5).unwrap();
+
+        assert_eq!(output, b"/A#20B#23(\\(x\\)\\\\)0.5");
+    }
+}
@@ -0,0 +1,78 @@
+//! テストで共通に使うフィクスチャ
+
+use crate::domain::models::{
+    CourseKey, CourseSlug, LectureKey, LectureSlug, PageKey, PageSlug, Year,
+};
+use lopdf::{dictionary, Dictionary, Document, Object, Stream};
+use std::path::PathBuf;
+use svg2pdf::usvg::fontdb::Database;
+
+/// テスト用のページのキー（`2024/CS101/01/01-1`）
+pub(crate) fn page_key() -> PageKey {
+    PageKey::new(
+        LectureKey::new(
+            CourseKey::new(Year::new(2024).unwrap(), CourseSlug::new("CS101").unwrap()),
+            LectureSlug::new("01").unwrap(),
+        ),
+        PageSlug::new("01-1").unwrap(),
+    )
+}
+
+/// 同じ大きさ（pt）と内容のページを`pages`枚持つドキュメント
+pub(crate) fn document(pages: usize, width: i64, height: i64, content: &[u8]) -> Document {
+    let mut document = Document::with_version("1.7");
+    let pages = (0..pages)
+        .map(|_| page(&mut document, width, height, content))
+        .collect();
+    with_pages(document, pages)
+}
+
+/// 大きさ（pt）と内容だけを持つページ
+pub(crate) fn page(document: &mut Document, width: i64, height: i64, content: &[u8]) -> Dictionary {
+    let content_id = document.add_object(Stream::new(dictionary! {}, content.to_vec()));
+    dictionary! {
+        "Type" => "Page",
+        "MediaBox" => vec![0.into(), 0.into(), width.into(), height.into()],
+        "Contents" => content_id,
+    }
+}
+
+/// `pages`を順にページツリーへ並べ、カタログを加える
+///
+/// 各ページの`Parent`はここで設定する。
+pub(crate) fn with_pages(mut document: Document, pages: Vec<Dictionary>) -> Document {
+    let pages_id = document.new_object_id();
+    let kids = pages
+        .into_iter()
+        .map(|mut page| {
+            page.set("Parent", pages_id);
+            document.add_object(page).into()
+        })
+        .collect::<Vec<Object>>();
+    document.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Count" => kids.len() as i64,
+            "Kids" => kids,
+        }),
+    );
+    let catalog_id = document.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    document.trailer.set("Root", catalog_id);
+    document
+}
+
+/// テスト用のフォントファイル（パブリックドメインのTuffy、ラテン文字のみ）
+pub(crate) fn font_file() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/testdata/Tuffy.ttf")
+}
+
+/// テスト用のフォントだけを読み込んだデータベース
+pub(crate) fn font_database() -> Database {
+    let mut database = Database::new();
+    database.load_font_file(font_file()).unwrap();
+    database
+}